@@ -0,0 +1,215 @@
+//! Benchmarks for the three ingest pipeline stages — parsing, record
+//! building, and write-batch chunking — so performance-oriented changes
+//! can be measured instead of guessed at. Everything runs offline: the
+//! ingestion stage writes to a no-op client. Throughput is reported in
+//! lines (elements) per second, and a one-shot allocation report prints
+//! before the timing runs. Indicative numbers live in
+//! `benches/BASELINE.md`.
+
+use async_trait::async_trait;
+use aws_sdk_timestreamwrite::types::{
+    MagneticStoreWriteProperties, Record, RetentionProperties, Schema, TimeUnit,
+};
+use criterion::{criterion_group, BatchSize, Criterion, Throughput};
+use influxdb_timestream_connector::line_protocol_parser::parse_line_protocol;
+use influxdb_timestream_connector::records_builder;
+use influxdb_timestream_connector::timestream_utils::{
+    ingest_records, split_into_batches, ClientError, TimestreamWriteClient,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// System allocator wrapper counting allocation calls, so each stage's
+/// cost can be reported as allocations per line alongside wall time.
+struct CountingAllocator;
+
+static ALLOCATION_COUNT: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations_during<T>(run: impl FnOnce() -> T) -> (T, u64) {
+    let before = ALLOCATION_COUNT.load(Ordering::Relaxed);
+    let result = run();
+    (result, ALLOCATION_COUNT.load(Ordering::Relaxed) - before)
+}
+
+/// Accepts every call and discards the records, isolating the chunking
+/// and retry bookkeeping in `ingest_records` from any network cost.
+struct NoopWriter;
+
+#[async_trait]
+impl TimestreamWriteClient for NoopWriter {
+    async fn describe_database(&self, _: &str) -> Result<Option<String>, ClientError> {
+        Ok(None)
+    }
+
+    async fn describe_table(&self, _: &str, _: &str) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    async fn create_database(&self, _: &str, _: Option<&str>) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    async fn create_table(
+        &self,
+        _: &str,
+        _: &str,
+        _: RetentionProperties,
+        _: MagneticStoreWriteProperties,
+        _: Option<Schema>,
+    ) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    async fn write_records(&self, _: &str, _: &str, _: Vec<Record>) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    async fn delete_table(&self, _: &str, _: &str) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    async fn delete_database(&self, _: &str) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+/// Generates a line protocol batch spread over `tables` measurements,
+/// with a representative tag and field set and unique timestamps.
+fn line_protocol_batch(lines: usize, tables: usize) -> String {
+    (0..lines)
+        .map(|index| {
+            format!(
+                "readings_{},fleet=Alberta,truck_id=truck_{} fuel=42.5,load=512i,status=\"ok\" {}\n",
+                index % tables,
+                index % 100,
+                1677605771000000000i64 + index as i64
+            )
+        })
+        .collect()
+}
+
+/// Builds the single-table record batch the chunking benchmarks consume.
+fn built_records(lines: usize) -> Vec<Record> {
+    let metrics = parse_line_protocol(&line_protocol_batch(lines, 1))
+        .expect("Failed to parse benchmark batch");
+    records_builder::build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Failed to build benchmark records")
+        .remove("readings_0")
+        .expect("Benchmark batch lost its table")
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_line_protocol");
+    group.sample_size(10);
+    for lines in [1_000usize, 10_000, 100_000] {
+        let body = line_protocol_batch(lines, 8);
+        group.throughput(Throughput::Elements(lines as u64));
+        group.bench_function(format!("{}_lines", lines), |bencher| {
+            bencher.iter(|| parse_line_protocol(&body).expect("Failed to parse"))
+        });
+    }
+    group.finish();
+}
+
+fn bench_build_distributions(c: &mut Criterion) {
+    env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+    let mut group = c.benchmark_group("build_records_distribution");
+    group.sample_size(10);
+    for (name, tables) in [("single_table", 1usize), ("100_tables", 100)] {
+        let metrics = parse_line_protocol(&line_protocol_batch(10_000, tables))
+            .expect("Failed to parse benchmark batch");
+        group.throughput(Throughput::Elements(10_000));
+        group.bench_function(name, |bencher| {
+            bencher.iter_batched(
+                || metrics.clone(),
+                |metrics| {
+                    records_builder::build_records(
+                        metrics,
+                        &TimeUnit::Nanoseconds,
+                        "influxdb-measure",
+                    )
+                    .expect("Failed to build records")
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_chunked_ingestion(c: &mut Criterion) {
+    env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+    let records = built_records(10_000);
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to build runtime");
+
+    let mut group = c.benchmark_group("ingest_chunking");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(records.len() as u64));
+    group.bench_function("split_into_batches", |bencher| {
+        bencher.iter(|| split_into_batches(&records).len())
+    });
+    group.bench_function("ingest_records_noop_writer", |bencher| {
+        bencher.iter(|| {
+            runtime
+                .block_on(ingest_records(&NoopWriter, "db", "readings_0", &records))
+                .expect("Failed to ingest")
+        })
+    });
+    group.finish();
+}
+
+/// One-shot allocation counts per stage, printed before the timing runs.
+/// Criterion cannot chart these, but a before/after comparison of the
+/// printed counts catches clone regressions immediately.
+fn report_allocation_counts() {
+    env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+    let lines = 10_000;
+    let body = line_protocol_batch(lines, 8);
+    let (metrics, parse_allocations) =
+        allocations_during(|| parse_line_protocol(&body).expect("Failed to parse"));
+    let (tables, build_allocations) = allocations_during(|| {
+        records_builder::build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+            .expect("Failed to build records")
+    });
+    let records: Vec<Record> = tables.into_values().flatten().collect();
+    let (batches, chunk_allocations) = allocations_during(|| split_into_batches(&records).len());
+    println!(
+        "allocation counts for {} lines: parse {} ({:.1}/line), build {} ({:.1}/line), \
+        chunk into {} batches {}",
+        lines,
+        parse_allocations,
+        parse_allocations as f64 / lines as f64,
+        build_allocations,
+        build_allocations as f64 / lines as f64,
+        batches,
+        chunk_allocations
+    );
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_build_distributions,
+    bench_chunked_ingestion
+);
+
+fn main() {
+    report_allocation_counts();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,154 @@
+use aws_sdk_timestreamwrite::types::TimeUnit;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use influxdb_timestream_connector::metric::{FieldValue, Metric};
+use influxdb_timestream_connector::records_builder;
+use std::env;
+
+/// Builds a representative batch: several measurements, each point with a
+/// handful of tags and fields, unique timestamps so deduplication is a
+/// no-op.
+fn sample_metrics(count: usize) -> Vec<Metric> {
+    (0..count)
+        .map(|index| {
+            Metric::new(
+                format!("readings_{}", index % 8),
+                Some(vec![
+                    ("fleet".to_string(), "Alberta".to_string()),
+                    ("truck_id".to_string(), format!("truck_{}", index % 100)),
+                    ("model".to_string(), "GV80".to_string()),
+                ]),
+                vec![
+                    ("fuel".to_string(), FieldValue::F64(42.5)),
+                    ("load".to_string(), FieldValue::F64(512.0)),
+                    ("speed".to_string(), FieldValue::I64(80)),
+                    ("status".to_string(), FieldValue::String("ok".to_string())),
+                ],
+                1677605771000000000 + index as i64,
+            )
+        })
+        .collect()
+}
+
+fn bench_build_records(c: &mut Criterion) {
+    env::set_var(
+        "measure_name_for_multi_measure_records",
+        "influxdb-measure",
+    );
+    let metrics = sample_metrics(5000);
+
+    let mut group = c.benchmark_group("build_records");
+    group.bench_function("by_value", |bencher| {
+        bencher.iter_batched(
+            || metrics.clone(),
+            |metrics| {
+                records_builder::build_records(
+                    metrics,
+                    &TimeUnit::Nanoseconds,
+                    "influxdb-measure",
+                )
+                .expect("Failed to build records")
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("borrowed", |bencher| {
+        bencher.iter(|| {
+            records_builder::build_records_borrowed(
+                &metrics,
+                &TimeUnit::Nanoseconds,
+                "influxdb-measure",
+            )
+            .expect("Failed to build records")
+        })
+    });
+    group.finish();
+}
+
+/// Compares the sequential and rayon-parallel build paths on a batch
+/// large enough to clear `PARALLEL_BUILD_THRESHOLD`.
+fn bench_parallel_build(c: &mut Criterion) {
+    env::set_var(
+        "measure_name_for_multi_measure_records",
+        "influxdb-measure",
+    );
+    let metrics = sample_metrics(50_000);
+
+    let mut group = c.benchmark_group("build_records_50k");
+    group.sample_size(10);
+    group.bench_function("sequential", |bencher| {
+        bencher.iter_batched(
+            || metrics.clone(),
+            |metrics| {
+                records_builder::build_multi_measure_records_with_threshold(
+                    metrics,
+                    &TimeUnit::Nanoseconds,
+                    "influxdb-measure",
+                    usize::MAX,
+                )
+                .expect("Failed to build records")
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("parallel", |bencher| {
+        bencher.iter_batched(
+            || metrics.clone(),
+            |metrics| {
+                records_builder::build_multi_measure_records_with_threshold(
+                    metrics,
+                    &TimeUnit::Nanoseconds,
+                    "influxdb-measure",
+                    0,
+                )
+                .expect("Failed to build records")
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Exercises the per-table grouping on a wide batch: 1000 measurements
+/// with 100 points each.
+fn bench_grouping(c: &mut Criterion) {
+    env::set_var(
+        "measure_name_for_multi_measure_records",
+        "influxdb-measure",
+    );
+    let metrics: Vec<Metric> = (0..100_000)
+        .map(|index| {
+            Metric::new(
+                format!("readings_{}", index % 1000),
+                Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+                vec![("fuel".to_string(), FieldValue::F64(42.5))],
+                1677605771000000000 + index as i64,
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("build_records_1000x100");
+    group.sample_size(10);
+    group.bench_function("grouping", |bencher| {
+        bencher.iter_batched(
+            || metrics.clone(),
+            |metrics| {
+                records_builder::build_records(
+                    metrics,
+                    &TimeUnit::Nanoseconds,
+                    "influxdb-measure",
+                )
+                .expect("Failed to build records")
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_build_records,
+    bench_parallel_build,
+    bench_grouping
+);
+criterion_main!(benches);
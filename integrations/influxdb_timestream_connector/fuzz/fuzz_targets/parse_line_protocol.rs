@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// The parser must return an error, never panic, on arbitrary input.
+// Run with `cargo +nightly fuzz run parse_line_protocol`.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(body) = std::str::from_utf8(data) {
+        let _ = influxdb_timestream_connector::line_protocol_parser::parse_line_protocol(body);
+    }
+});
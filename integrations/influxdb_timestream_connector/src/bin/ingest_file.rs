@@ -0,0 +1,409 @@
+//! CLI for bulk-ingesting an InfluxDB line protocol export file into
+//! Timestream, bypassing the Lambda payload limits. The file is streamed
+//! line by line through the connector's normal parse/build/ingest
+//! pipeline in bounded concurrent batches.
+
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_timestreamwrite::types::TimeUnit;
+use flate2::read::GzDecoder;
+use futures::stream::{FuturesUnordered, StreamExt};
+use influxdb_timestream_connector::{
+    ingest_line_protocol, records_builder, timestream_utils, ConnectorConfig,
+};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// How often ingestion progress is reported.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "Usage: ingest-file --file <path> [--precision ns|us|ms|s] \
+[--database <name>] [--batch-lines <n>] [--concurrency <n>] [--skip-lines <n>]
+
+Ingests an InfluxDB line protocol file (optionally gzip-compressed) into
+Timestream using the connector's configuration environment variables.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IngestFileArgs {
+    file: PathBuf,
+    precision: String,
+    database: Option<String>,
+    batch_lines: usize,
+    concurrency: usize,
+    skip_lines: usize,
+}
+
+fn parse_args(args: &[String]) -> Result<IngestFileArgs> {
+    let mut file: Option<PathBuf> = None;
+    let mut precision = "ns".to_string();
+    let mut database: Option<String> = None;
+    let mut batch_lines: usize = 1000;
+    let mut concurrency: usize = 4;
+    let mut skip_lines: usize = 0;
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value_of = |flag: &str| {
+            iter.next()
+                .ok_or_else(|| anyhow!("{} requires a value", flag))
+        };
+        match flag.as_str() {
+            "--file" => file = Some(PathBuf::from(value_of("--file")?)),
+            "--precision" => {
+                let value = value_of("--precision")?;
+                if !matches!(value.as_str(), "ns" | "us" | "ms" | "s") {
+                    return Err(anyhow!(
+                        "--precision must be one of ns, us, ms, s, got {}",
+                        value
+                    ));
+                }
+                precision = value.to_string();
+            }
+            "--database" => database = Some(value_of("--database")?.to_string()),
+            "--batch-lines" => {
+                batch_lines = value_of("--batch-lines")?
+                    .parse()
+                    .context("--batch-lines must be a positive integer")?;
+                if batch_lines == 0 {
+                    return Err(anyhow!("--batch-lines must be at least 1"));
+                }
+            }
+            "--concurrency" => {
+                concurrency = value_of("--concurrency")?
+                    .parse()
+                    .context("--concurrency must be a positive integer")?;
+                if concurrency == 0 {
+                    return Err(anyhow!("--concurrency must be at least 1"));
+                }
+            }
+            "--skip-lines" => {
+                skip_lines = value_of("--skip-lines")?
+                    .parse()
+                    .context("--skip-lines must be a non-negative integer")?;
+            }
+            other => return Err(anyhow!("Unrecognized argument: {}\n\n{}", other, USAGE)),
+        }
+    }
+
+    Ok(IngestFileArgs {
+        file: file.ok_or_else(|| anyhow!("--file is required\n\n{}", USAGE))?,
+        precision,
+        database,
+        batch_lines,
+        concurrency,
+        skip_lines,
+    })
+}
+
+/// Opens the input file, transparently decompressing gzip input detected
+/// by its magic bytes.
+fn open_input(path: &PathBuf) -> Result<Box<dyn Read>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut magic = [0u8; 2];
+    let bytes_read = file.read(&mut magic)?;
+    use std::io::Seek;
+    file.rewind()?;
+    if bytes_read == 2 && magic == [0x1f, 0x8b] {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Reads line protocol batches of up to `batch_lines` non-empty lines,
+/// tagging each batch with the 1-based line number of its first line.
+struct BatchReader<R: BufRead> {
+    reader: R,
+    batch_lines: usize,
+    next_line_number: usize,
+    lines_skipped: usize,
+    skip_remaining: usize,
+}
+
+impl<R: BufRead> BatchReader<R> {
+    fn new(reader: R, batch_lines: usize, skip_lines: usize) -> Self {
+        BatchReader {
+            reader,
+            batch_lines,
+            next_line_number: 1,
+            lines_skipped: 0,
+            skip_remaining: skip_lines,
+        }
+    }
+
+    fn next_batch(&mut self) -> Result<Option<(usize, String)>> {
+        let mut batch = String::new();
+        let mut batch_size = 0;
+        let mut first_line_number = 0;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line_number = self.next_line_number;
+            self.next_line_number += 1;
+            if self.skip_remaining > 0 {
+                self.skip_remaining -= 1;
+                self.lines_skipped += 1;
+                continue;
+            }
+            let trimmed = line.trim_end_matches(['\n', '\r']);
+            if trimmed.is_empty() {
+                continue;
+            }
+            if batch_size == 0 {
+                first_line_number = line_number;
+            } else {
+                batch.push('\n');
+            }
+            batch.push_str(trimmed);
+            batch_size += 1;
+            if batch_size == self.batch_lines {
+                break;
+            }
+        }
+        if batch_size == 0 {
+            Ok(None)
+        } else {
+            Ok(Some((first_line_number, batch)))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct IngestFileSummary {
+    lines_ingested: usize,
+    lines_skipped: usize,
+    records_written: usize,
+    /// First line number and error message of each failed batch.
+    failed_batches: Vec<(usize, String)>,
+}
+
+async fn run_ingestion(
+    client: &Arc<aws_sdk_timestreamwrite::Client>,
+    config: &ConnectorConfig,
+    args: &IngestFileArgs,
+    precision: TimeUnit,
+) -> Result<IngestFileSummary> {
+    let reader = BufReader::new(open_input(&args.file)?);
+    let mut batches = BatchReader::new(reader, args.batch_lines, args.skip_lines);
+
+    let semaphore = Arc::new(Semaphore::new(args.concurrency));
+    let mut tasks = FuturesUnordered::new();
+    let mut summary = IngestFileSummary::default();
+    let started = Instant::now();
+    let mut last_progress = Instant::now();
+
+    loop {
+        let batch = batches.next_batch()?;
+        let Some((first_line_number, batch)) = batch else {
+            break;
+        };
+        let permit = Arc::clone(&semaphore).acquire_owned().await?;
+        let client = Arc::clone(client);
+        let config = config.clone();
+        let precision = precision.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = permit;
+            let result = ingest_line_protocol(&client, &config, &batch, &precision).await;
+            (first_line_number, result)
+        }));
+
+        // Drain completed tasks without blocking so the reader keeps up.
+        while tasks.len() >= args.concurrency {
+            if let Some(task) = tasks.next().await {
+                record_task_result(&mut summary, task?);
+            }
+        }
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            let elapsed = started.elapsed().as_secs_f64();
+            println!(
+                "Ingested {} lines ({:.0} lines/sec), {} records written, {} failed batches",
+                summary.lines_ingested,
+                summary.lines_ingested as f64 / elapsed,
+                summary.records_written,
+                summary.failed_batches.len()
+            );
+            last_progress = Instant::now();
+        }
+    }
+
+    while let Some(task) = tasks.next().await {
+        record_task_result(&mut summary, task?);
+    }
+    summary.lines_skipped = batches.lines_skipped;
+    Ok(summary)
+}
+
+fn record_task_result(
+    summary: &mut IngestFileSummary,
+    (first_line_number, result): (
+        usize,
+        Result<influxdb_timestream_connector::IngestionSummary>,
+    ),
+) {
+    match result {
+        Ok(ingestion_summary) => {
+            summary.lines_ingested += ingestion_summary.lines_parsed;
+            summary.records_written += ingestion_summary.records_written;
+        }
+        Err(error) => {
+            summary
+                .failed_batches
+                .push((first_line_number, error.to_string()));
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = parse_args(&env::args().skip(1).collect::<Vec<_>>())?;
+
+    records_builder::validate_all_env_variables()?;
+    let mut config = ConnectorConfig::from_env()?;
+    if let Some(database) = &args.database {
+        config.database_name = database.clone();
+    }
+    let precision = match args.precision.as_str() {
+        "ms" => TimeUnit::Milliseconds,
+        "us" => TimeUnit::Microseconds,
+        "s" => TimeUnit::Seconds,
+        _ => TimeUnit::Nanoseconds,
+    };
+
+    let region = timestream_utils::resolve_region()?;
+    let client = timestream_utils::get_or_init_connection(&region, None).await?;
+
+    let summary = run_ingestion(&client, &config, &args, precision).await?;
+    println!(
+        "Done: {} lines ingested, {} lines skipped, {} records written, {} failed batches",
+        summary.lines_ingested,
+        summary.lines_skipped,
+        summary.records_written,
+        summary.failed_batches.len()
+    );
+    if !summary.failed_batches.is_empty() {
+        for (first_line_number, error) in &summary.failed_batches {
+            eprintln!("Batch starting at line {}: {}", first_line_number, error);
+        }
+        eprintln!(
+            "{} batches failed; re-run with --skip-lines to resume after the last \
+            successfully ingested line",
+            summary.failed_batches.len()
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_full() {
+        let parsed = parse_args(&args(&[
+            "--file",
+            "export.lp",
+            "--precision",
+            "ms",
+            "--database",
+            "migration_db",
+            "--batch-lines",
+            "500",
+            "--concurrency",
+            "8",
+            "--skip-lines",
+            "1000",
+        ]))
+        .expect("Failed to parse valid arguments");
+        assert_eq!(
+            parsed,
+            IngestFileArgs {
+                file: PathBuf::from("export.lp"),
+                precision: "ms".to_string(),
+                database: Some("migration_db".to_string()),
+                batch_lines: 500,
+                concurrency: 8,
+                skip_lines: 1000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_defaults() {
+        let parsed = parse_args(&args(&["--file", "export.lp"]))
+            .expect("Failed to parse minimal arguments");
+        assert_eq!(parsed.precision, "ns");
+        assert_eq!(parsed.database, None);
+        assert_eq!(parsed.batch_lines, 1000);
+        assert_eq!(parsed.concurrency, 4);
+        assert_eq!(parsed.skip_lines, 0);
+    }
+
+    #[test]
+    fn test_parse_args_rejects_invalid() {
+        assert!(parse_args(&args(&[])).is_err(), "--file is required");
+        assert!(parse_args(&args(&["--file", "x", "--precision", "weeks"])).is_err());
+        assert!(parse_args(&args(&["--file", "x", "--batch-lines", "0"])).is_err());
+        assert!(parse_args(&args(&["--file", "x", "--unknown"])).is_err());
+    }
+
+    #[test]
+    fn test_batch_reader_batches_and_line_numbers() {
+        let input = "line1\nline2\n\nline3\nline4\nline5\n";
+        let mut reader = BatchReader::new(input.as_bytes(), 2, 0);
+        assert_eq!(
+            reader.next_batch().unwrap(),
+            Some((1, "line1\nline2".to_string()))
+        );
+        // The blank line 3 is skipped without ending up in a batch.
+        assert_eq!(
+            reader.next_batch().unwrap(),
+            Some((4, "line3\nline4".to_string()))
+        );
+        assert_eq!(reader.next_batch().unwrap(), Some((6, "line5".to_string())));
+        assert_eq!(reader.next_batch().unwrap(), None);
+    }
+
+    #[test]
+    fn test_batch_reader_skip_lines_resumption() {
+        let input = "line1\nline2\nline3\nline4\n";
+        let mut reader = BatchReader::new(input.as_bytes(), 10, 2);
+        assert_eq!(
+            reader.next_batch().unwrap(),
+            Some((3, "line3\nline4".to_string()))
+        );
+        assert_eq!(reader.lines_skipped, 2);
+    }
+
+    #[test]
+    fn test_open_input_transparently_decompresses_gzip() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("ingest_file_test.lp.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"readings fuel=30i 1677605771000000000\n").unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let mut contents = String::new();
+        open_input(&path)
+            .expect("Failed to open gzip input")
+            .read_to_string(&mut contents)
+            .expect("Failed to decompress gzip input");
+        assert_eq!(contents, "readings fuel=30i 1677605771000000000\n");
+        std::fs::remove_file(&path).ok();
+    }
+}
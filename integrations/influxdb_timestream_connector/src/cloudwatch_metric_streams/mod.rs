@@ -0,0 +1,94 @@
+use crate::metric::{FieldValue, Metric};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+
+#[cfg(test)]
+mod tests;
+
+/// One metric in the CloudWatch Metric Streams JSON format: Firehose
+/// records carry one such object per line.
+#[derive(Deserialize)]
+struct MetricStreamRecord {
+    namespace: String,
+    metric_name: String,
+    #[serde(default)]
+    dimensions: BTreeMap<String, String>,
+    /// Epoch milliseconds.
+    timestamp: i64,
+    value: MetricStreamValue,
+}
+
+/// The statistic set CloudWatch streams for each metric.
+#[derive(Deserialize)]
+struct MetricStreamValue {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: f64,
+}
+
+/// Returns the optional namespace allowlist from the
+/// `metric_stream_namespace_allowlist` environment variable, a
+/// comma-separated list of CloudWatch namespaces (e.g. `AWS/EC2,AWS/RDS`).
+/// When set, metrics from other namespaces are skipped.
+pub fn namespace_allowlist() -> Option<Vec<String>> {
+    let allowlist = env::var("metric_stream_namespace_allowlist").ok()?;
+    Some(
+        allowlist
+            .split(',')
+            .map(str::trim)
+            .filter(|namespace| !namespace.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Parses the decoded data of one Firehose record in the CloudWatch
+/// Metric Streams JSON format (one JSON object per line) into metrics.
+/// The namespace and metric name form the measurement, dimensions become
+/// tags, and the min/max/sum/count statistics become fields. Timestamps
+/// are epoch milliseconds.
+pub fn parse_metric_stream_data(data: &str) -> Result<Vec<Metric>> {
+    let allowlist = namespace_allowlist();
+    let mut metrics: Vec<Metric> = Vec::new();
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: MetricStreamRecord = serde_json::from_str(line)
+            .with_context(|| format!("Metric stream line {}", index + 1))?;
+        if let Some(allowlist) = &allowlist {
+            if !allowlist.contains(&record.namespace) {
+                continue;
+            }
+        }
+        metrics.push(metric_stream_record_to_metric(record)?);
+    }
+    Ok(metrics)
+}
+
+fn metric_stream_record_to_metric(record: MetricStreamRecord) -> Result<Metric> {
+    // CloudWatch namespaces contain slashes (AWS/EC2), which are not valid
+    // in Timestream table names.
+    let name = format!(
+        "{}_{}",
+        record.namespace.replace('/', "_"),
+        record.metric_name
+    );
+    let tags = if record.dimensions.is_empty() {
+        None
+    } else {
+        Some(record.dimensions.into_iter().collect())
+    };
+    let fields = vec![
+        ("count".to_string(), FieldValue::F64(record.value.count)),
+        ("max".to_string(), FieldValue::F64(record.value.max)),
+        ("min".to_string(), FieldValue::F64(record.value.min)),
+        ("sum".to_string(), FieldValue::F64(record.value.sum)),
+    ];
+    let metric = Metric::new(name, tags, fields, record.timestamp);
+    metric.validate()?;
+    Ok(metric)
+}
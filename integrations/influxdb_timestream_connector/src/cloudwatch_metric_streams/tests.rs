@@ -0,0 +1,74 @@
+use super::*;
+use crate::test_support::EnvVarGuard;
+
+const SAMPLE_LINE: &str = r#"{"metric_stream_name":"grafana","account_id":"123456789012","region":"us-east-1","namespace":"AWS/EC2","metric_name":"CPUUtilization","dimensions":{"InstanceId":"i-0123456789abcdef0"},"timestamp":1677605771000,"value":{"max":12.5,"min":1.5,"sum":28.0,"count":4.0},"unit":"Percent"}"#;
+
+#[test]
+fn test_parse_metric_stream_line() {
+    let mut env_vars = EnvVarGuard::acquire();
+    env_vars.remove("metric_stream_namespace_allowlist");
+    let metrics =
+        parse_metric_stream_data(SAMPLE_LINE).expect("Failed to parse metric stream data");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].name(), "AWS_EC2_CPUUtilization");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![(
+            "InstanceId".to_string(),
+            "i-0123456789abcdef0".to_string()
+        )])
+    );
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![
+            ("count".to_string(), FieldValue::F64(4.0)),
+            ("max".to_string(), FieldValue::F64(12.5)),
+            ("min".to_string(), FieldValue::F64(1.5)),
+            ("sum".to_string(), FieldValue::F64(28.0)),
+        ]
+    );
+    assert_eq!(metrics[0].timestamp(), 1677605771000);
+}
+
+#[test]
+fn test_parse_multiple_lines_skips_blank_lines() {
+    let mut env_vars = EnvVarGuard::acquire();
+    env_vars.remove("metric_stream_namespace_allowlist");
+    let data = format!("{}\n\n{}\n", SAMPLE_LINE, SAMPLE_LINE);
+    let metrics =
+        parse_metric_stream_data(&data).expect("Failed to parse metric stream data");
+    assert_eq!(metrics.len(), 2);
+}
+
+#[test]
+fn test_namespace_allowlist_filters_and_admits() {
+    let rds_line = SAMPLE_LINE.replace("AWS/EC2", "AWS/RDS");
+    let data = format!("{}\n{}", SAMPLE_LINE, rds_line);
+
+    let mut env_vars = EnvVarGuard::acquire();
+    env_vars.set("metric_stream_namespace_allowlist", "AWS/RDS, AWS/Lambda");
+    let metrics =
+        parse_metric_stream_data(&data).expect("Failed to parse metric stream data");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].name(), "AWS_RDS_CPUUtilization");
+
+    env_vars.set("metric_stream_namespace_allowlist", "AWS/Lambda");
+    let metrics =
+        parse_metric_stream_data(&data).expect("Failed to parse metric stream data");
+    assert!(metrics.is_empty());
+
+    env_vars.remove("metric_stream_namespace_allowlist");
+    let metrics =
+        parse_metric_stream_data(&data).expect("Failed to parse metric stream data");
+    assert_eq!(metrics.len(), 2);
+}
+
+#[test]
+fn test_malformed_line_reports_line_number() {
+    let mut env_vars = EnvVarGuard::acquire();
+    env_vars.remove("metric_stream_namespace_allowlist");
+    let data = format!("{}\nnot json", SAMPLE_LINE);
+    let error = parse_metric_stream_data(&data)
+        .expect_err("Malformed metric stream line must be rejected");
+    assert!(format!("{:#}", error).contains("Metric stream line 2"));
+}
@@ -0,0 +1,159 @@
+use crate::metric::{FieldValue, Metric};
+use anyhow::{anyhow, Context, Result};
+use std::env;
+
+#[cfg(test)]
+mod tests;
+
+/// Maps CSV header columns onto the parts of a metric. The measurement
+/// and timestamp columns are required in the header; the listed tag
+/// columns become tags and every remaining column becomes a field.
+pub struct CsvColumnMapping {
+    pub measurement_column: String,
+    pub time_column: String,
+    pub tag_columns: Vec<String>,
+}
+
+impl CsvColumnMapping {
+    /// Resolves the mapping from a query parameter lookup, falling back
+    /// to the `csv_measurement_column`, `csv_time_column`, and
+    /// `csv_tag_columns` environment variables, then to the defaults
+    /// `measurement`, `time`, and no tag columns.
+    pub fn resolve(query_parameter: impl Fn(&str) -> Option<String>) -> Self {
+        let setting = |name: &str| query_parameter(name).or_else(|| env::var(name).ok());
+        CsvColumnMapping {
+            measurement_column: setting("csv_measurement_column")
+                .unwrap_or_else(|| "measurement".to_string()),
+            time_column: setting("csv_time_column").unwrap_or_else(|| "time".to_string()),
+            tag_columns: setting("csv_tag_columns")
+                .map(|tag_columns| {
+                    tag_columns
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|column| !column.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Parses a CSV body with a header row into metrics. Timestamps are kept
+/// in the request's time unit, like line protocol. In strict mode
+/// (`skip_invalid_lines` false) the first invalid row fails the batch; in
+/// lenient mode invalid rows are skipped and returned as warnings, both
+/// reporting the 1-based row number (the header is row 1).
+pub fn parse_csv_metrics(
+    body: &str,
+    mapping: &CsvColumnMapping,
+    skip_invalid_lines: bool,
+) -> Result<(Vec<Metric>, Vec<String>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let headers = reader
+        .headers()
+        .context("Failed to read the CSV header row")?
+        .clone();
+    let measurement_index = column_index(&headers, &mapping.measurement_column)?;
+    let time_index = column_index(&headers, &mapping.time_column)?;
+    let tag_indexes = mapping
+        .tag_columns
+        .iter()
+        .map(|column| column_index(&headers, column))
+        .collect::<Result<Vec<usize>>>()?;
+
+    let mut metrics: Vec<Metric> = Vec::new();
+    let mut skipped_rows: Vec<String> = Vec::new();
+    // Row 1 is the header, so data rows start at row 2.
+    for (index, row) in reader.records().enumerate() {
+        let row_number = index + 2;
+        let metric = row
+            .map_err(anyhow::Error::from)
+            .and_then(|row| {
+                row_to_metric(&headers, &row, measurement_index, time_index, &tag_indexes)
+            })
+            .with_context(|| format!("Row {}", row_number));
+        match metric {
+            Ok(metric) => metrics.push(metric),
+            Err(error) if skip_invalid_lines => {
+                let warning = format!("{:#}", error);
+                tracing::warn!("Skipping invalid CSV row: {}", warning);
+                skipped_rows.push(warning);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    Ok((metrics, skipped_rows))
+}
+
+fn column_index(headers: &csv::StringRecord, column: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|header| header == column)
+        .ok_or_else(|| anyhow!("CSV header has no {} column", column))
+}
+
+fn row_to_metric(
+    headers: &csv::StringRecord,
+    row: &csv::StringRecord,
+    measurement_index: usize,
+    time_index: usize,
+    tag_indexes: &[usize],
+) -> Result<Metric> {
+    let measurement = row
+        .get(measurement_index)
+        .filter(|measurement| !measurement.is_empty())
+        .ok_or_else(|| anyhow!("Row has no measurement value"))?;
+    let timestamp = row
+        .get(time_index)
+        .ok_or_else(|| anyhow!("Row has no timestamp value"))?;
+    let timestamp: i64 = timestamp
+        .parse()
+        .with_context(|| format!("Timestamp '{}' is not an integer", timestamp))?;
+
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut fields: Vec<(String, FieldValue)> = Vec::new();
+    for (column_index, value) in row.iter().enumerate() {
+        if column_index == measurement_index || column_index == time_index {
+            continue;
+        }
+        let Some(column) = headers.get(column_index) else {
+            return Err(anyhow!("Row has more columns than the header"));
+        };
+        // Empty cells mean the row has no value for that column.
+        if value.is_empty() {
+            continue;
+        }
+        if tag_indexes.contains(&column_index) {
+            tags.push((column.to_string(), value.to_string()));
+        } else {
+            fields.push((column.to_string(), infer_field_value(value)));
+        }
+    }
+
+    let tags = if tags.is_empty() { None } else { Some(tags) };
+    let metric = Metric::new(measurement.to_string(), tags, fields, timestamp);
+    metric.validate()?;
+    Ok(metric)
+}
+
+/// Infers a field value's type from its CSV text: integer, then float,
+/// then boolean, with everything else kept as a string.
+fn infer_field_value(value: &str) -> FieldValue {
+    if let Ok(value) = value.parse::<i64>() {
+        return FieldValue::I64(value);
+    }
+    if let Ok(value) = value.parse::<u64>() {
+        return FieldValue::U64(value);
+    }
+    if let Ok(value) = value.parse::<f64>() {
+        return FieldValue::F64(value);
+    }
+    match value {
+        "true" | "True" | "TRUE" => FieldValue::Boolean(true),
+        "false" | "False" | "FALSE" => FieldValue::Boolean(false),
+        _ => FieldValue::String(value.to_string()),
+    }
+}
@@ -0,0 +1,130 @@
+use super::*;
+
+fn test_mapping() -> CsvColumnMapping {
+    CsvColumnMapping {
+        measurement_column: "measurement".to_string(),
+        time_column: "time".to_string(),
+        tag_columns: vec!["fleet".to_string()],
+    }
+}
+
+#[test]
+fn test_parse_basic_rows() {
+    let body = "measurement,time,fleet,fuel,status\n\
+        readings,1677605771000000000,Alberta,30,active\n\
+        readings,1677605772000000000,Zurich,40.5,idle\n";
+    let (metrics, skipped) = parse_csv_metrics(body, &test_mapping(), false)
+        .expect("Failed to parse CSV body");
+    assert!(skipped.is_empty());
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].name(), "readings");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![("fleet".to_string(), "Alberta".to_string())])
+    );
+    // Numeric cells are inferred as integers or floats; everything else
+    // stays a string.
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![
+            ("fuel".to_string(), FieldValue::I64(30)),
+            ("status".to_string(), FieldValue::String("active".to_string())),
+        ]
+    );
+    assert_eq!(
+        metrics[1].fields(),
+        &vec![
+            ("fuel".to_string(), FieldValue::F64(40.5)),
+            ("status".to_string(), FieldValue::String("idle".to_string())),
+        ]
+    );
+    assert_eq!(metrics[0].timestamp(), 1677605771000000000);
+}
+
+#[test]
+fn test_parse_quoted_field_with_commas() {
+    let body = "measurement,time,note\n\
+        readings,1677605771000000000,\"stopped, then restarted\"\n";
+    let mapping = CsvColumnMapping {
+        tag_columns: Vec::new(),
+        ..test_mapping()
+    };
+    let (metrics, _) =
+        parse_csv_metrics(body, &mapping, false).expect("Failed to parse CSV body");
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![(
+            "note".to_string(),
+            FieldValue::String("stopped, then restarted".to_string())
+        )]
+    );
+}
+
+#[test]
+fn test_missing_mapped_column_is_rejected() {
+    let body = "name,time,fuel\nreadings,1677605771000000000,30\n";
+    let error = parse_csv_metrics(body, &test_mapping(), false)
+        .expect_err("A header without the measurement column must be rejected");
+    assert!(error.to_string().contains("measurement"));
+}
+
+#[test]
+fn test_invalid_row_reports_row_number() {
+    let body = "measurement,time,fuel\n\
+        readings,1677605771000000000,30\n\
+        readings,not-a-timestamp,31\n";
+    let mapping = CsvColumnMapping {
+        tag_columns: Vec::new(),
+        ..test_mapping()
+    };
+    let error = parse_csv_metrics(body, &mapping, false)
+        .expect_err("A row with a bad timestamp must be rejected in strict mode");
+    assert!(format!("{:#}", error).contains("Row 3"));
+}
+
+#[test]
+fn test_lenient_mode_skips_invalid_rows() {
+    let body = "measurement,time,fuel\n\
+        readings,1677605771000000000,30\n\
+        readings,not-a-timestamp,31\n\
+        readings,1677605773000000000,32\n";
+    let mapping = CsvColumnMapping {
+        tag_columns: Vec::new(),
+        ..test_mapping()
+    };
+    let (metrics, skipped) = parse_csv_metrics(body, &mapping, true)
+        .expect("Lenient mode must keep the valid rows");
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(skipped.len(), 1);
+    assert!(skipped[0].contains("Row 3"));
+}
+
+#[test]
+fn test_empty_cells_are_skipped() {
+    let body = "measurement,time,fleet,fuel,status\n\
+        readings,1677605771000000000,,30,\n";
+    let (metrics, _) = parse_csv_metrics(body, &test_mapping(), false)
+        .expect("Failed to parse CSV body");
+    assert_eq!(metrics[0].tags(), &None);
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![("fuel".to_string(), FieldValue::I64(30))]
+    );
+}
+
+#[test]
+fn test_mapping_resolution_precedence() {
+    let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+    env_vars.remove("csv_measurement_column");
+    env_vars.set("csv_time_column", "ts");
+    let mapping = CsvColumnMapping::resolve(|name| match name {
+        "csv_tag_columns" => Some("fleet, region".to_string()),
+        _ => None,
+    });
+    assert_eq!(mapping.measurement_column, "measurement");
+    assert_eq!(mapping.time_column, "ts");
+    assert_eq!(
+        mapping.tag_columns,
+        vec!["fleet".to_string(), "region".to_string()]
+    );
+}
@@ -0,0 +1,53 @@
+//! Typed errors for the connector's public API.
+
+use crate::timestream_utils::ClientError;
+
+/// Errors returned by the connector's public entry points, so library
+/// consumers can branch on the failure class instead of matching message
+/// strings.
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectorError {
+    /// A payload line failed to parse. `line` is 1-based and `content`
+    /// echoes the offending line, truncated for readability.
+    #[error("Line {line}: '{content}': {message}")]
+    Parse {
+        line: usize,
+        content: String,
+        message: String,
+    },
+    /// A parsed value cannot be represented as a Timestream record.
+    #[error("{0}")]
+    Validation(String),
+    /// The connector configuration is missing or invalid.
+    #[error("{0}")]
+    Configuration(String),
+    /// Creating a table failed.
+    #[error("Failed to create table {table}: {source}")]
+    TableCreation { table: String, source: ClientError },
+    /// Writing records to a table failed.
+    #[error("Failed to write records to table {table}: {source}")]
+    Ingestion { table: String, source: ClientError },
+    /// Any other Timestream API failure.
+    #[error(transparent)]
+    Aws(#[from] ClientError),
+}
+
+impl ConnectorError {
+    /// Recovers a typed error that crossed an `anyhow` boundary, falling
+    /// back to `Validation` with the full context chain for untyped
+    /// errors.
+    pub fn from_anyhow(error: anyhow::Error) -> Self {
+        match error.downcast::<ConnectorError>() {
+            Ok(error) => error,
+            Err(error) => ConnectorError::Validation(format!("{:#}", error)),
+        }
+    }
+
+    pub(crate) fn configuration(error: anyhow::Error) -> Self {
+        ConnectorError::Configuration(format!("{:#}", error))
+    }
+
+    pub(crate) fn validation(error: anyhow::Error) -> Self {
+        ConnectorError::Validation(format!("{:#}", error))
+    }
+}
@@ -0,0 +1,66 @@
+use crate::metric::Metric;
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_timestreamwrite::types::TimeUnit;
+use serde_json::Value;
+
+#[cfg(test)]
+mod tests;
+
+/// Parses a JSON array of metric objects into owned `Metric`s. Each
+/// element may carry its own `precision` (`ns`, `us`, `ms`, or `s`);
+/// elements without one use `default_precision`. Timestamps are
+/// normalized to nanoseconds so mixed-precision payloads share one
+/// records batch.
+pub fn parse_json_metrics(body: &str, default_precision: &TimeUnit) -> Result<Vec<Metric>> {
+    let elements: Vec<Value> = serde_json::from_str(body)
+        .context("Request body is not a JSON array of metric objects")?;
+    let mut metrics: Vec<Metric> = Vec::new();
+    for (index, element) in elements.into_iter().enumerate() {
+        metrics.push(
+            parse_json_metric(element, default_precision)
+                .with_context(|| format!("Element {}", index))?,
+        );
+    }
+    Ok(metrics)
+}
+
+/// Parses a single metric object, normalizing its timestamp to
+/// nanoseconds using the element's `precision` or the default.
+fn parse_json_metric(element: Value, default_precision: &TimeUnit) -> Result<Metric> {
+    let precision = match element["precision"].as_str() {
+        Some("ns") => TimeUnit::Nanoseconds,
+        Some("us") => TimeUnit::Microseconds,
+        Some("ms") => TimeUnit::Milliseconds,
+        Some("s") => TimeUnit::Seconds,
+        Some(other) => return Err(anyhow!("Unsupported precision: {}", other)),
+        None => default_precision.clone(),
+    };
+    let metric: Metric =
+        serde_json::from_value(element).map_err(|error| anyhow!("{}", error))?;
+    let metric = Metric::new(
+        metric.name().to_string(),
+        metric.tags().clone(),
+        metric.fields().clone(),
+        timestamp_to_nanos(metric.timestamp(), &precision)?,
+    );
+    metric.validate()?;
+    Ok(metric)
+}
+
+/// Converts a timestamp in the given unit to nanoseconds. Also used by
+/// the line protocol parser's `per_line_precision` mode.
+pub(crate) fn timestamp_to_nanos(timestamp: i64, precision: &TimeUnit) -> Result<i64> {
+    let per_unit = match precision {
+        TimeUnit::Seconds => 1_000_000_000,
+        TimeUnit::Milliseconds => 1_000_000,
+        TimeUnit::Microseconds => 1_000,
+        _ => 1,
+    };
+    timestamp.checked_mul(per_unit).ok_or_else(|| {
+        anyhow!(
+            "Timestamp {} in {} overflows the nanosecond range",
+            timestamp,
+            precision
+        )
+    })
+}
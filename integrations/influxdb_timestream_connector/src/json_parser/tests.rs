@@ -0,0 +1,100 @@
+use super::*;
+use crate::metric::FieldValue;
+
+#[test]
+fn test_parse_json_metrics_mixed_types() {
+    let body = r#"[
+        {
+            "measurement": "readings",
+            "tags": {"fleet": "Alberta", "truck": "t-123"},
+            "fields": {
+                "active": true,
+                "fuel": 30,
+                "load": 0.5,
+                "state": "ok",
+                "total": 18446744073709551615
+            },
+            "timestamp": 1677605771000000000
+        }
+    ]"#;
+    let metrics = parse_json_metrics(body, &TimeUnit::Nanoseconds)
+        .expect("Failed to parse valid JSON metrics");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].name(), "readings");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![
+            ("fleet".to_string(), "Alberta".to_string()),
+            ("truck".to_string(), "t-123".to_string()),
+        ])
+    );
+    // Field keys deserialize in sorted order.
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![
+            ("active".to_string(), FieldValue::Boolean(true)),
+            ("fuel".to_string(), FieldValue::I64(30)),
+            ("load".to_string(), FieldValue::F64(0.5)),
+            ("state".to_string(), FieldValue::String("ok".to_string())),
+            ("total".to_string(), FieldValue::U64(u64::MAX)),
+        ]
+    );
+    assert_eq!(metrics[0].timestamp(), 1677605771000000000);
+}
+
+#[test]
+fn test_parse_json_metrics_missing_fields_reports_index() {
+    let body = r#"[
+        {"measurement": "readings", "fields": {"fuel": 30}, "timestamp": 1},
+        {"measurement": "readings", "timestamp": 2}
+    ]"#;
+    let error = parse_json_metrics(body, &TimeUnit::Nanoseconds)
+        .expect_err("Element without fields must be rejected");
+    let message = format!("{:#}", error);
+    assert!(message.contains("Element 1"), "Got error: {}", message);
+    assert!(message.contains("fields"), "Got error: {}", message);
+}
+
+#[test]
+fn test_parse_json_metrics_precision_handling() {
+    let body = r#"[
+        {"measurement": "readings", "fields": {"fuel": 30}, "timestamp": 1677605771000},
+        {
+            "measurement": "readings",
+            "fields": {"fuel": 31},
+            "timestamp": 1677605772,
+            "precision": "s"
+        }
+    ]"#;
+    // The first element has no precision and uses the default; both are
+    // normalized to nanoseconds.
+    let metrics = parse_json_metrics(body, &TimeUnit::Milliseconds)
+        .expect("Failed to parse valid JSON metrics");
+    assert_eq!(metrics[0].timestamp(), 1677605771000000000);
+    assert_eq!(metrics[1].timestamp(), 1677605772000000000);
+}
+
+#[test]
+fn test_parse_json_metrics_rejects_unsupported_precision() {
+    let body = r#"[
+        {"measurement": "readings", "fields": {"fuel": 30}, "timestamp": 1, "precision": "weeks"}
+    ]"#;
+    assert!(parse_json_metrics(body, &TimeUnit::Nanoseconds).is_err());
+}
+
+#[test]
+fn test_parse_json_metrics_omitted_tags() {
+    let body = r#"[{"measurement": "readings", "fields": {"fuel": 30}, "timestamp": 1}]"#;
+    let metrics = parse_json_metrics(body, &TimeUnit::Nanoseconds)
+        .expect("Failed to parse valid JSON metrics");
+    assert_eq!(metrics[0].tags(), &None);
+}
+
+#[test]
+fn test_parse_json_metrics_rejects_non_array_body() {
+    assert!(parse_json_metrics(
+        r#"{"measurement": "readings", "fields": {"fuel": 30}, "timestamp": 1}"#,
+        &TimeUnit::Nanoseconds
+    )
+    .is_err());
+}
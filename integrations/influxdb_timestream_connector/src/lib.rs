@@ -0,0 +1,2426 @@
+pub mod cloudwatch_metric_streams;
+pub mod csv_parser;
+pub mod error;
+pub mod json_parser;
+pub mod line_protocol_parser;
+pub mod metric;
+pub mod otlp;
+pub mod prometheus_remote_write;
+pub mod records_builder;
+pub mod self_monitoring;
+pub mod ssm_config;
+pub mod telegraf_json;
+#[doc(hidden)]
+pub mod test_support;
+pub mod timestream_utils;
+pub mod xray;
+
+use anyhow::{anyhow, Result};
+use aws_sdk_timestreamwrite::types::{Record, TimeUnit};
+use base64::Engine;
+use futures::stream::{FuturesUnordered, StreamExt};
+use lambda_runtime::LambdaEvent;
+use metric::Metric;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use timestream_utils::TimestreamWriteClient;
+use tokio::sync::Semaphore;
+use tracing::Instrument;
+
+pub use error::ConnectorError;
+
+/// The `aws_sdk_timestreamwrite` crate the connector builds against,
+/// re-exported so downstream crates can name the SDK types appearing in
+/// public signatures (`Record`, `TimeUnit`, and friends) without pinning
+/// a matching SDK version themselves.
+///
+/// ```
+/// use influxdb_timestream_connector::timestream_utils::{self, TimestreamWriteClient};
+/// use influxdb_timestream_connector::timestream_write::types::{Record, TimeUnit};
+/// use influxdb_timestream_connector::ConnectorError;
+///
+/// async fn write_one(client: &impl TimestreamWriteClient) -> Result<(), ConnectorError> {
+///     let record = Record::builder()
+///         .time("1677605771000".to_string())
+///         .time_unit(TimeUnit::Milliseconds)
+///         .build();
+///     timestream_utils::ingest_records(client, "db", "readings", &[record]).await
+/// }
+/// ```
+pub use aws_sdk_timestreamwrite as timestream_write;
+
+/// Maximum number of concurrent per-table ingestion tasks.
+pub const NUM_BATCH_THREADS: usize = 12;
+
+/// Resolved connector settings, decoupled from the process environment so
+/// the ingestion entry points can be embedded in non-Lambda services.
+#[derive(Debug, Clone)]
+pub struct ConnectorConfig {
+    pub database_name: String,
+    pub enable_database_creation: bool,
+    pub enable_table_creation: bool,
+    pub fail_fast: bool,
+    pub measure_name_for_multi_measure_records: String,
+    pub sort_records_by_time: bool,
+    pub skip_invalid_lines: bool,
+    /// Drop records whose target table does not exist (when table
+    /// creation is disabled) instead of failing the whole batch.
+    pub skip_missing_tables: bool,
+    /// Tag key whose value selects the target database per record, for
+    /// multi-tenant routing; records without the tag use `database_name`.
+    pub database_from_tag: Option<String>,
+}
+
+impl ConnectorConfig {
+    /// Resolves the connector configuration from the environment, then
+    /// overlays any parameters cached from SSM Parameter Store.
+    pub fn from_env() -> Result<Self> {
+        let config = ConnectorConfig {
+            database_name: env::var("database_name")
+                .map_err(|_| anyhow!("database_name is not defined"))?,
+            enable_database_creation: records_builder::env_var_to_bool(
+                "enable_database_creation",
+            ),
+            enable_table_creation: records_builder::env_var_to_bool("enable_table_creation"),
+            fail_fast: records_builder::env_var_to_bool("fail_fast"),
+            measure_name_for_multi_measure_records: env::var(
+                "measure_name_for_multi_measure_records",
+            )
+            .map_err(|_| anyhow!("measure_name_for_multi_measure_records is not defined"))?,
+            sort_records_by_time: records_builder::env_var_to_bool("sort_records_by_time"),
+            skip_invalid_lines: records_builder::env_var_to_bool("skip_invalid_lines"),
+            skip_missing_tables: records_builder::env_var_to_bool("skip_missing_tables"),
+            database_from_tag: env::var("database_from_tag").ok(),
+        };
+        Ok(ssm_config::overlay_config(
+            config,
+            &ssm_config::ssm_cache().parameters(),
+        ))
+    }
+}
+
+/// Counts describing one processed line protocol payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestionSummary {
+    pub lines_parsed: usize,
+    /// Malformed lines skipped under `skip_invalid_lines`, plus records
+    /// dropped because a skip policy removed every one of their measure
+    /// values; always zero in strict mode.
+    pub lines_skipped: usize,
+    pub records_written: usize,
+    pub tables: Vec<String>,
+    /// Effective ingest concurrency limit after this payload, as adjusted
+    /// by the adaptive controller in response to throttling.
+    pub concurrency_limit: usize,
+}
+
+/// Parses, validates, and ingests a line protocol payload. This is the
+/// library entry point for embedders; it reads no environment variables,
+/// taking all settings from `config`.
+pub async fn ingest_line_protocol<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    config: &ConnectorConfig,
+    line_protocol: &str,
+    precision: &TimeUnit,
+) -> Result<IngestionSummary> {
+    let mut subsegment = xray::Subsegment::begin("parse_line_protocol");
+    let parsed = line_protocol_parser::parse_line_protocol_with_mode(
+        line_protocol,
+        config.skip_invalid_lines,
+    );
+    if parsed.is_err() {
+        subsegment.set_error();
+    }
+    subsegment.end();
+    let (metrics, skipped_lines) = parsed?;
+    let mut summary = ingest_metrics(client, config, metrics, precision).await?;
+    summary.lines_skipped += skipped_lines.len();
+    Ok(summary)
+}
+
+/// Builds and ingests Timestream records from already-parsed metrics.
+/// Shared by the line protocol and Prometheus remote_write paths. Takes
+/// the metrics by value so record building can move their strings;
+/// `ingest_metrics_borrowed` clones for callers that keep theirs.
+#[tracing::instrument(level = "trace", skip_all, fields(metrics = metrics.len()))]
+pub async fn ingest_metrics<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    config: &ConnectorConfig,
+    metrics: Vec<Metric>,
+    precision: &TimeUnit,
+) -> Result<IngestionSummary> {
+    let lines_parsed = metrics.len();
+    let mut subsegment = xray::Subsegment::begin("build_records");
+    subsegment.annotate("metric_count", json!(lines_parsed));
+    let built = records_builder::build_records(
+        metrics,
+        precision,
+        &config.measure_name_for_multi_measure_records,
+    );
+    if built.is_err() {
+        subsegment.set_error();
+    }
+    subsegment.end();
+    let mut records = built?;
+    // A skip policy can leave a record with no measure values at all
+    // (e.g. a metric whose only field was an empty string); such records
+    // are dropped here rather than poisoning their write batch.
+    let records_dropped = records_builder::drop_measureless_records(&mut records);
+    if config.sort_records_by_time {
+        records_builder::sort_records_by_time(&mut records);
+    }
+    // An enforced partition key is checked up front so the payload fails
+    // with one clear message rather than per-record rejections.
+    if env::var("custom_partition_key_type").is_ok() {
+        let table_config = timestream_utils::get_table_config()?;
+        records_builder::validate_partition_key_present(&records, &table_config)?;
+    }
+    let mut summary = IngestionSummary {
+        lines_parsed,
+        lines_skipped: records_dropped,
+        records_written: records.values().map(Vec::len).sum(),
+        tables: records.keys().cloned().collect(),
+        concurrency_limit: 0,
+    };
+    let self_monitoring = self_monitoring::self_monitoring_enabled();
+    let table_counts: Vec<(String, usize)> = if self_monitoring {
+        records
+            .iter()
+            .map(|(table_name, table_records)| (table_name.clone(), table_records.len()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let batch_bytes = if self_monitoring {
+        approximate_batch_bytes(&records)
+    } else {
+        0
+    };
+    // The breaker counts consecutive throttling failures within one
+    // invocation, so it starts closed for each payload.
+    timestream_utils::circuit_breaker().reset();
+    let started = std::time::Instant::now();
+    let ingestion_result = handle_multi_table_ingestion(client, config, records).await;
+    if self_monitoring {
+        self_monitoring::write_self_monitoring_records(
+            client,
+            config,
+            &table_counts,
+            started.elapsed(),
+            ingestion_result.is_err(),
+            batch_bytes,
+        )
+        .await;
+    }
+    // Records dropped for missing tables under skip_missing_tables were
+    // counted as written above; correct the summary.
+    summary.records_written -= ingestion_result?;
+    // Report the post-ingestion limit so throttling during this payload is
+    // visible to the caller.
+    summary.concurrency_limit = timestream_utils::adaptive_concurrency().current_limit();
+    Ok(summary)
+}
+
+/// `ingest_metrics` for callers that need to retain their metrics, at
+/// the cost of cloning them.
+pub async fn ingest_metrics_borrowed<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    config: &ConnectorConfig,
+    metrics: &[Metric],
+    precision: &TimeUnit,
+) -> Result<IngestionSummary> {
+    ingest_metrics(client, config, metrics.to_vec(), precision).await
+}
+
+/// Lambda entry point. Expects an API Gateway/ALB-style event carrying a
+/// line protocol payload in `body` and an optional `precision` query
+/// string parameter.
+///
+/// Every invocation carries a correlation id — the caller's
+/// `X-Request-Id` (or `X-Amzn-Trace-Id`) header when one is present,
+/// otherwise a generated one. The id is attached as a span field so
+/// every log line emitted while handling the request includes it, and
+/// it is echoed back in the `X-Request-Id` response header so callers
+/// can quote it when reporting a problem.
+pub async fn lambda_handler<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    event: LambdaEvent<Value>,
+) -> Result<Value, lambda_runtime::Error> {
+    let (event, _context) = event.into_parts();
+    let correlation_id = correlation_id(&event);
+    let span = tracing::info_span!("request", correlation_id = %correlation_id);
+    let mut response = handle_request(client, event).instrument(span).await?;
+    attach_correlation_id(&mut response, &correlation_id);
+    Ok(response)
+}
+
+/// The correlation id for one invocation: the caller's `X-Request-Id`
+/// header when present so their own id shows up in the connector's
+/// logs, falling back to the `X-Amzn-Trace-Id` ALB and API Gateway
+/// inject, and finally to a generated id for events carrying neither.
+fn correlation_id(event: &Value) -> String {
+    if let Some(id) = get_header(event, "x-request-id") {
+        return id.to_string();
+    }
+    if let Some(id) = get_header(event, "x-amzn-trace-id") {
+        return id.to_string();
+    }
+    // Epoch nanoseconds, process id, and a per-process counter,
+    // hex-encoded: unique without pulling in a uuid dependency.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{:x}",
+        now.as_nanos(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Echoes the correlation id back on the response, merging into any
+/// headers the handler already set (the ping and health probes carry
+/// their own).
+fn attach_correlation_id(response: &mut Value, correlation_id: &str) {
+    if let Some(headers) = response
+        .get_mut("headers")
+        .and_then(Value::as_object_mut)
+    {
+        headers.insert("X-Request-Id".to_string(), json!(correlation_id));
+    } else {
+        response["headers"] = json!({ "X-Request-Id": correlation_id });
+    }
+}
+
+#[tracing::instrument(level = "trace", skip_all)]
+async fn handle_request<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    event: Value,
+) -> Result<Value, lambda_runtime::Error> {
+    // Pick up any rotated settings from SSM Parameter Store before the
+    // configuration is resolved; a no-op unless config_ssm_prefix is set.
+    ssm_config::refresh_from_ssm().await;
+
+    // Warm-up invocations (provisioned-concurrency schedules or explicit
+    // `{"warmup": true}` test events) exist only to get the client built
+    // and the configuration validated ahead of real traffic; answer them
+    // before any ingestion dispatch.
+    if is_warmup_event(&event) {
+        return match records_builder::validate_env_variables() {
+            Ok(()) => Ok(build_response(200, &json!({ "message": "Warmed" }).to_string())),
+            Err(error) => Ok(error_response(500, &error.to_string())),
+        };
+    }
+
+    // Firehose delivery streams (e.g. carrying CloudWatch Metric Streams
+    // data) invoke the Lambda with a records batch rather than an HTTP
+    // event; dispatch on the event shape before any HTTP handling.
+    if is_firehose_event(&event) {
+        return Ok(handle_firehose_event(client, &event).await?);
+    }
+
+    // InfluxDB clients GET /ping (or /health) to verify connectivity
+    // before writing; answer the InfluxDB-style probe responses before
+    // the method check rejects their GETs.
+    if is_ping_request(&event) {
+        return Ok(ping_response());
+    }
+    if is_health_request(&event) {
+        return Ok(health_response());
+    }
+
+    // ALB and API Gateway forward any verb to the Lambda, but only POST
+    // carries a write payload; answer other methods with a 405 instead of
+    // treating their (usually absent) body as line protocol. Events
+    // without a method (direct invocations, test events) pass through.
+    if let Some(method) = get_http_method(&event) {
+        if !method.eq_ignore_ascii_case("POST") {
+            return Ok(method_not_allowed_response(method));
+        }
+    }
+
+    // InfluxDB v2 clients issue POST /api/v2/delete to remove points by
+    // time range and predicate. Timestream does not support row deletes, so
+    // answer the delete shape with a documented error instead of treating
+    // the payload as malformed line protocol.
+    if is_delete_request(&event) {
+        return Ok(error_response(
+            501,
+            "The InfluxDB delete API is not supported; Timestream for \
+            LiveAnalytics does not support deleting individual records",
+        ));
+    }
+
+    let Some(body) = event["body"].as_str() else {
+        return Ok(error_response(400, "Request body is missing"));
+    };
+
+    // Reject oversized payloads before any decompression or parsing to
+    // bound the handler's memory use.
+    if let Some(limit) = max_body_bytes() {
+        if body.len() > limit {
+            return Ok(payload_too_large_response(body.len(), limit));
+        }
+    }
+
+    // The OTel collector's otlphttp exporter POSTs protobuf to
+    // /v1/metrics; this must be checked before the remote_write dispatch
+    // since both formats use the protobuf Content-Type.
+    if is_otlp_request(&event) {
+        let body = match decode_body_bytes(&event, body) {
+            Ok(body) => body,
+            Err(error) => return Ok(error_response(400, &error.to_string())),
+        };
+        return match handle_otlp_body(client, &body).await {
+            Ok(conversion) => Ok(otlp_response(&conversion)),
+            Err(error) => Ok(error_response(400, &error.to_string())),
+        };
+    }
+
+    // Prometheus remote_write bodies are snappy-compressed protobuf rather
+    // than line protocol; dispatch on the Content-Type header (or a
+    // `format` query parameter for clients that cannot set headers).
+    if is_remote_write_request(&event) {
+        let body = match decode_body_bytes(&event, body) {
+            Ok(body) => body,
+            Err(error) => return Ok(error_response(400, &error.to_string())),
+        };
+        return match handle_remote_write_body(client, &body).await {
+            Ok(()) => Ok(success_response()),
+            Err(error) => Ok(error_response(400, &error.to_string())),
+        };
+    }
+
+    // Anything not dispatched above is treated as line protocol, which
+    // clients send as text/plain (or application/octet-stream when
+    // compressed). A mismatched Content-Type gets a clear 415 instead of
+    // confusing parse errors; requests without the header pass through.
+    if strict_content_type() {
+        if let Some(content_type) = get_header(&event, "content-type") {
+            if !content_type.starts_with("text/plain")
+                && !content_type.starts_with("application/octet-stream")
+            {
+                return Ok(error_response(
+                    415,
+                    &format!(
+                        "Unsupported Content-Type {}; line protocol requests use text/plain",
+                        content_type
+                    ),
+                ));
+            }
+        }
+    }
+
+    // Influx clients commonly gzip line protocol payloads; API Gateway
+    // passes the compressed body through base64-encoded.
+    let decompressed_body;
+    let body = if has_gzip_content_encoding(&event) {
+        let body_bytes = match decode_body_bytes(&event, body) {
+            Ok(body_bytes) => body_bytes,
+            Err(error) => return Ok(error_response(400, &error.to_string())),
+        };
+        let decompressed_bytes = match decompress_gzip_body(&body_bytes) {
+            Ok(decompressed_bytes) => decompressed_bytes,
+            Err(error) => return Ok(error_response(400, &error.to_string())),
+        };
+        // The size guard above only saw the compressed body; apply it to
+        // the decompressed size as well.
+        if let Some(limit) = max_body_bytes() {
+            if decompressed_bytes.len() > limit {
+                return Ok(payload_too_large_response(decompressed_bytes.len(), limit));
+            }
+        }
+        match String::from_utf8(decompressed_bytes) {
+            Ok(body) => {
+                decompressed_body = body;
+                decompressed_body.as_str()
+            }
+            Err(_) => {
+                return Ok(error_response(400, "Decompressed body is not valid UTF-8"))
+            }
+        }
+    } else {
+        body
+    };
+
+    let precision = match get_precision(&event) {
+        Some(raw) => precision_from_str(&raw).unwrap_or_else(|| {
+            tracing::warn!("Unrecognized precision {:?}; defaulting to nanoseconds", raw);
+            TimeUnit::Nanoseconds
+        }),
+        None => TimeUnit::Nanoseconds,
+    };
+
+    // A `db` query parameter routes the request to another database, but
+    // only when that database is explicitly allowed.
+    let database_override = match get_database_override(&event) {
+        Ok(database_override) => database_override,
+        Err(error) => return Ok(error_response(403, &error.to_string())),
+    };
+    let database_override = database_override.as_deref();
+
+    // JSON metric payloads skip the line protocol parser entirely.
+    if is_json_request(&event) {
+        return match handle_json_body(client, body, &precision, database_override).await {
+            Ok(()) => Ok(success_response()),
+            Err(error) => Ok(error_response(400, &format!("{:#}", error))),
+        };
+    }
+
+    // CSV payloads map header columns onto metric parts; the mapping can
+    // be set per-request through query parameters.
+    if is_csv_request(&event) {
+        let mapping = csv_parser::CsvColumnMapping::resolve(|name| {
+            event["queryStringParameters"][name]
+                .as_str()
+                .map(str::to_string)
+        });
+        return match handle_csv_body(client, body, &precision, &mapping, database_override)
+            .await
+        {
+            Ok(()) => Ok(success_response()),
+            Err(error) => Ok(error_response(400, &format!("{:#}", error))),
+        };
+    }
+
+    // Bound the whole parse-and-ingest path so a huge malformed batch or
+    // a slow Timestream fails with a clean 503 before the Lambda runtime
+    // kills the invocation at its own deadline.
+    match tokio::time::timeout(
+        handler_timeout(),
+        handle_body(client, body, &precision, database_override),
+    )
+    .await
+    {
+        Ok(Ok(summary)) => Ok(summary_response(&summary)),
+        Ok(Err(error)) => Ok(error_response(400, &error.to_string())),
+        Err(_) => Ok(error_response(503, "handler_timeout")),
+    }
+}
+
+/// Returns whether the event is a warm-up ping rather than a real
+/// request: a top-level `warmup: true` field, which no HTTP event shape
+/// carries.
+fn is_warmup_event(event: &Value) -> bool {
+    event["warmup"].as_bool().unwrap_or(false)
+}
+
+/// Returns whether the event has the shape of a Firehose invocation: a
+/// delivery stream ARN and a batch of base64-encoded records.
+fn is_firehose_event(event: &Value) -> bool {
+    event["deliveryStreamArn"].is_string() && event["records"].is_array()
+}
+
+/// Handles a Firehose records batch carrying CloudWatch Metric Streams
+/// data. Each record's decoded data is parsed as metric stream JSON and
+/// ingested; the response reports a per-record disposition in the shape
+/// Firehose expects from a processing Lambda. Ingested records are marked
+/// `Dropped` (consumed here, nothing left to deliver downstream) and
+/// records that fail to decode, parse, or ingest are `ProcessingFailed`
+/// so Firehose retries or dead-letters them.
+pub async fn handle_firehose_event<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    event: &Value,
+) -> Result<Value> {
+    let config = ConnectorConfig::from_env()?;
+    let records = event["records"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Firehose event has no records array"))?;
+
+    let mut metrics: Vec<Metric> = Vec::new();
+    let mut dispositions: Vec<(String, &str)> = Vec::new();
+    for record in records {
+        let record_id = record["recordId"].as_str().unwrap_or_default().to_string();
+        let parsed = decode_firehose_record_data(record)
+            .and_then(|data| cloudwatch_metric_streams::parse_metric_stream_data(&data));
+        match parsed {
+            Ok(parsed) => {
+                metrics.extend(parsed);
+                dispositions.push((record_id, "Dropped"));
+            }
+            Err(error) => {
+                tracing::warn!("Failed to parse Firehose record {}: {:#}", record_id, error);
+                dispositions.push((record_id, "ProcessingFailed"));
+            }
+        }
+    }
+
+    // Metric stream timestamps are epoch milliseconds.
+    if !metrics.is_empty() {
+        if let Err(error) =
+            ingest_metrics(client, &config, metrics, &TimeUnit::Milliseconds).await
+        {
+            tracing::error!("Failed to ingest Firehose batch: {:#}", error);
+            for disposition in &mut dispositions {
+                disposition.1 = "ProcessingFailed";
+            }
+        }
+    }
+
+    Ok(json!({
+        "records": dispositions
+            .into_iter()
+            .map(|(record_id, result)| json!({ "recordId": record_id, "result": result }))
+            .collect::<Vec<Value>>(),
+    }))
+}
+
+/// Decodes a Firehose record's base64 data into a UTF-8 string.
+fn decode_firehose_record_data(record: &Value) -> Result<String> {
+    let data = record["data"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Firehose record has no data"))?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|error| anyhow!("Failed to decode base64 data: {}", error))?;
+    Ok(String::from_utf8(data)?)
+}
+
+/// Extracts the request path from the event, handling both the payload
+/// format 1.0 (`path`) and 2.0 (`rawPath`) shapes.
+fn get_request_path(event: &Value) -> Option<&str> {
+    event["rawPath"]
+        .as_str()
+        .or_else(|| event["path"].as_str())
+}
+
+/// Extracts the HTTP method from the event, handling both the payload
+/// format 1.0 (`httpMethod`) and 2.0 (`requestContext.http.method`)
+/// shapes. Direct invocations carry neither and return `None`.
+fn get_http_method(event: &Value) -> Option<&str> {
+    event["httpMethod"]
+        .as_str()
+        .or_else(|| event["requestContext"]["http"]["method"].as_str())
+}
+
+/// Whether line protocol requests must carry a `text/plain` or
+/// `application/octet-stream` Content-Type. Enabled by default; set
+/// `strict_content_type=false` to restore the old accept-anything
+/// behavior.
+fn strict_content_type() -> bool {
+    env::var("strict_content_type").ok().as_deref() != Some("false")
+}
+
+/// Returns whether the event is an InfluxDB `/ping` connectivity probe.
+fn is_ping_request(event: &Value) -> bool {
+    get_request_path(event)
+        .map(|path| path.ends_with("/ping"))
+        .unwrap_or(false)
+}
+
+/// Returns whether the event is an InfluxDB `/health` probe.
+fn is_health_request(event: &Value) -> bool {
+    get_request_path(event)
+        .map(|path| path.ends_with("/health"))
+        .unwrap_or(false)
+}
+
+/// Returns whether the event has the shape of an InfluxDB v2 delete
+/// request: a POST to `/api/v2/delete`.
+fn is_delete_request(event: &Value) -> bool {
+    get_request_path(event)
+        .map(|path| path.ends_with("/api/v2/delete"))
+        .unwrap_or(false)
+}
+
+/// Returns the value of a header from the event, matching the header name
+/// case-insensitively since API Gateway forwards client casing as-is.
+fn get_header<'a>(event: &'a Value, header_name: &str) -> Option<&'a str> {
+    event["headers"].as_object().and_then(|headers| {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(header_name))
+            .and_then(|(_, value)| value.as_str())
+    })
+}
+
+/// Returns whether the event carries a Prometheus remote_write payload:
+/// a protobuf Content-Type or an explicit `format=prometheus` query
+/// string parameter.
+fn is_remote_write_request(event: &Value) -> bool {
+    if let Some(content_type) = get_header(event, "content-type") {
+        if content_type.starts_with("application/x-protobuf") {
+            return true;
+        }
+    }
+    event["queryStringParameters"]["format"].as_str() == Some("prometheus")
+}
+
+/// Returns whether the event has the shape of an OTLP/HTTP metrics
+/// export: a protobuf POST to `/v1/metrics`.
+fn is_otlp_request(event: &Value) -> bool {
+    get_request_path(event)
+        .map(|path| path.ends_with("/v1/metrics"))
+        .unwrap_or(false)
+        && get_header(event, "content-type")
+            .map(|content_type| content_type.starts_with("application/x-protobuf"))
+            .unwrap_or(false)
+}
+
+/// Builds the OTLP export response: an empty object on full success, or a
+/// `partialSuccess` per the OTLP spec when data points were rejected.
+fn otlp_response(conversion: &otlp::OtlpConversion) -> Value {
+    if conversion.rejected_data_points == 0 {
+        return build_response(200, &json!({}).to_string());
+    }
+    let body = json!({
+        "partialSuccess": {
+            "rejectedDataPoints": conversion.rejected_data_points,
+            "errorMessage": format!(
+                "Unsupported metric types skipped: {}",
+                conversion.rejected_types.join(", ")
+            ),
+        }
+    });
+    build_response(200, &body.to_string())
+}
+
+/// Returns whether the event carries a JSON metric payload: a JSON
+/// Content-Type or an explicit `format=json` query string parameter.
+fn is_json_request(event: &Value) -> bool {
+    if let Some(content_type) = get_header(event, "content-type") {
+        if content_type.starts_with("application/json") {
+            return true;
+        }
+    }
+    event["queryStringParameters"]["format"].as_str() == Some("json")
+}
+
+/// Returns whether the event carries a CSV payload: a `text/csv`
+/// Content-Type or an explicit `format=csv` query string parameter.
+fn is_csv_request(event: &Value) -> bool {
+    if let Some(content_type) = get_header(event, "content-type") {
+        if content_type.starts_with("text/csv") {
+            return true;
+        }
+    }
+    event["queryStringParameters"]["format"].as_str() == Some("csv")
+}
+
+/// Returns whether the request body is gzip-compressed, per its
+/// Content-Encoding header.
+fn has_gzip_content_encoding(event: &Value) -> bool {
+    get_header(event, "content-encoding")
+        .map(|content_encoding| content_encoding.contains("gzip"))
+        .unwrap_or(false)
+}
+
+/// Decompresses a gzip body. When `max_body_bytes` is configured the
+/// decoder stops one byte past the limit, so a gzip bomb cannot buffer
+/// more than the limit before the caller's size check rejects it.
+fn decompress_gzip_body(body: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+    let cap = max_body_bytes()
+        .map(|limit| limit as u64 + 1)
+        .unwrap_or(u64::MAX);
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(body)
+        .take(cap)
+        .read_to_end(&mut decompressed)
+        .map_err(|error| anyhow!("Failed to decompress gzip body: {}", error))?;
+    Ok(decompressed)
+}
+
+/// Default ingestion timeout, leaving a 30-second margin under Lambda's
+/// default 900-second maximum execution time.
+const DEFAULT_HANDLER_TIMEOUT_SECONDS: u64 = 870;
+
+/// The ingestion timeout from the optional `handler_timeout_seconds`
+/// environment variable. Deployments with a shorter Lambda timeout set
+/// this to keep the same margin.
+fn handler_timeout() -> std::time::Duration {
+    let seconds = env::var("handler_timeout_seconds")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_HANDLER_TIMEOUT_SECONDS);
+    std::time::Duration::from_secs(seconds)
+}
+
+/// Default maximum request body size: Lambda's 6 MB synchronous
+/// invocation payload limit. Larger bodies cannot arrive through the
+/// Lambda SDK, but can through a custom invoker or a gzip payload, and
+/// the connector should bound its memory use regardless.
+const DEFAULT_MAX_BODY_BYTES: usize = 6 * 1024 * 1024;
+
+/// Maximum request body size in bytes, from the `max_body_bytes`
+/// environment variable; defaults to Lambda's synchronous payload limit
+/// and `0` disables the check. Applies to both the raw and, for gzip
+/// payloads, the decompressed body.
+fn max_body_bytes() -> Option<usize> {
+    match env::var("max_body_bytes")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+    {
+        Some(0) => None,
+        Some(limit) => Some(limit),
+        None => Some(DEFAULT_MAX_BODY_BYTES),
+    }
+}
+
+fn payload_too_large_response(body_bytes: usize, limit: usize) -> Value {
+    error_response(
+        413,
+        &format!(
+            "Request body of {} bytes exceeds the max_body_bytes limit of {} bytes",
+            body_bytes, limit
+        ),
+    )
+}
+
+/// Returns the raw body bytes, decoding base64 when API Gateway has
+/// flagged the body as encoded (always the case for binary payloads).
+fn decode_body_bytes(event: &Value, body: &str) -> Result<Vec<u8>> {
+    if event["isBase64Encoded"].as_bool().unwrap_or(false) {
+        base64::engine::general_purpose::STANDARD
+            .decode(body)
+            .map_err(|error| anyhow!("Failed to decode base64 body: {}", error))
+    } else {
+        Ok(body.as_bytes().to_vec())
+    }
+}
+
+/// Extracts a query string parameter from the event, handling both the
+/// API Gateway object form (`{"precision": "ms"}`) and the multi-value
+/// array form (`{"precision": ["ms"]}`).
+fn get_query_parameter(event: &Value, name: &str) -> Option<String> {
+    let parameter = &event["queryStringParameters"][name];
+    if let Some(parameter) = parameter.as_str() {
+        return Some(parameter.to_string());
+    }
+    if let Some(parameter) = parameter.as_array() {
+        return parameter
+            .first()
+            .and_then(|value| value.as_str())
+            .map(str::to_string);
+    }
+    None
+}
+
+/// Extracts the request precision: the `precision` query string
+/// parameter, or — for REST-style routes that encode it in the path,
+/// like `/write/ns` — a trailing path segment naming a known unit. The
+/// query string parameter wins when both are present.
+pub fn get_precision(event: &Value) -> Option<String> {
+    get_query_parameter(event, "precision").or_else(|| get_precision_from_path(event))
+}
+
+/// Returns the trailing path segment when it names a known precision.
+/// Restricting the match to known units keeps routes like `/write` or
+/// `/api/v2/write` from being misread as a precision.
+fn get_precision_from_path(event: &Value) -> Option<String> {
+    let segment = get_request_path(event)?
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()?;
+    precision_from_str(segment).map(|_| segment.to_string())
+}
+
+/// Maps an InfluxDB precision string (`ns`, `us`, `ms`, or `s`) to its
+/// Timestream time unit, or `None` for anything else. Matching is
+/// case-insensitive and the spelled-out unit names (`nanoseconds`,
+/// `microseconds`, `milliseconds`, `seconds`) are accepted as aliases.
+pub fn precision_from_str(precision: &str) -> Option<TimeUnit> {
+    match precision.to_ascii_lowercase().as_str() {
+        "ns" | "nanoseconds" => Some(TimeUnit::Nanoseconds),
+        "us" | "microseconds" => Some(TimeUnit::Microseconds),
+        "ms" | "milliseconds" => Some(TimeUnit::Milliseconds),
+        "s" | "seconds" => Some(TimeUnit::Seconds),
+        _ => None,
+    }
+}
+
+/// Resolves the optional per-request database override from the `db` (or
+/// `database`) query string parameter, mirroring InfluxDB v1's
+/// `/write?db=`. Overrides must be listed in the comma-separated
+/// `allowed_database_overrides` environment variable so arbitrary callers
+/// cannot route data into unexpected databases.
+fn get_database_override(event: &Value) -> Result<Option<String>> {
+    let Some(database_name) = get_query_parameter(event, "db")
+        .or_else(|| get_query_parameter(event, "database"))
+    else {
+        return Ok(None);
+    };
+    let allowed = env::var("allowed_database_overrides").unwrap_or_default();
+    if allowed
+        .split(',')
+        .map(str::trim)
+        .any(|allowed_name| allowed_name == database_name)
+    {
+        Ok(Some(database_name))
+    } else {
+        Err(anyhow!(
+            "Database override {} is not listed in allowed_database_overrides",
+            database_name
+        ))
+    }
+}
+
+/// Resolves the connector configuration, applying a per-request database
+/// override when one was given.
+fn config_with_database_override(database_override: Option<&str>) -> Result<ConnectorConfig> {
+    let mut config = ConnectorConfig::from_env()?;
+    if let Some(database_name) = database_override {
+        config.database_name = database_name.to_string();
+    }
+    Ok(config)
+}
+
+/// Approximates the payload size of built records for the
+/// self-monitoring `batch_bytes` measure: the summed lengths of every
+/// dimension and measure name and value.
+fn approximate_batch_bytes(records: &HashMap<String, Vec<Record>>) -> usize {
+    records
+        .values()
+        .flatten()
+        .map(timestream_utils::approximate_record_bytes)
+        .sum()
+}
+
+/// Parses a line protocol body, builds Timestream records, and ingests
+/// them, resolving the connector configuration from the environment.
+pub async fn handle_body<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    body: &str,
+    precision: &TimeUnit,
+    database_override: Option<&str>,
+) -> Result<IngestionSummary, ConnectorError> {
+    let config = config_with_database_override(database_override)
+        .map_err(ConnectorError::configuration)?;
+    // Under per_line_precision the parser infers each line's unit and
+    // normalizes its timestamp to nanoseconds, so the request-level
+    // precision no longer applies.
+    let precision = if records_builder::env_var_to_bool("per_line_precision") {
+        &TimeUnit::Nanoseconds
+    } else {
+        precision
+    };
+    ingest_line_protocol(client, &config, body, precision)
+        .await
+        .map_err(ConnectorError::from_anyhow)
+}
+
+/// Parses a JSON metric payload and ingests the resulting metrics.
+/// Bodies with a top-level `metrics` array are parsed as Telegraf JSON
+/// output; anything else is parsed as the connector's own JSON array
+/// format. Element timestamps are normalized to nanoseconds by the
+/// parsers, so `default_precision` only governs array elements without
+/// their own `precision`.
+pub async fn handle_json_body<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    body: &str,
+    default_precision: &TimeUnit,
+    database_override: Option<&str>,
+) -> Result<()> {
+    let config = config_with_database_override(database_override)?;
+    let metrics = if telegraf_json::is_telegraf_payload(body) {
+        telegraf_json::parse_telegraf_json(body)?
+    } else {
+        json_parser::parse_json_metrics(body, default_precision)?
+    };
+    ingest_metrics(client, &config, metrics, &TimeUnit::Nanoseconds).await?;
+    Ok(())
+}
+
+/// Parses a CSV body using the given column mapping and ingests the
+/// resulting metrics. Row timestamps are in the request's time unit, and
+/// invalid rows follow the same strict/lenient semantics as line
+/// protocol via `skip_invalid_lines`.
+pub async fn handle_csv_body<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    body: &str,
+    precision: &TimeUnit,
+    mapping: &csv_parser::CsvColumnMapping,
+    database_override: Option<&str>,
+) -> Result<()> {
+    let config = config_with_database_override(database_override)?;
+    let (metrics, _skipped_rows) =
+        csv_parser::parse_csv_metrics(body, mapping, config.skip_invalid_lines)?;
+    ingest_metrics(client, &config, metrics, precision).await?;
+    Ok(())
+}
+
+/// Parses an OTLP metrics export body and ingests the supported data
+/// points, returning the conversion so the caller can report partial
+/// success. OTLP timestamps are always nanoseconds.
+pub async fn handle_otlp_body<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    body: &[u8],
+) -> Result<otlp::OtlpConversion> {
+    let config = ConnectorConfig::from_env()?;
+    let conversion = otlp::parse_otlp_metrics(body)?;
+    ingest_metrics_borrowed(client, &config, &conversion.metrics, &TimeUnit::Nanoseconds).await?;
+    Ok(conversion)
+}
+
+/// Parses a Prometheus remote_write body and ingests the resulting
+/// metrics. Sample timestamps are always milliseconds, per the
+/// remote_write contract, so the `precision` parameter does not apply.
+pub async fn handle_remote_write_body<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    body: &[u8],
+) -> Result<()> {
+    let config = ConnectorConfig::from_env()?;
+    let metrics = prometheus_remote_write::parse_remote_write(body)?;
+    ingest_metrics(client, &config, metrics, &TimeUnit::Milliseconds).await?;
+    Ok(())
+}
+
+/// Ingests records into their target tables, creating databases and
+/// missing tables when the corresponding creation flags are enabled. With
+/// `database_from_tag` set, records route to a database named by that
+/// tag's value (falling back to `database_name`); otherwise everything
+/// goes to `database_name`. Table existence is resolved up front with
+/// `timestream_utils::preflight_table_check`, so the per-table tasks only
+/// create missing tables and write. Table ingestion runs concurrently,
+/// bounded by the adaptive concurrency limit (at most
+/// `NUM_BATCH_THREADS`). Returns the number of records dropped under
+/// `skip_missing_tables`.
+#[tracing::instrument(level = "trace", skip_all, fields(tables = records.len()))]
+pub async fn handle_multi_table_ingestion<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    config: &ConnectorConfig,
+    records: HashMap<String, Vec<Record>>,
+) -> Result<usize> {
+    let region = timestream_utils::resolve_region().unwrap_or_else(|_| "unknown".to_string());
+    let databases = match &config.database_from_tag {
+        Some(tag_key) => group_records_by_database(records, tag_key, &config.database_name),
+        None => HashMap::from([(config.database_name.clone(), records)]),
+    };
+
+    for database_name in databases.keys() {
+        if !timestream_utils::database_exists_cached(client, database_name).await? {
+            if config.enable_database_creation {
+                timestream_utils::create_database(client, database_name).await?;
+            } else {
+                return Err(anyhow!(
+                    "Database {} does not exist in region {} and database creation is not \
+                    enabled; set enable_database_creation=true to create it",
+                    database_name,
+                    region
+                ));
+            }
+        }
+    }
+
+    // Pre-flight: fire every table existence check concurrently before
+    // the write-bounded per-table tasks start, so a wide batch is not
+    // serialized behind describe calls.
+    let mut preflight: HashMap<String, HashMap<String, bool>> = HashMap::new();
+    for (database_name, tables) in &databases {
+        let table_names: Vec<String> = tables.keys().cloned().collect();
+        preflight.insert(
+            database_name.clone(),
+            timestream_utils::preflight_table_check(client, database_name, &table_names)
+                .await?,
+        );
+    }
+
+    let fail_fast = config.fail_fast;
+    let enable_table_creation = config.enable_table_creation;
+    let skip_missing_tables = config.skip_missing_tables;
+    let concurrency_limit = timestream_utils::adaptive_concurrency().current_limit();
+    tracing::debug!("Ingesting with adaptive concurrency limit {}", concurrency_limit);
+    let semaphore = Arc::new(Semaphore::new(concurrency_limit));
+    let tasks = FuturesUnordered::new();
+    let mut abort_handles = Vec::new();
+    for (database_name, table_name, table_records) in databases
+        .into_iter()
+        .flat_map(|(database_name, tables)| {
+            tables.into_iter().map(move |(table_name, table_records)| {
+                (database_name.clone(), table_name, table_records)
+            })
+        })
+    {
+        let client = Arc::clone(client);
+        let region = region.clone();
+        let table_exists = preflight
+            .get(&database_name)
+            .and_then(|tables| tables.get(&table_name))
+            .copied()
+            .unwrap_or(false);
+        let permit = Arc::clone(&semaphore).acquire_owned().await?;
+        let task = tokio::spawn(async move {
+            let _permit = permit;
+            if !table_exists {
+                if enable_table_creation {
+                    let table_config = timestream_utils::get_table_config()?;
+                    timestream_utils::create_table(
+                        &client,
+                        &database_name,
+                        &table_name,
+                        table_config,
+                    )
+                    .await?;
+                } else if skip_missing_tables {
+                    tracing::warn!(
+                        "Dropping {} records for table {} in database {}, which does \
+                        not exist and table creation is not enabled",
+                        table_records.len(),
+                        table_name,
+                        database_name
+                    );
+                    return Ok(table_records.len());
+                } else {
+                    return Err(anyhow!(
+                        "Table {} does not exist in database {} (region {}) and table \
+                        creation is not enabled; set enable_table_creation=true to \
+                        create it",
+                        table_name,
+                        database_name,
+                        region
+                    ));
+                }
+            }
+            timestream_utils::ingest_records(&client, &database_name, &table_name, &table_records)
+                .await
+                .map(|()| 0)
+                .map_err(anyhow::Error::from)
+        });
+        abort_handles.push(task.abort_handle());
+        tasks.push(task);
+    }
+
+    drain_ingestion_tasks(tasks, abort_handles, fail_fast).await
+}
+
+/// Partitions built records by the value of the `database_from_tag`
+/// dimension, so multi-tenant setups land each tenant's data in its own
+/// database. Records without the dimension fall back to the default
+/// database.
+fn group_records_by_database(
+    records: HashMap<String, Vec<Record>>,
+    tag_key: &str,
+    default_database: &str,
+) -> HashMap<String, HashMap<String, Vec<Record>>> {
+    let mut databases: HashMap<String, HashMap<String, Vec<Record>>> = HashMap::new();
+    for (table_name, table_records) in records {
+        for record in table_records {
+            let database_name = record
+                .dimensions()
+                .iter()
+                .find(|dimension| dimension.name() == tag_key)
+                .map(|dimension| dimension.value().to_string())
+                .unwrap_or_else(|| default_database.to_string());
+            databases
+                .entry(database_name)
+                .or_default()
+                .entry(table_name.clone())
+                .or_default()
+                .push(record);
+        }
+    }
+    databases
+}
+
+/// Drains per-table ingestion tasks, summing the records they dropped
+/// under `skip_missing_tables`. By default the first task error is
+/// returned immediately and remaining tasks run to completion in the
+/// background; with `fail_fast` the remaining tasks are aborted before the
+/// error is returned.
+async fn drain_ingestion_tasks(
+    mut tasks: FuturesUnordered<tokio::task::JoinHandle<Result<usize>>>,
+    abort_handles: Vec<tokio::task::AbortHandle>,
+    fail_fast: bool,
+) -> Result<usize> {
+    let mut records_dropped = 0;
+    let mut first_error: Option<anyhow::Error> = None;
+    while let Some(task) = tasks.next().await {
+        match task {
+            Ok(Ok(dropped)) => records_dropped += dropped,
+            Ok(Err(error)) => {
+                if !fail_fast {
+                    return Err(error);
+                }
+                if first_error.is_none() {
+                    for abort_handle in &abort_handles {
+                        abort_handle.abort();
+                    }
+                    first_error = Some(error);
+                }
+            }
+            Err(join_error) => {
+                if !join_error.is_cancelled() {
+                    return Err(join_error.into());
+                }
+            }
+        }
+    }
+    match first_error {
+        Some(error) => Err(error),
+        None => Ok(records_dropped),
+    }
+}
+
+fn success_response() -> Value {
+    build_response(200, &json!({ "message": "Success" }).to_string())
+}
+
+/// Success response for the line protocol path, reporting the parsed
+/// line counts, the tables touched, and how many records were written
+/// (and skipped, in lenient mode).
+fn summary_response(summary: &IngestionSummary) -> Value {
+    let body = json!({
+        "message": "Success",
+        "lines_parsed": summary.lines_parsed,
+        "records_written": summary.records_written,
+        "records_skipped": summary.lines_skipped,
+        "tables": summary.tables,
+        "concurrency_limit": summary.concurrency_limit,
+    });
+    build_response(200, &body.to_string())
+}
+
+fn error_response(status_code: u16, message: &str) -> Value {
+    build_response(status_code, &json!({ "error": message }).to_string())
+}
+
+/// InfluxDB version reported to pinging clients; 1.8 is the newest line
+/// whose write API matches what the connector accepts.
+const INFLUXDB_COMPAT_VERSION: &str = "1.8";
+
+/// 204 `/ping` response carrying the `X-Influxdb-Version` header
+/// InfluxDB clients check before writing.
+fn ping_response() -> Value {
+    let mut response = build_response(204, "");
+    response["headers"] = json!({ "X-Influxdb-Version": INFLUXDB_COMPAT_VERSION });
+    response
+}
+
+/// 200 `/health` response in the InfluxDB health JSON shape.
+fn health_response() -> Value {
+    let mut response = build_response(
+        200,
+        &json!({
+            "name": "influxdb",
+            "message": "ready for queries and writes",
+            "status": "pass",
+            "version": INFLUXDB_COMPAT_VERSION,
+        })
+        .to_string(),
+    );
+    response["headers"] = json!({ "X-Influxdb-Version": INFLUXDB_COMPAT_VERSION });
+    response
+}
+
+/// 405 response carrying the `Allow: POST` header RFC 9110 requires.
+fn method_not_allowed_response(method: &str) -> Value {
+    let mut response = error_response(
+        405,
+        &format!("Method {} is not allowed; use POST", method),
+    );
+    response["headers"] = json!({ "Allow": "POST" });
+    response
+}
+
+fn build_response(status_code: u16, body: &str) -> Value {
+    let mut response = json!({
+        "statusCode": status_code,
+        "body": body,
+    });
+    // The payload format 2.0 response shape includes a cookies array;
+    // 1.0 integrations reject unknown fields.
+    if response_payload_version_is_v2() {
+        response["cookies"] = json!([]);
+    }
+    response
+}
+
+/// Whether responses use the API Gateway payload format 2.0 shape, from
+/// the `response_payload_version` environment variable (`1.0`, the
+/// default, or `2.0`). The older `local_invocation` toggle — `cargo
+/// lambda watch` expects the 2.0 shape — is kept as an alias for `2.0`.
+fn response_payload_version_is_v2() -> bool {
+    match env::var("response_payload_version").ok().as_deref() {
+        Some("2.0") => true,
+        Some(_) => false,
+        None => records_builder::env_var_to_bool("local_invocation"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::EnvVarGuard;
+    use crate::timestream_utils::mock::MockTimestreamClient;
+    use aws_sdk_timestreamwrite as timestream_write;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    fn spawn_ingestion_task(
+        result: Result<usize>,
+        delay: Duration,
+        completed: Arc<AtomicBool>,
+    ) -> tokio::task::JoinHandle<Result<usize>> {
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            completed.store(true, Ordering::SeqCst);
+            result
+        })
+    }
+
+    #[tokio::test]
+    async fn test_drain_ingestion_tasks_fail_fast_cancels_remaining() {
+        let tasks = FuturesUnordered::new();
+        let mut abort_handles = Vec::new();
+
+        let failed = Arc::new(AtomicBool::new(false));
+        let task = spawn_ingestion_task(
+            Err(anyhow!("ingestion failed")),
+            Duration::from_millis(10),
+            Arc::clone(&failed),
+        );
+        abort_handles.push(task.abort_handle());
+        tasks.push(task);
+
+        let slow_completed = Arc::new(AtomicBool::new(false));
+        let slow_task = spawn_ingestion_task(
+            Ok(0),
+            Duration::from_secs(30),
+            Arc::clone(&slow_completed),
+        );
+        abort_handles.push(slow_task.abort_handle());
+        tasks.push(slow_task);
+
+        let result = drain_ingestion_tasks(tasks, abort_handles, true).await;
+        assert!(result.is_err());
+        assert!(
+            !slow_completed.load(Ordering::SeqCst),
+            "Slow task should have been aborted before completing"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drain_ingestion_tasks_default_returns_first_error() {
+        let tasks = FuturesUnordered::new();
+        let mut abort_handles = Vec::new();
+
+        let task = spawn_ingestion_task(
+            Err(anyhow!("ingestion failed")),
+            Duration::from_millis(10),
+            Arc::new(AtomicBool::new(false)),
+        );
+        abort_handles.push(task.abort_handle());
+        tasks.push(task);
+
+        let result = drain_ingestion_tasks(tasks, abort_handles, false).await;
+        assert_eq!(result.unwrap_err().to_string(), "ingestion failed");
+    }
+
+    #[tokio::test]
+    async fn test_drain_ingestion_tasks_all_succeed() {
+        let tasks = FuturesUnordered::new();
+        let mut abort_handles = Vec::new();
+        for dropped in 0..3 {
+            let task = spawn_ingestion_task(
+                Ok(dropped),
+                Duration::from_millis(1),
+                Arc::new(AtomicBool::new(false)),
+            );
+            abort_handles.push(task.abort_handle());
+            tasks.push(task);
+        }
+        assert_eq!(
+            drain_ingestion_tasks(tasks, abort_handles, true)
+                .await
+                .expect("All tasks succeeded"),
+            3
+        );
+    }
+
+    fn test_config() -> ConnectorConfig {
+        ConnectorConfig {
+            database_name: "lib_test_db".to_string(),
+            enable_database_creation: true,
+            enable_table_creation: true,
+            fail_fast: false,
+            measure_name_for_multi_measure_records: "influxdb-measure".to_string(),
+            sort_records_by_time: false,
+            skip_invalid_lines: false,
+            skip_missing_tables: false,
+            database_from_tag: None,
+        }
+    }
+
+    fn offline_test_client() -> Arc<timestream_write::Client> {
+        let config = timestream_write::Config::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .region(aws_config::Region::new("us-east-1"))
+            .credentials_provider(aws_credential_types::Credentials::new(
+                "test-access-key",
+                "test-secret-key",
+                None,
+                None,
+                "test",
+            ))
+            .build();
+        Arc::new(timestream_write::Client::from_conf(config))
+    }
+
+    fn test_records(table_names: &[&str]) -> HashMap<String, Vec<Record>> {
+        table_names
+            .iter()
+            .map(|table_name| {
+                (
+                    table_name.to_string(),
+                    vec![Record::builder().measure_name("influxdb-measure").build()],
+                )
+            })
+            .collect()
+    }
+
+    fn set_table_config_env_vars() {
+        env::set_var("enable_table_creation", "true");
+        env::set_var("mem_store_retention_period", "24");
+        env::set_var("mag_store_retention_period", "7");
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_creates_missing_table() {
+        set_table_config_env_vars();
+        // Successful writes feed the process-wide circuit breaker; hold
+        // the environment lock so the breaker tests are undisturbed.
+        let _env = EnvVarGuard::acquire();
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(timestream_utils::ClientError::ResourceNotFound(
+                "no such table".to_string(),
+            )));
+
+        // A unique database name keeps the existence cache from other
+        // tests out of this flow.
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_create_table".to_string(),
+            ..test_config()
+        };
+        handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+            .await
+            .expect("Failed to ingest with table creation enabled");
+        let calls = client.calls();
+        assert!(calls.iter().any(|call| call.starts_with("create_table")));
+        assert!(calls.iter().any(|call| call.starts_with("write_records")));
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_skips_creation_when_table_exists() {
+        set_table_config_env_vars();
+        let _env = EnvVarGuard::acquire();
+        let client = Arc::new(MockTimestreamClient::new());
+
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_table_exists".to_string(),
+            ..test_config()
+        };
+        handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+            .await
+            .expect("Failed to ingest into existing table");
+        let calls = client.calls();
+        assert!(!calls.iter().any(|call| call.starts_with("create_table")));
+        assert!(calls.iter().any(|call| call.starts_with("write_records")));
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_groups_records_by_database_tag() {
+        set_table_config_env_vars();
+        let _env = EnvVarGuard::acquire();
+        let client = Arc::new(MockTimestreamClient::new());
+        let tenant_record = |tenant: Option<&str>| {
+            let mut builder = Record::builder().measure_name("influxdb-measure");
+            if let Some(tenant) = tenant {
+                builder = builder.dimensions(
+                    timestream_write::types::Dimension::builder()
+                        .name("tenant")
+                        .value(tenant)
+                        .build()
+                        .unwrap(),
+                );
+            }
+            builder.build()
+        };
+        let records = HashMap::from([(
+            "readings".to_string(),
+            vec![
+                tenant_record(Some("tenant_db_acme")),
+                tenant_record(Some("tenant_db_globex")),
+                tenant_record(None),
+            ],
+        )]);
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_tenant_default".to_string(),
+            database_from_tag: Some("tenant".to_string()),
+            ..test_config()
+        };
+
+        handle_multi_table_ingestion(&client, &config, records)
+            .await
+            .expect("Failed to ingest tenant-routed records");
+        let calls = client.calls();
+        // One write per tenant database, with the untagged record falling
+        // back to the default database.
+        for database_name in [
+            "tenant_db_acme",
+            "tenant_db_globex",
+            "lib_test_db_tenant_default",
+        ] {
+            assert!(
+                calls
+                    .iter()
+                    .any(|call| call == &format!("write_records {} readings 1", database_name)),
+                "Expected a write to {}, got: {:?}",
+                database_name,
+                calls
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_rejects_missing_table_when_creation_disabled() {
+        set_table_config_env_vars();
+        env::set_var("region", "us-east-1");
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(timestream_utils::ClientError::ResourceNotFound(
+                "no such table".to_string(),
+            )));
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_no_table_creation".to_string(),
+            enable_table_creation: false,
+            ..test_config()
+        };
+
+        let error = handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+            .await
+            .expect_err("Missing table must be rejected when creation is disabled");
+        assert_eq!(
+            error.to_string(),
+            "Table readings does not exist in database lib_test_db_no_table_creation \
+            (region us-east-1) and table creation is not enabled; set \
+            enable_table_creation=true to create it"
+        );
+        // One existence check per resource and nothing else: no creation,
+        // no writes, no extra describes.
+        let calls = client.calls();
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| call.starts_with("describe_database"))
+                .count(),
+            1
+        );
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| call.starts_with("describe_table"))
+                .count(),
+            1
+        );
+        assert!(!calls
+            .iter()
+            .any(|call| call.starts_with("create_table") || call.starts_with("write_records")));
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_drops_missing_table_records_when_skip_enabled() {
+        set_table_config_env_vars();
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(timestream_utils::ClientError::ResourceNotFound(
+                "no such table".to_string(),
+            )));
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_skip_missing".to_string(),
+            enable_table_creation: false,
+            skip_missing_tables: true,
+            ..test_config()
+        };
+
+        let dropped =
+            handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+                .await
+                .expect("Missing table must be skipped, not rejected");
+        assert_eq!(dropped, 1);
+        assert!(!client
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("create_table") || call.starts_with("write_records")));
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_propagates_write_error() {
+        set_table_config_env_vars();
+        let _env = EnvVarGuard::acquire();
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .write_records_results
+            .lock()
+            .unwrap()
+            .push_back(Err(timestream_utils::ClientError::Other(anyhow!(
+                "validation error"
+            ))));
+
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_write_error".to_string(),
+            ..test_config()
+        };
+        assert!(
+            handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_creates_missing_database() {
+        set_table_config_env_vars();
+        let _env = EnvVarGuard::acquire();
+        env::set_var("enable_database_creation", "true");
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_database_results
+            .lock()
+            .unwrap()
+            .push_back(Err(timestream_utils::ClientError::ResourceNotFound(
+                "no such database".to_string(),
+            )));
+
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_create_db".to_string(),
+            ..test_config()
+        };
+        handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+            .await
+            .expect("Failed to ingest with database creation enabled");
+        let calls = client.calls();
+        assert!(calls
+            .iter()
+            .any(|call| call.starts_with("create_database lib_test_db_create_db")));
+        assert!(calls.iter().any(|call| call.starts_with("write_records")));
+    }
+
+    #[tokio::test]
+    async fn test_ingestion_rejects_missing_database_when_creation_disabled() {
+        set_table_config_env_vars();
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_database_results
+            .lock()
+            .unwrap()
+            .push_back(Err(timestream_utils::ClientError::ResourceNotFound(
+                "no such database".to_string(),
+            )));
+        let config = ConnectorConfig {
+            database_name: "lib_test_db_missing".to_string(),
+            enable_database_creation: false,
+            ..test_config()
+        };
+
+        let error = handle_multi_table_ingestion(&client, &config, test_records(&["readings"]))
+            .await
+            .expect_err("Missing database must be rejected when creation is disabled");
+        assert!(error.to_string().contains("Database lib_test_db_missing"));
+        assert!(error
+            .to_string()
+            .contains("set enable_database_creation=true"));
+        assert!(!client
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("create_database")));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_ingestion_limits_concurrency() {
+        set_table_config_env_vars();
+        let _env = EnvVarGuard::acquire();
+        // Other tests simulate throttling against the process-wide
+        // adaptive limiter; start from full concurrency.
+        timestream_utils::adaptive_concurrency().reset();
+        let client = Arc::new(MockTimestreamClient::new());
+        *client.write_delay.lock().unwrap() = Some(Duration::from_millis(20));
+        let table_names: Vec<String> = (0..NUM_BATCH_THREADS * 2)
+            .map(|index| format!("table_{}", index))
+            .collect();
+        let table_names: Vec<&str> =
+            table_names.iter().map(String::as_str).collect();
+
+        handle_multi_table_ingestion(&client, &test_config(), test_records(&table_names))
+            .await
+            .expect("Failed to ingest across many tables");
+        let max_concurrent = client
+            .max_concurrent_writes
+            .load(std::sync::atomic::Ordering::SeqCst);
+        assert!(
+            max_concurrent <= NUM_BATCH_THREADS,
+            "Semaphore must bound concurrent writes, saw {}",
+            max_concurrent
+        );
+        assert!(max_concurrent > 1, "Table ingestion should run concurrently");
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_decompresses_gzip_body() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        set_table_config_env_vars();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        // The handler resets the process-wide circuit breaker per
+        // invocation; hold the lock so the breaker tests are undisturbed.
+        let _env = EnvVarGuard::acquire();
+        let client = Arc::new(MockTimestreamClient::new());
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(
+                b"readings fuel=30i 1677605771000000000\n\
+                readings fuel=31i 1677605772000000000",
+            )
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+        let event = json!({
+            "body": base64::engine::general_purpose::STANDARD.encode(compressed),
+            "isBase64Encoded": true,
+            "headers": { "Content-Encoding": "gzip" },
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+        assert!(client
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("write_records") && call.ends_with("readings 2")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lambda_handler_times_out_with_503() {
+        set_table_config_env_vars();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.set("handler_timeout_seconds", "1");
+        let client = Arc::new(MockTimestreamClient::new());
+        // A write slower than the configured timeout; paused time makes
+        // both timers fire without real waiting.
+        *client.write_delay.lock().unwrap() = Some(Duration::from_secs(5));
+
+        let event = json!({ "body": "readings fuel=30i 1677605771000000000" });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 503);
+        assert_eq!(
+            response["body"].as_str().unwrap(),
+            json!({ "error": "handler_timeout" }).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_content_type_validation() {
+        set_table_config_env_vars();
+        let mut env_vars = EnvVarGuard::acquire();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let client = Arc::new(MockTimestreamClient::new());
+        let body = "readings fuel=30i 1677605771000000000";
+
+        // text/plain (with parameters) is the line protocol happy path.
+        let event = json!({
+            "body": body,
+            "headers": { "Content-Type": "text/plain; charset=utf-8" },
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+
+        // A Content-Type with no handler is rejected before parsing.
+        let event = json!({
+            "body": "<xml/>",
+            "headers": { "Content-Type": "application/xml" },
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 415);
+        assert!(
+            response["body"].as_str().unwrap().contains("application/xml"),
+            "Got: {}",
+            response["body"]
+        );
+
+        // strict_content_type=false restores the old accept-anything
+        // behavior for clients that cannot set headers.
+        env_vars.set("strict_content_type", "false");
+        let event = json!({
+            "body": body,
+            "headers": { "Content-Type": "application/xml" },
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_answers_ping_and_health_probes() {
+        let client = Arc::new(MockTimestreamClient::new());
+
+        // GET /ping in both payload format shapes.
+        for event in [
+            json!({ "httpMethod": "GET", "path": "/ping" }),
+            json!({
+                "requestContext": { "http": { "method": "GET" } },
+                "rawPath": "/ping",
+            }),
+        ] {
+            let response = lambda_handler(
+                &client,
+                lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+            )
+            .await
+            .expect("Handler returned an error");
+            assert_eq!(response["statusCode"], 204);
+            assert_eq!(response["headers"]["X-Influxdb-Version"], "1.8");
+        }
+
+        // GET /health reports the InfluxDB health JSON shape.
+        let event = json!({ "httpMethod": "GET", "path": "/health" });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["headers"]["X-Influxdb-Version"], "1.8");
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["status"], "pass");
+
+        // Probes never touch Timestream.
+        assert!(client.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_rejects_non_post_methods() {
+        let client = Arc::new(MockTimestreamClient::new());
+        for method in ["GET", "HEAD", "PUT", "DELETE"] {
+            // Payload format 1.0 carries the method in `httpMethod`.
+            let event = json!({ "httpMethod": method, "body": "" });
+            let response = lambda_handler(
+                &client,
+                lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+            )
+            .await
+            .expect("Handler returned an error");
+            assert_eq!(response["statusCode"], 405, "Method {}", method);
+            assert_eq!(response["headers"]["Allow"], "POST", "Method {}", method);
+
+            // Payload format 2.0 nests it under `requestContext.http`.
+            let event = json!({
+                "requestContext": { "http": { "method": method } },
+                "body": "",
+            });
+            let response = lambda_handler(
+                &client,
+                lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+            )
+            .await
+            .expect("Handler returned an error");
+            assert_eq!(response["statusCode"], 405, "Method {}", method);
+        }
+        assert!(client.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_accepts_post_method() {
+        set_table_config_env_vars();
+        // The handler resets the process-wide circuit breaker per
+        // invocation; hold the lock so the breaker tests are undisturbed.
+        let _env = EnvVarGuard::acquire();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let client = Arc::new(MockTimestreamClient::new());
+        let event = json!({
+            "httpMethod": "POST",
+            "body": "readings fuel=30i 1677605771000000000",
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_echoes_incoming_request_id() {
+        set_table_config_env_vars();
+        let _env = EnvVarGuard::acquire();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let client = Arc::new(MockTimestreamClient::new());
+        let event = json!({
+            "httpMethod": "POST",
+            "headers": { "X-Request-Id": "caller-trace-42" },
+            "body": "readings fuel=30i 1677605771000000000",
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["headers"]["X-Request-Id"], "caller-trace-42");
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_generates_request_id_and_keeps_headers() {
+        // A ping already carries its own headers; the correlation id is
+        // merged in rather than replacing them, and an event without an
+        // X-Request-Id or X-Amzn-Trace-Id header gets a generated id.
+        let client = Arc::new(MockTimestreamClient::new());
+        let event = json!({
+            "httpMethod": "GET",
+            "path": "/ping",
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 204);
+        assert_eq!(response["headers"]["X-Influxdb-Version"], "1.8");
+        let generated = response["headers"]["X-Request-Id"]
+            .as_str()
+            .expect("Missing generated request id");
+        assert!(!generated.is_empty());
+
+        // The ALB/API Gateway trace header is honored when the caller
+        // did not set an X-Request-Id of its own.
+        let event = json!({
+            "httpMethod": "GET",
+            "path": "/ping",
+            "headers": { "x-amzn-trace-id": "Root=1-67891233-abcdef012345678912345678" },
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(
+            response["headers"]["X-Request-Id"],
+            "Root=1-67891233-abcdef012345678912345678"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_database_override() {
+        set_table_config_env_vars();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.set("allowed_database_overrides", "staging_metrics, qa_metrics");
+        let client = Arc::new(MockTimestreamClient::new());
+        let body = "readings fuel=30i 1677605771000000000";
+
+        // An allowed override routes the write to the requested database.
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({
+                    "body": body,
+                    "queryStringParameters": { "db": "staging_metrics" },
+                }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+        assert!(client
+            .calls()
+            .iter()
+            .any(|call| call == "write_records staging_metrics readings 1"));
+
+        // An override outside the allowlist is rejected before parsing.
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({
+                    "body": body,
+                    "queryStringParameters": { "db": "prod_metrics" },
+                }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 403);
+
+        // Requests without the parameter keep using the configured database.
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({ "body": body }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+        assert!(!client
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("write_records prod_metrics")));
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_max_body_bytes() {
+        set_table_config_env_vars();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.set("max_body_bytes", "10000");
+        let client = Arc::new(MockTimestreamClient::new());
+
+        // A valid single-line body padded with a string field to an exact
+        // byte length.
+        let body_of_len = |len: usize| {
+            let prefix = "readings fuel=\"";
+            let suffix = "\" 1677605771000000000";
+            format!(
+                "{}{}{}",
+                prefix,
+                "x".repeat(len - prefix.len() - suffix.len()),
+                suffix
+            )
+        };
+
+        let at_limit = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({ "body": body_of_len(10000) }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(at_limit["statusCode"], 200);
+
+        let oversized = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({ "body": body_of_len(10001) }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        env_vars.remove("max_body_bytes");
+        assert_eq!(oversized["statusCode"], 413);
+        assert!(oversized["body"].as_str().unwrap().contains("10001 bytes"));
+        assert!(oversized["body"]
+            .as_str()
+            .unwrap()
+            .contains("limit of 10000 bytes"));
+
+        // With the variable unset the default Lambda payload limit applies.
+        let over_default = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({ "body": body_of_len(DEFAULT_MAX_BODY_BYTES + 1) }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(over_default["statusCode"], 413);
+        assert_eq!(
+            client
+                .calls()
+                .iter()
+                .filter(|call| call.starts_with("write_records"))
+                .count(),
+            1,
+            "Only the at-limit body should have been written"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_warmup_short_circuit() {
+        set_table_config_env_vars();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("enable_database_creation", "true");
+        env::set_var("enable_mag_store_writes", "false");
+        env::set_var("region", "us-east-1");
+        env::set_var("database_name", "lib_test_db");
+        let client = Arc::new(MockTimestreamClient::new());
+
+        // A warm-up ping validates the configuration and returns without
+        // touching Timestream, even when a body is present.
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(
+                json!({
+                    "warmup": true,
+                    "body": "readings fuel=30i 1677605771000000000",
+                }),
+                lambda_runtime::Context::default(),
+            ),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 200);
+        assert!(response["body"].as_str().unwrap().contains("Warmed"));
+        assert!(client.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lambda_handler_firehose_metric_stream_event() {
+        set_table_config_env_vars();
+        // The handler resets the process-wide circuit breaker per
+        // invocation; hold the lock so the breaker tests are undisturbed.
+        let _env = EnvVarGuard::acquire();
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        env::set_var("database_name", "lib_test_db");
+        let client = Arc::new(MockTimestreamClient::new());
+
+        let metric_line = r#"{"namespace":"AWS/EC2","metric_name":"CPUUtilization","dimensions":{"InstanceId":"i-01"},"timestamp":1677605771000,"value":{"max":12.5,"min":1.5,"sum":28.0,"count":4.0}}"#;
+        let event = json!({
+            "invocationId": "invocation-1",
+            "deliveryStreamArn": "arn:aws:firehose:us-east-1:123456789012:deliverystream/metrics",
+            "records": [
+                {
+                    "recordId": "record-1",
+                    "data": base64::engine::general_purpose::STANDARD.encode(metric_line),
+                },
+                {
+                    "recordId": "record-2",
+                    "data": base64::engine::general_purpose::STANDARD.encode("not json"),
+                },
+            ],
+        });
+        let response = lambda_handler(
+            &client,
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["records"][0]["recordId"], "record-1");
+        assert_eq!(response["records"][0]["result"], "Dropped");
+        assert_eq!(response["records"][1]["recordId"], "record-2");
+        assert_eq!(response["records"][1]["result"], "ProcessingFailed");
+        assert!(client.calls().iter().any(|call| {
+            call.starts_with("write_records") && call.ends_with("AWS_EC2_CPUUtilization 1")
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_line_protocol_skip_invalid_lines() {
+        set_table_config_env_vars();
+        // The handler resets the process-wide circuit breaker per
+        // invocation; hold the lock so the breaker tests are undisturbed.
+        let _env = EnvVarGuard::acquire();
+        let client = Arc::new(MockTimestreamClient::new());
+        let mut config = test_config();
+        config.skip_invalid_lines = true;
+
+        let summary = ingest_line_protocol(
+            &client,
+            &config,
+            "readings fuel=30i 1677605771000000000\n\
+            readings,fleet= 1677605772000000000\n\
+            readings fuel=32i 1677605773000000000",
+            &TimeUnit::Nanoseconds,
+        )
+        .await
+        .expect("Lenient mode must ingest the valid lines");
+        assert_eq!(summary.lines_parsed, 2);
+        assert_eq!(summary.lines_skipped, 1);
+        assert_eq!(summary.records_written, 2);
+        assert!(client
+            .calls()
+            .iter()
+            .any(|call| call == "write_records lib_test_db readings 2"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_string_skip_drops_measureless_record() {
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.set("empty_string_behavior", "skip");
+        let client = Arc::new(MockTimestreamClient::new());
+        let config = test_config();
+
+        // The second line's only field is skipped, leaving a record with
+        // no measure values; it must be dropped and counted as skipped
+        // rather than poisoning the batch, which Timestream would reject
+        // wholesale.
+        let summary = ingest_line_protocol(
+            &client,
+            &config,
+            "readings fuel=30i 1677605771000000000\n\
+            readings status=\"\" 1677605772000000000",
+            &TimeUnit::Nanoseconds,
+        )
+        .await
+        .expect("Skip policy must not fail the payload");
+        assert_eq!(summary.lines_parsed, 2);
+        assert_eq!(summary.lines_skipped, 1);
+        assert_eq!(summary.records_written, 1);
+        assert!(client
+            .calls()
+            .iter()
+            .any(|call| call == "write_records lib_test_db readings 1"));
+    }
+
+    #[tokio::test]
+    async fn test_u64_overflow_skip_drops_measureless_record() {
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.set("u64_overflow_behavior", "skip");
+        let client = Arc::new(MockTimestreamClient::new());
+        let config = test_config();
+
+        // A metric whose only field overflows BIGINT is emptied by the
+        // skip, and the whole payload consists of that one record: no
+        // write may be attempted, and the drop shows up as skipped.
+        let summary = ingest_line_protocol(
+            &client,
+            &config,
+            "readings big=18446744073709551615u 1677605771000000000",
+            &TimeUnit::Nanoseconds,
+        )
+        .await
+        .expect("Skip policy must not fail the payload");
+        assert_eq!(summary.lines_parsed, 1);
+        assert_eq!(summary.lines_skipped, 1);
+        assert_eq!(summary.records_written, 0);
+        assert!(!client
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("write_records")));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_line_protocol_parse_error() {
+        // A malformed payload must fail before any Timestream call is made,
+        // so an offline client is sufficient.
+        let result = ingest_line_protocol(
+            &offline_test_client(),
+            &test_config(),
+            "readings,fleet= 1677605771000000000",
+            &TimeUnit::Nanoseconds,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_request_returns_documented_error() {
+        let event = json!({
+            "rawPath": "/api/v2/delete",
+            "body": json!({
+                "start": "2023-01-01T00:00:00Z",
+                "stop": "2023-01-02T00:00:00Z",
+                "predicate": "_measurement=\"readings\"",
+            }).to_string(),
+        });
+        let response = lambda_handler(
+            &offline_test_client(),
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 501);
+        assert!(response["body"]
+            .as_str()
+            .unwrap()
+            .contains("delete API is not supported"));
+
+        // The payload format 1.0 path field is handled too.
+        let event = json!({ "path": "/api/v2/delete", "body": "" });
+        let response = lambda_handler(
+            &offline_test_client(),
+            lambda_runtime::LambdaEvent::new(event, lambda_runtime::Context::default()),
+        )
+        .await
+        .expect("Handler returned an error");
+        assert_eq!(response["statusCode"], 501);
+    }
+
+    #[test]
+    fn test_connector_config_from_env() {
+        // database_name deviates from the baseline value, so hold the
+        // environment lock and hand the baseline back afterwards instead
+        // of restoring a possibly-unset pre-test state.
+        let _env = EnvVarGuard::acquire();
+        env::set_var("database_name", "config_test_db");
+        env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+        let config = ConnectorConfig::from_env().expect("Failed to resolve config");
+        assert_eq!(config.database_name, "config_test_db");
+        assert_eq!(
+            config.measure_name_for_multi_measure_records,
+            "influxdb-measure"
+        );
+        env::set_var("database_name", "lib_test_db");
+    }
+
+    #[test]
+    fn test_otlp_response_shapes() {
+        let full_success = otlp::OtlpConversion::default();
+        let response = otlp_response(&full_success);
+        assert_eq!(response["statusCode"], 200);
+        assert_eq!(response["body"], "{}");
+
+        let partial = otlp::OtlpConversion {
+            metrics: vec![],
+            rejected_data_points: 3,
+            rejected_types: vec!["exponential histogram".to_string()],
+        };
+        let response = otlp_response(&partial);
+        assert_eq!(response["statusCode"], 200);
+        let body: Value =
+            serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["partialSuccess"]["rejectedDataPoints"], 3);
+        assert!(body["partialSuccess"]["errorMessage"]
+            .as_str()
+            .unwrap()
+            .contains("exponential histogram"));
+    }
+
+    #[test]
+    fn test_response_payload_version_controls_cookies_field() {
+        let mut env_vars = EnvVarGuard::acquire();
+
+        // Format 1.0 (the default) rejects unknown fields, so the 2.0
+        // cookies array must be absent.
+        let response = build_response(200, "body");
+        assert!(response.get("cookies").is_none());
+        env_vars.set("response_payload_version", "1.0");
+        assert!(build_response(200, "body").get("cookies").is_none());
+
+        env_vars.set("response_payload_version", "2.0");
+        assert_eq!(build_response(200, "body")["cookies"], json!([]));
+
+        // local_invocation is a backward-compatible alias for 2.0, but an
+        // explicit version wins over it.
+        env_vars.remove("response_payload_version");
+        env_vars.set("local_invocation", "true");
+        assert_eq!(build_response(200, "body")["cookies"], json!([]));
+        env_vars.set("response_payload_version", "1.0");
+        assert!(build_response(200, "body").get("cookies").is_none());
+    }
+
+    #[test]
+    fn test_get_precision_object() {
+        let event = json!({ "queryStringParameters": { "precision": "ms" } });
+        assert_eq!(get_precision(&event), Some("ms".to_string()));
+    }
+
+    #[test]
+    fn test_get_precision_array() {
+        let event = json!({ "queryStringParameters": { "precision": ["us"] } });
+        assert_eq!(get_precision(&event), Some("us".to_string()));
+    }
+
+    #[test]
+    fn test_get_precision_missing() {
+        let event = json!({ "queryStringParameters": {} });
+        assert_eq!(get_precision(&event), None);
+        let event = json!({});
+        assert_eq!(get_precision(&event), None);
+    }
+
+    #[test]
+    fn test_get_precision_from_path_segment() {
+        // REST-style routes in both payload format shapes.
+        let event = json!({ "path": "/write/ms" });
+        assert_eq!(get_precision(&event), Some("ms".to_string()));
+        let event = json!({ "rawPath": "/write/us/" });
+        assert_eq!(get_precision(&event), Some("us".to_string()));
+
+        // A trailing segment that is not a unit is not a precision.
+        let event = json!({ "rawPath": "/api/v2/write" });
+        assert_eq!(get_precision(&event), None);
+    }
+
+    #[test]
+    fn test_get_precision_query_overrides_path() {
+        let event = json!({
+            "path": "/write/s",
+            "queryStringParameters": { "precision": "ns" },
+        });
+        assert_eq!(get_precision(&event), Some("ns".to_string()));
+    }
+
+    #[test]
+    fn test_precision_from_str_short_forms() {
+        assert_eq!(precision_from_str("ns"), Some(TimeUnit::Nanoseconds));
+        assert_eq!(precision_from_str("us"), Some(TimeUnit::Microseconds));
+        assert_eq!(precision_from_str("ms"), Some(TimeUnit::Milliseconds));
+        assert_eq!(precision_from_str("s"), Some(TimeUnit::Seconds));
+    }
+
+    #[test]
+    fn test_precision_from_str_is_case_insensitive() {
+        assert_eq!(precision_from_str("MS"), Some(TimeUnit::Milliseconds));
+        assert_eq!(precision_from_str("Ns"), Some(TimeUnit::Nanoseconds));
+        assert_eq!(precision_from_str("S"), Some(TimeUnit::Seconds));
+    }
+
+    #[test]
+    fn test_precision_from_str_spelled_out_aliases() {
+        assert_eq!(
+            precision_from_str("nanoseconds"),
+            Some(TimeUnit::Nanoseconds)
+        );
+        assert_eq!(
+            precision_from_str("microseconds"),
+            Some(TimeUnit::Microseconds)
+        );
+        assert_eq!(
+            precision_from_str("Milliseconds"),
+            Some(TimeUnit::Milliseconds)
+        );
+        assert_eq!(precision_from_str("seconds"), Some(TimeUnit::Seconds));
+    }
+
+    #[test]
+    fn test_precision_from_str_rejects_unknown() {
+        assert_eq!(precision_from_str("minutes"), None);
+        assert_eq!(precision_from_str(""), None);
+    }
+}
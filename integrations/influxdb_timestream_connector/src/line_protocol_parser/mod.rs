@@ -0,0 +1,185 @@
+use crate::error::ConnectorError;
+use crate::metric::{FieldValue, Metric};
+use anyhow::{anyhow, Result};
+use aws_sdk_timestreamwrite::types::TimeUnit;
+use influxdb_line_protocol::{parse_lines, ParsedLine};
+use std::env;
+
+#[cfg(test)]
+mod tests;
+
+/// How much of an offending line is echoed back in parse errors.
+const MAX_ERROR_LINE_LENGTH: usize = 200;
+
+/// Per-line length limit applied when `max_line_bytes` is unset: far
+/// above Timestream's record limits, but small enough that one missing
+/// newline cannot make the parser walk megabytes before failing.
+pub const DEFAULT_MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// The per-line length limit from the optional `max_line_bytes`
+/// environment variable.
+fn max_line_bytes() -> usize {
+    env::var("max_line_bytes")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_MAX_LINE_BYTES)
+}
+
+/// Optional cap on the number of lines per request, from the
+/// `max_lines_per_request` environment variable. Unlimited when unset.
+fn max_lines_per_request() -> Option<usize> {
+    env::var("max_lines_per_request")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+}
+
+/// Parses a line protocol payload into owned `Metric`s. Errors identify
+/// the offending line by its 1-based number and content.
+pub fn parse_line_protocol(line_protocol: &str) -> Result<Vec<Metric>, ConnectorError> {
+    let (metrics, _) = parse_line_protocol_with_mode(line_protocol, false)?;
+    Ok(metrics)
+}
+
+/// Parses a line protocol payload, optionally skipping malformed lines.
+/// In strict mode (`skip_invalid_lines` false) the first malformed line
+/// fails the whole payload; in lenient mode malformed lines are collected
+/// as error messages and the remaining lines parse normally.
+pub fn parse_line_protocol_with_mode(
+    line_protocol: &str,
+    skip_invalid_lines: bool,
+) -> Result<(Vec<Metric>, Vec<String>), ConnectorError> {
+    let mut metrics: Vec<Metric> = Vec::new();
+    let mut skipped_lines: Vec<String> = Vec::new();
+    let line_limit = max_line_bytes();
+    let line_count_cap = max_lines_per_request();
+    for (index, line) in line_protocol.lines().enumerate() {
+        // The count cap fails the request outright in both modes: a
+        // producer sending too many lines should shard its batches, not
+        // have the tail silently skipped.
+        if let Some(cap) = line_count_cap {
+            if index >= cap {
+                return Err(ConnectorError::Validation(format!(
+                    "Request exceeds the configured max_lines_per_request limit of {} lines",
+                    cap
+                )));
+            }
+        }
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        // Reject oversized lines before the parser walks them; the error
+        // echoes only the usual truncated prefix, never the whole line.
+        if line.len() > line_limit {
+            let error = ConnectorError::Parse {
+                line: index + 1,
+                content: line.chars().take(MAX_ERROR_LINE_LENGTH).collect(),
+                message: format!(
+                    "Line is {} bytes, above the max_line_bytes limit of {} bytes",
+                    line.len(),
+                    line_limit
+                ),
+            };
+            if skip_invalid_lines {
+                tracing::warn!("Skipping oversized line: {}", error);
+                skipped_lines.push(error.to_string());
+                continue;
+            }
+            return Err(error);
+        }
+        for parsed_line in parse_lines(line) {
+            let metric = match parsed_line {
+                Ok(parsed_line) => parsed_line_to_metric(parsed_line),
+                Err(error) => Err(anyhow!("Failed to parse line: {}", error)),
+            }
+            .map_err(|error| ConnectorError::Parse {
+                line: index + 1,
+                content: line.chars().take(MAX_ERROR_LINE_LENGTH).collect(),
+                message: format!("{:#}", error),
+            });
+            match metric {
+                Ok(metric) => metrics.push(metric),
+                Err(error) if skip_invalid_lines => {
+                    tracing::warn!("Skipping invalid line: {}", error);
+                    skipped_lines.push(error.to_string());
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+    Ok((metrics, skipped_lines))
+}
+
+/// Converts a borrowed `ParsedLine` into an owned `Metric`.
+pub fn parsed_line_to_metric(parsed_line: ParsedLine) -> Result<Metric> {
+    let mut new_tags: Vec<(String, String)> = Vec::new();
+    if let Some(tag_set) = &parsed_line.series.tag_set {
+        for tag in tag_set {
+            new_tags.push((tag.0.to_string(), tag.1.to_string()));
+        }
+    }
+
+    let mut new_fields: Vec<(String, FieldValue)> = Vec::new();
+    for field in &parsed_line.field_set {
+        let field_value = match &field.1 {
+            influxdb_line_protocol::FieldValue::I64(value) => FieldValue::I64(*value),
+            influxdb_line_protocol::FieldValue::U64(value) => FieldValue::U64(*value),
+            influxdb_line_protocol::FieldValue::F64(value) => FieldValue::F64(*value),
+            influxdb_line_protocol::FieldValue::Boolean(value) => FieldValue::Boolean(*value),
+            influxdb_line_protocol::FieldValue::String(value) => {
+                FieldValue::String(value.to_string())
+            }
+        };
+        new_fields.push((field.0.to_string(), field_value));
+    }
+
+    let timestamp = parsed_line
+        .timestamp
+        .ok_or_else(|| anyhow!("Point is missing a timestamp"))?;
+
+    // Under `per_line_precision`, each line's time unit is inferred from
+    // its timestamp magnitude (as InfluxDB itself does) and the timestamp
+    // is normalized to nanoseconds, matching the JSON parser's handling
+    // of mixed-precision payloads.
+    let timestamp = if crate::records_builder::env_var_to_bool("per_line_precision") {
+        crate::json_parser::timestamp_to_nanos(
+            timestamp,
+            &infer_precision_from_timestamp(timestamp),
+        )?
+    } else {
+        timestamp
+    };
+
+    // A line without tags maps to `None`, not `Some(vec![])`, so
+    // downstream emptiness checks see one consistent representation.
+    let metric = Metric::new(
+        parsed_line.series.measurement.to_string(),
+        if new_tags.is_empty() {
+            None
+        } else {
+            Some(new_tags)
+        },
+        new_fields,
+        timestamp,
+    );
+    metric.validate()?;
+    Ok(metric)
+}
+
+/// Infers a timestamp's time unit from its decimal digit count, the
+/// heuristic InfluxDB uses for precision-less writes: present-day epoch
+/// timestamps have 19 digits in nanoseconds, 16 in microseconds, 13 in
+/// milliseconds, and 10 in seconds. Counts between the buckets round to
+/// the nearest unit; anything at or below 12 digits reads as seconds.
+pub fn infer_precision_from_timestamp(timestamp: i64) -> TimeUnit {
+    let digits = if timestamp == 0 {
+        1
+    } else {
+        timestamp.unsigned_abs().to_string().len()
+    };
+    match digits {
+        18.. => TimeUnit::Nanoseconds,
+        15..=17 => TimeUnit::Microseconds,
+        13..=14 => TimeUnit::Milliseconds,
+        _ => TimeUnit::Seconds,
+    }
+}
@@ -0,0 +1,314 @@
+use super::*;
+
+#[test]
+fn test_parse_basic_line() {
+    let metrics = parse_line_protocol("readings,fleet=Alberta fuel=30i 1677605771000000000")
+        .expect("Failed to parse valid line protocol");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].name(), "readings");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![("fleet".to_string(), "Alberta".to_string())])
+    );
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![("fuel".to_string(), FieldValue::I64(30))]
+    );
+    assert_eq!(metrics[0].timestamp(), 1677605771000000000);
+}
+
+#[test]
+fn test_parse_multiple_lines() {
+    let line_protocol = "readings,fleet=Alberta fuel=30i 1677605771000000000\n\
+        readings,fleet=Zurich fuel=40.5 1677605772000000000";
+    let metrics =
+        parse_line_protocol(line_protocol).expect("Failed to parse valid line protocol");
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(
+        metrics[1].fields(),
+        &vec![("fuel".to_string(), FieldValue::F64(40.5))]
+    );
+}
+
+#[test]
+fn test_parse_all_field_value_types() {
+    let metrics = parse_line_protocol(
+        "readings count=1i,total=2u,load=0.5,active=true,state=\"ok\" 1677605771000000000",
+    )
+    .expect("Failed to parse valid line protocol");
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![
+            ("count".to_string(), FieldValue::I64(1)),
+            ("total".to_string(), FieldValue::U64(2)),
+            ("load".to_string(), FieldValue::F64(0.5)),
+            ("active".to_string(), FieldValue::Boolean(true)),
+            ("state".to_string(), FieldValue::String("ok".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_scientific_notation_float() {
+    let metrics = parse_line_protocol("readings fuel=1.23e10 1677605771000000000")
+        .expect("Failed to parse scientific notation float");
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![("fuel".to_string(), FieldValue::F64(1.23e10))]
+    );
+
+    let metrics = parse_line_protocol("readings fuel=-4.56E-3 1677605771000000000")
+        .expect("Failed to parse scientific notation float");
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![("fuel".to_string(), FieldValue::F64(-4.56e-3))]
+    );
+}
+
+#[test]
+fn test_parse_scientific_notation_overflow_to_infinity() {
+    // 1e309 exceeds f64::MAX and parses to infinity; the records builder
+    // is responsible for rejecting or replacing non-finite values.
+    let metrics = parse_line_protocol("readings fuel=1e309 1677605771000000000")
+        .expect("Failed to parse overflowing scientific notation float");
+    match metrics[0].fields()[0].1 {
+        FieldValue::F64(value) => assert!(value.is_infinite()),
+        ref other => panic!("Expected F64, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_invalid_line() {
+    assert!(parse_line_protocol("readings,fleet= 1677605771000000000").is_err());
+}
+
+#[test]
+fn test_parse_error_includes_line_number_and_content() {
+    let line_protocol = "readings fuel=30i 1677605771000000000\n\
+        readings fuel=31i 1677605772000000000\n\
+        readings,fleet= 1677605773000000000\n\
+        readings fuel=33i 1677605774000000000";
+    let error = parse_line_protocol(line_protocol)
+        .expect_err("Malformed third line must fail to parse");
+    let ConnectorError::Parse { line, content, .. } = error else {
+        panic!("Expected a Parse error, got: {}", error);
+    };
+    assert_eq!(line, 3);
+    assert!(content.contains("readings,fleet="), "Got line: {}", content);
+}
+
+#[test]
+fn test_parse_error_names_middle_line() {
+    let line_protocol = "readings fuel=30i 1677605771000000000\n\
+        readings,fleet= 1677605772000000000\n\
+        readings fuel=32i 1677605773000000000";
+    let error = parse_line_protocol(line_protocol)
+        .expect_err("Malformed middle line must fail to parse");
+    assert!(
+        error.to_string().contains("Line 2"),
+        "Got error: {}",
+        error
+    );
+}
+
+#[test]
+fn test_parse_error_truncates_long_lines() {
+    let long_line = format!("readings fuel={}", "9".repeat(400));
+    let error = parse_line_protocol(&long_line)
+        .expect_err("Malformed long line must fail to parse");
+    let message = error.to_string();
+    assert!(message.contains("Line 1"), "Got error: {}", message);
+    assert!(
+        message.len() < 300,
+        "Line content must be truncated to 200 characters, got: {}",
+        message
+    );
+}
+
+#[test]
+fn test_parse_lenient_mode_skips_invalid_lines() {
+    let line_protocol = "readings fuel=30i 1677605771000000000\n\
+        readings,fleet= 1677605772000000000\n\
+        readings fuel=32i 1677605773000000000";
+    let (metrics, skipped_lines) = parse_line_protocol_with_mode(line_protocol, true)
+        .expect("Lenient mode must not fail on a malformed line");
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].fields()[0].1, FieldValue::I64(30));
+    assert_eq!(metrics[1].fields()[0].1, FieldValue::I64(32));
+    assert_eq!(skipped_lines.len(), 1);
+    assert!(skipped_lines[0].contains("Line 2"), "Got: {}", skipped_lines[0]);
+
+    // Strict mode still fails the whole payload.
+    assert!(parse_line_protocol(line_protocol).is_err());
+}
+
+#[test]
+fn test_parse_escaped_delimiters_in_identifiers() {
+    // Escaped commas, spaces, and equals in the measurement and tag set
+    // unescape to the literal characters.
+    let metrics = parse_line_protocol(
+        "my\\ reading\\,s,tag\\ key\\==va\\,lue fuel=30i 1677605771000000000",
+    )
+    .expect("Failed to parse escaped identifiers");
+    assert_eq!(metrics[0].name(), "my reading,s");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![("tag key=".to_string(), "va,lue".to_string())])
+    );
+}
+
+#[test]
+fn test_parse_escaped_string_field_values() {
+    // Backslashes and quotes in string values use the `\\` and `\"`
+    // escapes; multi-byte text passes through untouched.
+    let metrics = parse_line_protocol(
+        "readings note=\"path\\\\to \\\"dir\\\"\",city=\"Zürich 🚀\" 1677605771000000000",
+    )
+    .expect("Failed to parse escaped string values");
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![
+            (
+                "note".to_string(),
+                FieldValue::String("path\\to \"dir\"".to_string())
+            ),
+            (
+                "city".to_string(),
+                FieldValue::String("Zürich 🚀".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_duplicate_field_keys() {
+    assert!(
+        parse_line_protocol("readings fuel=30i,fuel=31i 1677605771000000000").is_err()
+    );
+}
+
+#[test]
+fn test_parse_missing_timestamp() {
+    assert!(parse_line_protocol("readings fuel=30i").is_err());
+}
+
+#[test]
+fn test_max_line_bytes_guard() {
+    // A line with an exact total byte length, valid apart from its size.
+    let line_of_len = |len: usize| {
+        let prefix = "readings fuel=\"";
+        let suffix = "\" 1677605771000000000";
+        format!(
+            "{}{}{}",
+            prefix,
+            "x".repeat(len - prefix.len() - suffix.len()),
+            suffix
+        )
+    };
+
+    // Just under and exactly at the limit parse normally.
+    assert!(parse_line_protocol(&line_of_len(DEFAULT_MAX_LINE_BYTES - 1)).is_ok());
+    assert!(parse_line_protocol(&line_of_len(DEFAULT_MAX_LINE_BYTES)).is_ok());
+
+    // One byte over is rejected before the parser walks the line, with
+    // the line number and observed length.
+    let error = parse_line_protocol(&line_of_len(DEFAULT_MAX_LINE_BYTES + 1))
+        .expect_err("Oversized line must be rejected");
+    let message = error.to_string();
+    assert!(message.contains("Line 1"), "Got: {}", message);
+    assert!(
+        message.contains(&format!("{} bytes", DEFAULT_MAX_LINE_BYTES + 1)),
+        "Got: {}",
+        message
+    );
+    assert!(message.contains("max_line_bytes"), "Got: {}", message);
+
+    // Lenient mode skips the oversized line and keeps the rest.
+    let payload = format!(
+        "{}\nreadings fuel=30i 1677605771000000000",
+        line_of_len(DEFAULT_MAX_LINE_BYTES + 1)
+    );
+    let (metrics, skipped_lines) =
+        parse_line_protocol_with_mode(&payload, true).expect("Lenient mode must not fail");
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(skipped_lines.len(), 1);
+}
+
+#[test]
+fn test_max_lines_per_request_cap() {
+    let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+
+    let payload = "readings fuel=30i 1677605771000000000\n".repeat(11);
+    env_vars.set("max_lines_per_request", "10");
+    let result = parse_line_protocol(&payload);
+    env_vars.remove("max_lines_per_request");
+    let error = result.expect_err("A request above the line cap must be rejected");
+    assert!(
+        error
+            .to_string()
+            .contains("max_lines_per_request limit of 10"),
+        "Got: {}",
+        error
+    );
+
+    // Without the cap the same payload parses.
+    assert_eq!(parse_line_protocol(&payload).unwrap().len(), 11);
+}
+
+#[test]
+fn test_infer_precision_from_timestamp_digit_buckets() {
+    use aws_sdk_timestreamwrite::types::TimeUnit;
+
+    // 19, 16, 13, and 10 digits: the same instant in each unit.
+    assert_eq!(
+        infer_precision_from_timestamp(1677605771000000000),
+        TimeUnit::Nanoseconds
+    );
+    assert_eq!(
+        infer_precision_from_timestamp(1677605771000000),
+        TimeUnit::Microseconds
+    );
+    assert_eq!(
+        infer_precision_from_timestamp(1677605771000),
+        TimeUnit::Milliseconds
+    );
+    assert_eq!(
+        infer_precision_from_timestamp(1677605771),
+        TimeUnit::Seconds
+    );
+    assert_eq!(infer_precision_from_timestamp(0), TimeUnit::Seconds);
+}
+
+#[test]
+fn test_per_line_precision_normalizes_mixed_timestamps() {
+    let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+
+    env_vars.set("per_line_precision", "true");
+    let metrics = parse_line_protocol(
+        "readings fuel=30i 1677605771\n\
+        readings fuel=31i 1677605771000\n\
+        readings fuel=32i 1677605771000000\n\
+        readings fuel=33i 1677605771000000000",
+    )
+    .expect("Failed to parse mixed-precision batch");
+    // Every line normalizes to the same nanosecond instant.
+    for metric in &metrics {
+        assert_eq!(metric.timestamp(), 1677605771000000000);
+    }
+}
+
+#[test]
+fn test_parse_line_without_tags_has_no_tag_set() {
+    let metrics = parse_line_protocol("readings fuel=30i 1677605771000000000")
+        .expect("Failed to parse tagless line");
+    assert_eq!(metrics.len(), 1);
+    // No tags must map to `None`, not `Some(vec![])`.
+    assert_eq!(metrics[0].tags(), &None);
+
+    let metrics = parse_line_protocol("readings,fleet=Alberta fuel=30i 1677605771000000000")
+        .expect("Failed to parse tagged line");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![("fleet".to_string(), "Alberta".to_string())])
+    );
+}
@@ -0,0 +1,37 @@
+use influxdb_timestream_connector::{lambda_handler, records_builder, timestream_utils};
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde_json::Value;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Shared write client for the lifetime of the execution environment.
+/// Built lazily on the first invocation (including warm-up pings) and
+/// rebuilt if its endpoint discovery reload task dies, so a stale client
+/// never outlives a single failed invocation.
+static CLIENT: timestream_utils::ManagedClient<aws_sdk_timestreamwrite::Client> =
+    timestream_utils::ManagedClient::new();
+
+async fn build_client() -> anyhow::Result<(aws_sdk_timestreamwrite::Client, Arc<AtomicBool>)> {
+    let region = timestream_utils::resolve_region()?;
+    timestream_utils::get_connection_with_health(&region, None).await
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false)
+        .without_time()
+        .init();
+
+    records_builder::validate_all_env_variables()?;
+
+    run(service_fn(|event: LambdaEvent<Value>| async {
+        let client = CLIENT.get_or_build(build_client).await?;
+        lambda_handler(&client, event).await
+    }))
+    .await
+}
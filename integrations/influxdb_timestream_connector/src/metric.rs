@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+
+/// The maximum number of decimal places `float_precision` may request.
+/// `f64` carries at most 15 significant decimal digits.
+pub const MAX_FLOAT_PRECISION: usize = 15;
+
+/// An owned field value parsed from a line protocol point. In JSON the
+/// variants map to untagged primitives: integers become `I64` (or `U64`
+/// beyond `i64::MAX`), floats `F64`, and so on. Nested arrays and objects
+/// are preserved as `Json`, which no Timestream measure type represents
+/// directly; the record builder stringifies or rejects them depending on
+/// `stringify_unsupported_fields`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Boolean(bool),
+    String(String),
+    Json(serde_json::Value),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::I64(value) => write!(f, "{}", value),
+            FieldValue::U64(value) => write!(f, "{}", value),
+            FieldValue::F64(value) => match float_format() {
+                FloatFormat::Fixed(precision) => write!(f, "{:.prec$}", value, prec = precision),
+                FloatFormat::Preserve => write!(f, "{}", value),
+            },
+            FieldValue::Boolean(value) => write!(f, "{}", value),
+            FieldValue::String(value) => write!(f, "{}", value),
+            FieldValue::Json(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Decimal places used by `float_format = fixed` when `float_precision`
+/// is not set.
+pub const DEFAULT_FIXED_FLOAT_PRECISION: usize = 6;
+
+/// How `FieldValue::F64` measure values are rendered. `Preserve` uses
+/// Rust's shortest round-trip formatting, so a parsed `4.6` is stored
+/// as `4.6` rather than a binary expansion like `4.5999999...`; `Fixed`
+/// pins the number of decimal places.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FloatFormat {
+    Preserve,
+    Fixed(usize),
+}
+
+/// Resolves the float rendering from the optional `float_format` and
+/// `float_precision` environment variables. An explicit
+/// `float_format = preserve` wins over a set `float_precision`;
+/// `float_format = fixed` takes its decimal count from
+/// `float_precision`, falling back to
+/// `DEFAULT_FIXED_FLOAT_PRECISION`. Unset (or unrecognized), the
+/// historical behavior applies: fixed decimals when `float_precision`
+/// is set, shortest round-trip otherwise.
+fn float_format() -> FloatFormat {
+    match env::var("float_format").ok().as_deref() {
+        Some("preserve") => FloatFormat::Preserve,
+        Some("fixed") => {
+            FloatFormat::Fixed(float_precision().unwrap_or(DEFAULT_FIXED_FLOAT_PRECISION))
+        }
+        _ => match float_precision() {
+            Some(precision) => FloatFormat::Fixed(precision),
+            None => FloatFormat::Preserve,
+        },
+    }
+}
+
+/// Returns the number of decimal places to emit for `FieldValue::F64`,
+/// read from the optional `float_precision` environment variable.
+/// Values outside 0..=15 or that fail to parse are ignored.
+fn float_precision() -> Option<usize> {
+    let precision = env::var("float_precision").ok()?.parse::<usize>().ok()?;
+    if precision <= MAX_FLOAT_PRECISION {
+        Some(precision)
+    } else {
+        None
+    }
+}
+
+/// An owned representation of a single parsed line protocol point. The
+/// JSON form uses `measurement` for the name and objects for the tag and
+/// field sets: `{"measurement": "...", "tags": {...}, "fields": {...},
+/// "timestamp": 123}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metric {
+    #[serde(rename = "measurement")]
+    name: String,
+    #[serde(default, with = "tag_map")]
+    tags: Option<Vec<(String, String)>>,
+    #[serde(with = "field_map")]
+    fields: Vec<(String, FieldValue)>,
+    timestamp: i64,
+}
+
+impl Metric {
+    pub fn new(
+        name: String,
+        tags: Option<Vec<(String, String)>>,
+        fields: Vec<(String, FieldValue)>,
+        timestamp: i64,
+    ) -> Self {
+        Metric {
+            name,
+            tags,
+            fields,
+            timestamp,
+        }
+    }
+
+    /// Checks the structural invariants Timestream relies on: a non-empty
+    /// measurement name and no duplicate tag or field keys. `new` performs
+    /// no validation for backwards compatibility; the parser calls this on
+    /// every constructed metric.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("Metric has an empty measurement name"));
+        }
+        if let Some(tags) = &self.tags {
+            if let Some(duplicate) = first_duplicate_key(tags.iter().map(|tag| &tag.0)) {
+                return Err(anyhow!(
+                    "Metric {} has a duplicate tag key: {}",
+                    self.name,
+                    duplicate
+                ));
+            }
+        }
+        if let Some(duplicate) = first_duplicate_key(self.fields.iter().map(|field| &field.0)) {
+            return Err(anyhow!(
+                "Metric {} has a duplicate field key: {}",
+                self.name,
+                duplicate
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn tags(&self) -> &Option<Vec<(String, String)>> {
+        &self.tags
+    }
+
+    pub fn fields(&self) -> &Vec<(String, FieldValue)> {
+        &self.fields
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// Decomposes the metric into its owned parts, letting record
+    /// builders move the strings instead of cloning them.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(
+        self,
+    ) -> (
+        String,
+        Option<Vec<(String, String)>>,
+        Vec<(String, FieldValue)>,
+        i64,
+    ) {
+        (self.name, self.tags, self.fields, self.timestamp)
+    }
+}
+
+/// Returns the first key that appears more than once, if any.
+fn first_duplicate_key<'a>(mut keys: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    let mut seen: HashSet<&String> = HashSet::new();
+    keys.find(|key| !seen.insert(key))
+}
+
+/// (De)serializes the optional tag set as a JSON object rather than an
+/// array of pairs. Keys deserialize in sorted order.
+mod tag_map {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        tags: &Option<Vec<(String, String)>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match tags {
+            Some(tags) => serializer.collect_map(tags.iter().map(|tag| (&tag.0, &tag.1))),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<(String, String)>>, D::Error> {
+        let tags = Option::<BTreeMap<String, String>>::deserialize(deserializer)?;
+        Ok(tags.map(|tags| tags.into_iter().collect()))
+    }
+}
+
+/// (De)serializes the field set as a JSON object rather than an array of
+/// pairs. Keys deserialize in sorted order.
+mod field_map {
+    use super::FieldValue;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        fields: &[(String, FieldValue)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(fields.iter().map(|field| (&field.0, &field.1)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(String, FieldValue)>, D::Error> {
+        let fields = BTreeMap::<String, FieldValue>::deserialize(deserializer)?;
+        Ok(fields.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_accepts_well_formed_metric() {
+        let metric = Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        );
+        assert!(metric.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_empty_name() {
+        let metric = Metric::new(
+            String::new(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        );
+        assert!(metric.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicate_field_keys() {
+        let metric = Metric::new(
+            "readings".to_string(),
+            None,
+            vec![
+                ("fuel".to_string(), FieldValue::I64(30)),
+                ("fuel".to_string(), FieldValue::I64(31)),
+            ],
+            1677605771000000000,
+        );
+        let error = metric.validate().unwrap_err();
+        assert!(error.to_string().contains("duplicate field key"));
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicate_tag_keys() {
+        let metric = Metric::new(
+            "readings".to_string(),
+            Some(vec![
+                ("fleet".to_string(), "Alberta".to_string()),
+                ("fleet".to_string(), "Zurich".to_string()),
+            ]),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        );
+        let error = metric.validate().unwrap_err();
+        assert!(error.to_string().contains("duplicate tag key"));
+    }
+
+    #[test]
+    fn test_metric_json_round_trip() {
+        let metric = Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+            vec![
+                ("active".to_string(), FieldValue::Boolean(true)),
+                ("fuel".to_string(), FieldValue::I64(30)),
+                ("load".to_string(), FieldValue::F64(0.5)),
+                ("state".to_string(), FieldValue::String("ok".to_string())),
+            ],
+            1677605771000000000,
+        );
+        let json = serde_json::to_string(&metric).expect("Failed to serialize metric");
+        let deserialized: Metric =
+            serde_json::from_str(&json).expect("Failed to deserialize metric");
+        assert_eq!(deserialized, metric);
+    }
+
+    #[test]
+    fn test_float_precision_formatting() {
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        env_vars.remove("float_format");
+        env_vars.remove("float_precision");
+        assert_eq!(FieldValue::F64(1.234567890123).to_string(), "1.234567890123");
+
+        env_vars.set("float_precision", "2");
+        assert_eq!(FieldValue::F64(1.234567890123).to_string(), "1.23");
+        // Exact decimals must not pick up rounding artifacts.
+        assert_eq!(FieldValue::F64(4.5).to_string(), "4.50");
+        assert_eq!(FieldValue::F64(100.0).to_string(), "100.00");
+
+        env_vars.set("float_precision", "0");
+        assert_eq!(FieldValue::F64(1.9).to_string(), "2");
+
+        // Out-of-range and unparsable values fall back to default formatting.
+        env_vars.set("float_precision", "16");
+        assert_eq!(FieldValue::F64(1.234567890123).to_string(), "1.234567890123");
+        env_vars.set("float_precision", "abc");
+        assert_eq!(FieldValue::F64(1.234567890123).to_string(), "1.234567890123");
+    }
+
+    #[test]
+    fn test_float_format_preserve_and_fixed() {
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        env_vars.remove("float_format");
+        env_vars.remove("float_precision");
+
+        // The default preserves the source value through shortest
+        // round-trip formatting: no binary expansion, no trailing zeros.
+        assert_eq!(FieldValue::F64(4.6).to_string(), "4.6");
+        assert_eq!(FieldValue::F64(0.1).to_string(), "0.1");
+        assert_eq!(FieldValue::F64(100.0).to_string(), "100");
+        assert_eq!(FieldValue::F64(-273.15).to_string(), "-273.15");
+
+        // An explicit preserve wins over a set float_precision.
+        env_vars.set("float_precision", "2");
+        env_vars.set("float_format", "preserve");
+        assert_eq!(FieldValue::F64(4.6).to_string(), "4.6");
+
+        // fixed pins the decimals from float_precision...
+        env_vars.set("float_format", "fixed");
+        assert_eq!(FieldValue::F64(4.6).to_string(), "4.60");
+
+        // ...and falls back to the default count without one.
+        env_vars.remove("float_precision");
+        assert_eq!(FieldValue::F64(4.6).to_string(), "4.600000");
+
+        // An unrecognized float_format keeps the historical behavior.
+        env_vars.set("float_format", "bogus");
+        env_vars.set("float_precision", "3");
+        assert_eq!(FieldValue::F64(4.6).to_string(), "4.600");
+        env_vars.remove("float_precision");
+        assert_eq!(FieldValue::F64(4.6).to_string(), "4.6");
+    }
+}
@@ -0,0 +1,250 @@
+use crate::metric::{FieldValue, Metric};
+use anyhow::{Context, Result};
+use prost::Message;
+
+#[cfg(test)]
+mod tests;
+
+// The OTLP metrics protobuf messages, hand-declared like the remote_write
+// ones so the build does not depend on protoc. Field numbers match
+// opentelemetry-proto v1 (metrics_service.proto, metrics.proto,
+// common.proto); unsupported portions (scope, exemplars, flags) are
+// omitted.
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExportMetricsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_metrics: Vec<ResourceMetrics>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ResourceMetrics {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_metrics: Vec<ScopeMetrics>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Resource {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ScopeMetrics {
+    #[prost(message, repeated, tag = "2")]
+    pub metrics: Vec<OtlpMetric>,
+}
+
+/// One OTLP metric; named `OtlpMetric` to avoid clashing with the
+/// connector's own `Metric`.
+#[derive(Clone, PartialEq, Message)]
+pub struct OtlpMetric {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(oneof = "MetricData", tags = "5, 7, 9, 10, 11")]
+    pub data: Option<MetricData>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum MetricData {
+    #[prost(message, tag = "5")]
+    Gauge(Gauge),
+    #[prost(message, tag = "7")]
+    Sum(Sum),
+    #[prost(message, tag = "9")]
+    Histogram(Histogram),
+    #[prost(message, tag = "10")]
+    ExponentialHistogram(ExponentialHistogram),
+    #[prost(message, tag = "11")]
+    Summary(Summary),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Gauge {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<NumberDataPoint>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Sum {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<NumberDataPoint>,
+}
+
+/// Histogram-family messages are decoded only far enough to count their
+/// data points for partial-success reporting.
+#[derive(Clone, PartialEq, Message)]
+pub struct Histogram {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<UnsupportedDataPoint>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExponentialHistogram {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<UnsupportedDataPoint>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Summary {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<UnsupportedDataPoint>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct UnsupportedDataPoint {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NumberDataPoint {
+    #[prost(fixed64, tag = "3")]
+    pub time_unix_nano: u64,
+    #[prost(message, repeated, tag = "7")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(oneof = "NumberValue", tags = "4, 6")]
+    pub value: Option<NumberValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum NumberValue {
+    #[prost(double, tag = "4")]
+    AsDouble(f64),
+    #[prost(sfixed64, tag = "6")]
+    AsInt(i64),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct KeyValue {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AnyValue {
+    #[prost(oneof = "AnyValueData", tags = "1, 2, 3, 4")]
+    pub value: Option<AnyValueData>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum AnyValueData {
+    #[prost(string, tag = "1")]
+    StringValue(String),
+    #[prost(bool, tag = "2")]
+    BoolValue(bool),
+    #[prost(int64, tag = "3")]
+    IntValue(i64),
+    #[prost(double, tag = "4")]
+    DoubleValue(f64),
+}
+
+/// Outcome of converting an OTLP export request: the supported data
+/// points as connector `Metric`s plus counts for partial-success
+/// reporting.
+#[derive(Debug, Default)]
+pub struct OtlpConversion {
+    pub metrics: Vec<Metric>,
+    /// Data points of unsupported metric types, dropped from the batch.
+    pub rejected_data_points: i64,
+    /// The unsupported metric type names encountered, for the error
+    /// message of a partial-success response.
+    pub rejected_types: Vec<String>,
+}
+
+/// Parses a protobuf `ExportMetricsServiceRequest` body into connector
+/// metrics. Gauge and sum data points convert to single-field metrics
+/// named after the OTLP metric, with resource and data point attributes
+/// flattened into tags; timestamps stay in nanoseconds.
+pub fn parse_otlp_metrics(body: &[u8]) -> Result<OtlpConversion> {
+    let request = ExportMetricsServiceRequest::decode(body)
+        .context("Failed to decode OTLP ExportMetricsServiceRequest")?;
+
+    let mut conversion = OtlpConversion::default();
+    for resource_metrics in &request.resource_metrics {
+        let resource_tags: Vec<(String, String)> = resource_metrics
+            .resource
+            .as_ref()
+            .map(|resource| attributes_to_tags(&resource.attributes))
+            .unwrap_or_default();
+        for scope_metrics in &resource_metrics.scope_metrics {
+            for otlp_metric in &scope_metrics.metrics {
+                convert_otlp_metric(otlp_metric, &resource_tags, &mut conversion)?;
+            }
+        }
+    }
+    Ok(conversion)
+}
+
+fn convert_otlp_metric(
+    otlp_metric: &OtlpMetric,
+    resource_tags: &[(String, String)],
+    conversion: &mut OtlpConversion,
+) -> Result<()> {
+    let data_points = match &otlp_metric.data {
+        Some(MetricData::Gauge(gauge)) => &gauge.data_points,
+        Some(MetricData::Sum(sum)) => &sum.data_points,
+        Some(unsupported) => {
+            let (type_name, data_points) = match unsupported {
+                MetricData::Histogram(histogram) => ("histogram", histogram.data_points.len()),
+                MetricData::ExponentialHistogram(histogram) => {
+                    ("exponential histogram", histogram.data_points.len())
+                }
+                MetricData::Summary(summary) => ("summary", summary.data_points.len()),
+                _ => unreachable!(),
+            };
+            tracing::warn!(
+                "Skipping unsupported {} metric {} ({} data points)",
+                type_name,
+                otlp_metric.name,
+                data_points
+            );
+            conversion.rejected_data_points += data_points as i64;
+            let type_name = type_name.to_string();
+            if !conversion.rejected_types.contains(&type_name) {
+                conversion.rejected_types.push(type_name);
+            }
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+
+    for data_point in data_points {
+        let field_value = match &data_point.value {
+            Some(NumberValue::AsDouble(value)) => FieldValue::F64(*value),
+            Some(NumberValue::AsInt(value)) => FieldValue::I64(*value),
+            None => {
+                conversion.rejected_data_points += 1;
+                continue;
+            }
+        };
+        let mut tags = resource_tags.to_vec();
+        tags.extend(attributes_to_tags(&data_point.attributes));
+        let metric = Metric::new(
+            otlp_metric.name.clone(),
+            Some(tags),
+            vec![(otlp_metric.name.clone(), field_value)],
+            data_point.time_unix_nano as i64,
+        );
+        metric.validate()?;
+        conversion.metrics.push(metric);
+    }
+    Ok(())
+}
+
+/// Flattens OTLP attributes into string tag pairs.
+fn attributes_to_tags(attributes: &[KeyValue]) -> Vec<(String, String)> {
+    attributes
+        .iter()
+        .filter_map(|attribute| {
+            let value = match attribute.value.as_ref()?.value.as_ref()? {
+                AnyValueData::StringValue(value) => value.to_string(),
+                AnyValueData::BoolValue(value) => value.to_string(),
+                AnyValueData::IntValue(value) => value.to_string(),
+                AnyValueData::DoubleValue(value) => value.to_string(),
+            };
+            Some((attribute.key.to_string(), value))
+        })
+        .collect()
+}
@@ -0,0 +1,102 @@
+use super::*;
+use prost::Message;
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(AnyValueData::StringValue(value.to_string())),
+        }),
+    }
+}
+
+fn fixture_request() -> ExportMetricsServiceRequest {
+    ExportMetricsServiceRequest {
+        resource_metrics: vec![ResourceMetrics {
+            resource: Some(Resource {
+                attributes: vec![string_attribute("service.name", "fleet-tracker")],
+            }),
+            scope_metrics: vec![ScopeMetrics {
+                metrics: vec![
+                    OtlpMetric {
+                        name: "cpu_usage".to_string(),
+                        data: Some(MetricData::Gauge(Gauge {
+                            data_points: vec![NumberDataPoint {
+                                time_unix_nano: 1677605771000000000,
+                                attributes: vec![string_attribute("host", "alpha")],
+                                value: Some(NumberValue::AsDouble(0.5)),
+                            }],
+                        })),
+                    },
+                    OtlpMetric {
+                        name: "request_count".to_string(),
+                        data: Some(MetricData::Sum(Sum {
+                            data_points: vec![NumberDataPoint {
+                                time_unix_nano: 1677605772000000000,
+                                attributes: vec![],
+                                value: Some(NumberValue::AsInt(42)),
+                            }],
+                        })),
+                    },
+                ],
+            }],
+        }],
+    }
+}
+
+#[test]
+fn test_parse_otlp_gauge_and_sum() {
+    let conversion = parse_otlp_metrics(&fixture_request().encode_to_vec())
+        .expect("Failed to parse OTLP fixture");
+    assert_eq!(conversion.rejected_data_points, 0);
+    assert_eq!(conversion.metrics.len(), 2);
+
+    let gauge = &conversion.metrics[0];
+    assert_eq!(gauge.name(), "cpu_usage");
+    assert_eq!(
+        gauge.tags(),
+        &Some(vec![
+            ("service.name".to_string(), "fleet-tracker".to_string()),
+            ("host".to_string(), "alpha".to_string()),
+        ])
+    );
+    assert_eq!(
+        gauge.fields(),
+        &vec![("cpu_usage".to_string(), FieldValue::F64(0.5))]
+    );
+    assert_eq!(gauge.timestamp(), 1677605771000000000);
+
+    let sum = &conversion.metrics[1];
+    assert_eq!(sum.name(), "request_count");
+    assert_eq!(
+        sum.fields(),
+        &vec![("request_count".to_string(), FieldValue::I64(42))]
+    );
+}
+
+#[test]
+fn test_parse_otlp_skips_unsupported_types() {
+    let mut request = fixture_request();
+    request.resource_metrics[0].scope_metrics[0]
+        .metrics
+        .push(OtlpMetric {
+            name: "latency".to_string(),
+            data: Some(MetricData::ExponentialHistogram(ExponentialHistogram {
+                data_points: vec![UnsupportedDataPoint {}, UnsupportedDataPoint {}],
+            })),
+        });
+
+    let conversion = parse_otlp_metrics(&request.encode_to_vec())
+        .expect("Unsupported types must be skipped, not fatal");
+    assert_eq!(conversion.metrics.len(), 2);
+    assert_eq!(conversion.rejected_data_points, 2);
+    assert_eq!(
+        conversion.rejected_types,
+        vec!["exponential histogram".to_string()]
+    );
+}
+
+#[test]
+fn test_parse_otlp_rejects_malformed_body() {
+    assert!(parse_otlp_metrics(b"not a protobuf payload").is_err());
+}
@@ -0,0 +1,94 @@
+use crate::metric::{FieldValue, Metric};
+use anyhow::{anyhow, Context, Result};
+use prost::Message;
+
+#[cfg(test)]
+mod tests;
+
+/// Reserved Prometheus label carrying the metric name.
+pub const METRIC_NAME_LABEL: &str = "__name__";
+
+// The remote_write protobuf messages, hand-declared so the build does not
+// depend on protoc. Field numbers match prometheus/prompb/remote.proto and
+// types.proto; fields the connector does not consume (exemplars, metadata,
+// histograms) are omitted, which protobuf decoding tolerates.
+
+#[derive(Clone, PartialEq, Message)]
+pub struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Sample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+/// Parses a snappy-compressed remote_write body into owned `Metric`s.
+/// Sample timestamps are milliseconds since the epoch, per the remote_write
+/// contract.
+pub fn parse_remote_write(body: &[u8]) -> Result<Vec<Metric>> {
+    let decompressed = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .context("Failed to decompress snappy remote_write body")?;
+    let write_request = WriteRequest::decode(decompressed.as_slice())
+        .context("Failed to decode remote_write WriteRequest")?;
+
+    let mut metrics: Vec<Metric> = Vec::new();
+    for time_series in &write_request.timeseries {
+        metrics.extend(time_series_to_metrics(time_series)?);
+    }
+    Ok(metrics)
+}
+
+/// Converts one time series into `Metric`s, one per sample. The metric
+/// name label becomes the measurement, the remaining labels become tags,
+/// and each sample becomes a single `value` field.
+pub fn time_series_to_metrics(time_series: &TimeSeries) -> Result<Vec<Metric>> {
+    let name = time_series
+        .labels
+        .iter()
+        .find(|label| label.name == METRIC_NAME_LABEL)
+        .map(|label| label.value.to_string())
+        .ok_or_else(|| {
+            anyhow!("Time series is missing the {} label", METRIC_NAME_LABEL)
+        })?;
+    let tags: Vec<(String, String)> = time_series
+        .labels
+        .iter()
+        .filter(|label| label.name != METRIC_NAME_LABEL)
+        .map(|label| (label.name.to_string(), label.value.to_string()))
+        .collect();
+
+    let mut metrics: Vec<Metric> = Vec::new();
+    for sample in &time_series.samples {
+        let metric = Metric::new(
+            name.clone(),
+            Some(tags.clone()),
+            vec![("value".to_string(), FieldValue::F64(sample.value))],
+            sample.timestamp,
+        );
+        metric.validate()?;
+        metrics.push(metric);
+    }
+    Ok(metrics)
+}
@@ -0,0 +1,103 @@
+use super::*;
+
+fn compress(write_request: &WriteRequest) -> Vec<u8> {
+    snap::raw::Encoder::new()
+        .compress_vec(&write_request.encode_to_vec())
+        .expect("Failed to snappy-compress WriteRequest")
+}
+
+fn label(name: &str, value: &str) -> Label {
+    Label {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_remote_write_basic() {
+    let write_request = WriteRequest {
+        timeseries: vec![TimeSeries {
+            labels: vec![
+                label(METRIC_NAME_LABEL, "cpu_usage"),
+                label("host", "alpha"),
+                label("region", "us-east-1"),
+            ],
+            samples: vec![
+                Sample {
+                    value: 0.5,
+                    timestamp: 1677605771000,
+                },
+                Sample {
+                    value: 0.75,
+                    timestamp: 1677605772000,
+                },
+            ],
+        }],
+    };
+    let metrics =
+        parse_remote_write(&compress(&write_request)).expect("Failed to parse WriteRequest");
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].name(), "cpu_usage");
+    assert_eq!(
+        metrics[0].tags(),
+        &Some(vec![
+            ("host".to_string(), "alpha".to_string()),
+            ("region".to_string(), "us-east-1".to_string()),
+        ])
+    );
+    assert_eq!(
+        metrics[0].fields(),
+        &vec![("value".to_string(), FieldValue::F64(0.5))]
+    );
+    assert_eq!(metrics[0].timestamp(), 1677605771000);
+    assert_eq!(metrics[1].timestamp(), 1677605772000);
+}
+
+#[test]
+fn test_parse_remote_write_multiple_series() {
+    let write_request = WriteRequest {
+        timeseries: vec![
+            TimeSeries {
+                labels: vec![label(METRIC_NAME_LABEL, "cpu_usage")],
+                samples: vec![Sample {
+                    value: 0.5,
+                    timestamp: 1677605771000,
+                }],
+            },
+            TimeSeries {
+                labels: vec![label(METRIC_NAME_LABEL, "mem_usage")],
+                samples: vec![Sample {
+                    value: 0.25,
+                    timestamp: 1677605771000,
+                }],
+            },
+        ],
+    };
+    let metrics =
+        parse_remote_write(&compress(&write_request)).expect("Failed to parse WriteRequest");
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0].name(), "cpu_usage");
+    assert_eq!(metrics[1].name(), "mem_usage");
+}
+
+#[test]
+fn test_parse_remote_write_missing_name_label() {
+    let write_request = WriteRequest {
+        timeseries: vec![TimeSeries {
+            labels: vec![label("host", "alpha")],
+            samples: vec![Sample {
+                value: 0.5,
+                timestamp: 1677605771000,
+            }],
+        }],
+    };
+    let error = parse_remote_write(&compress(&write_request))
+        .expect_err("Series without __name__ must be rejected");
+    assert!(error.to_string().contains(METRIC_NAME_LABEL));
+}
+
+#[test]
+fn test_parse_remote_write_rejects_uncompressed_body() {
+    let write_request = WriteRequest { timeseries: vec![] };
+    assert!(parse_remote_write(&write_request.encode_to_vec()).is_err());
+}
@@ -0,0 +1,1282 @@
+use crate::error::ConnectorError;
+use crate::metric::{FieldValue, Metric};
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_timestreamwrite::types::{
+    Dimension, MeasureValue, MeasureValueType, Record, TimeUnit,
+};
+use base64::Engine;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+
+/// Timestream's limit on dimensions per record, applied after static
+/// dimensions are merged in.
+const MAX_DIMENSIONS_PER_RECORD: usize = 128;
+
+/// Timestream's limit on distinct dimension names per table. A batch
+/// whose records together exceed it for one table is handled according
+/// to `dimension_overflow_behavior`.
+const MAX_DIMENSIONS_PER_TABLE: usize = 128;
+
+/// Timestream's limit on unique measure names per table. Tracked only
+/// under `batch_split_on_measure_count`, which splits overflowing
+/// batches the way `dimension_overflow_behavior = split` does for
+/// dimensions; without it, overflowing writes are left for Timestream
+/// to reject server-side.
+const MAX_UNIQUE_MEASURES_PER_TABLE: usize = 1024;
+
+/// Timestream's maximum VARCHAR measure value length, in bytes.
+pub const MAX_VARCHAR_BYTES: usize = 2048;
+
+/// Marker prefix of string measures compressed under
+/// `compress_string_fields`; consumers strip it, base64-decode the rest,
+/// and gunzip to recover the original value.
+pub const COMPRESSED_STRING_PREFIX: &str = "gzip:";
+
+/// Strings at or below this length are stored uncompressed even when
+/// `compress_string_fields` is enabled; gzip plus base64 only pays off
+/// for larger values.
+pub const COMPRESS_STRING_MIN_BYTES: usize = 256;
+
+/// Batch size at or above which record building runs on the rayon pool.
+/// Below it the per-task overhead outweighs the parallelism.
+pub const PARALLEL_BUILD_THRESHOLD: usize = 5000;
+
+#[cfg(test)]
+mod tests;
+
+/// Environment variables that must be defined for the connector to run.
+/// The region is resolved separately since it may come from the standard
+/// AWS variables; the retention periods are only required when table
+/// creation is enabled (see `TABLE_CREATION_ENV_VARIABLES`).
+const REQUIRED_ENV_VARIABLES: [&str; 5] = [
+    "database_name",
+    "enable_database_creation",
+    "enable_table_creation",
+    "enable_mag_store_writes",
+    "measure_name_for_multi_measure_records",
+];
+
+/// Environment variables that must be defined when table creation is
+/// enabled. A deployment that only writes to existing tables never uses
+/// the retention periods, so requiring them unconditionally would fail
+/// invocations that could have succeeded.
+const TABLE_CREATION_ENV_VARIABLES: [&str; 2] =
+    ["mag_store_retention_period", "mem_store_retention_period"];
+
+/// Validates that all required environment variables are defined.
+pub fn validate_env_variables() -> Result<()> {
+    for variable_name in REQUIRED_ENV_VARIABLES {
+        if env::var(variable_name).is_err() {
+            return Err(anyhow!(
+                "{} environment variable is not defined",
+                variable_name
+            ));
+        }
+    }
+    crate::timestream_utils::resolve_region()?;
+    let creation_enabled = crate::timestream_utils::table_creation_enabled()?;
+    if let Some(problem) =
+        table_creation_variable_problems(&|name| env::var(name).is_ok(), creation_enabled)
+            .into_iter()
+            .next()
+    {
+        return Err(anyhow!(problem));
+    }
+    crate::timestream_utils::get_table_config()?;
+    if let Ok(kms_key_id) = env::var("kms_key_id") {
+        validate_kms_key_id(&kms_key_id)?;
+    }
+    field_type_overrides()?;
+    duplicate_mode()?;
+    tag_key_renames()?;
+    TagFilter::from_env()?;
+    crate::timestream_utils::circuit_breaker_threshold()?;
+    Ok(())
+}
+
+/// The missing-variable problems for the given definedness lookup. Pure
+/// so tests can probe arbitrary configurations without mutating the
+/// process environment.
+fn missing_variable_problems(is_defined: &dyn Fn(&str) -> bool) -> Vec<String> {
+    REQUIRED_ENV_VARIABLES
+        .iter()
+        .filter(|variable_name| !is_defined(variable_name))
+        .map(|variable_name| format!("{} environment variable is not defined", variable_name))
+        .collect()
+}
+
+/// The missing-variable problems for the settings table creation needs,
+/// empty when creation is disabled. Pure for the same reason as
+/// `missing_variable_problems`.
+fn table_creation_variable_problems(
+    is_defined: &dyn Fn(&str) -> bool,
+    creation_enabled: bool,
+) -> Vec<String> {
+    if !creation_enabled {
+        return Vec::new();
+    }
+    TABLE_CREATION_ENV_VARIABLES
+        .iter()
+        .filter(|variable_name| !is_defined(variable_name))
+        .map(|variable_name| {
+            format!(
+                "{} environment variable is not defined; required when \
+                enable_table_creation is true",
+                variable_name
+            )
+        })
+        .collect()
+}
+
+/// Combines collected configuration problems into a single error listing
+/// every one, or `Ok` when there are none.
+fn combined_problems_error(problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Invalid connector configuration:\n  - {}",
+            problems.join("\n  - ")
+        ))
+    }
+}
+
+/// Validates the environment like [`validate_env_variables`], but
+/// collects every missing or invalid setting and reports them all in a
+/// single error, so operators can fix the whole configuration in one
+/// pass instead of redeploying once per variable. Used at startup; the
+/// fast-fail version remains for per-request checks.
+pub fn validate_all_env_variables() -> Result<()> {
+    let mut problems = missing_variable_problems(&|name| env::var(name).is_ok());
+    problems.extend(table_creation_variable_problems(
+        &|name| env::var(name).is_ok(),
+        crate::timestream_utils::table_creation_enabled().unwrap_or(false),
+    ));
+    let mut record = |result: Result<()>| {
+        if let Err(error) = result {
+            problems.push(format!("{:#}", error));
+        }
+    };
+    record(crate::timestream_utils::resolve_region().map(|_| ()));
+    record(crate::timestream_utils::get_table_config().map(|_| ()));
+    if let Ok(kms_key_id) = env::var("kms_key_id") {
+        record(validate_kms_key_id(&kms_key_id));
+    }
+    record(field_type_overrides().map(|_| ()));
+    record(duplicate_mode().map(|_| ()));
+    record(tag_key_renames().map(|_| ()));
+    record(TagFilter::from_env().map(|_| ()));
+    record(crate::timestream_utils::circuit_breaker_threshold().map(|_| ()));
+    combined_problems_error(problems)
+}
+
+/// Parses the optional `field_type_overrides` environment variable: a
+/// JSON object mapping field keys to Timestream measure value types, e.g.
+/// `{"last_updated": "TIMESTAMP"}`. Returns an empty map when unset.
+pub fn field_type_overrides() -> Result<HashMap<String, MeasureValueType>> {
+    let Ok(overrides) = env::var("field_type_overrides") else {
+        return Ok(HashMap::new());
+    };
+    let overrides: HashMap<String, String> = serde_json::from_str(&overrides)
+        .context("field_type_overrides must be a JSON object of field key to type")?;
+    overrides
+        .into_iter()
+        .map(|(field_key, type_name)| {
+            let measure_type = MeasureValueType::from(type_name.to_uppercase().as_str());
+            if !MeasureValueType::values().contains(&measure_type.as_str()) {
+                return Err(anyhow!(
+                    "field_type_overrides has an unknown measure value type {} for \
+                    field {}",
+                    type_name,
+                    field_key
+                ));
+            }
+            Ok((field_key, measure_type))
+        })
+        .collect()
+}
+
+/// Parses the optional `static_dimensions` environment variable: a JSON
+/// object of dimension name to value (e.g. `{"env": "prod"}`) injected
+/// into every record. Returns an empty list when unset.
+pub fn static_dimensions() -> Result<Vec<(String, String)>> {
+    let Ok(dimensions) = env::var("static_dimensions") else {
+        return Ok(Vec::new());
+    };
+    let dimensions: BTreeMap<String, String> = serde_json::from_str(&dimensions)
+        .context("static_dimensions must be a JSON object of dimension name to value")?;
+    Ok(dimensions.into_iter().collect())
+}
+
+/// Parses the optional `tag_key_rename` environment variable: a JSON
+/// object mapping incoming tag keys to the dimension names they should be
+/// stored under, e.g. `{"host": "hostname"}`. Returns an empty map when
+/// unset.
+pub fn tag_key_renames() -> Result<HashMap<String, String>> {
+    let Ok(renames) = env::var("tag_key_rename") else {
+        return Ok(HashMap::new());
+    };
+    serde_json::from_str(&renames)
+        .context("tag_key_rename must be a JSON object of tag key to dimension name")
+}
+
+/// Which incoming tag keys become dimensions, resolved from the
+/// mutually exclusive `tag_allowlist` and `tag_denylist` environment
+/// variables. High-cardinality tags like `request_id` are useless for
+/// Timestream queries but count toward the per-table dimension limits;
+/// a denylist drops them at record building.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagFilter {
+    /// No filter configured; every tag becomes a dimension.
+    None,
+    /// Only the listed tag keys are kept.
+    Allow(HashSet<String>),
+    /// The listed tag keys are dropped.
+    Deny(HashSet<String>),
+}
+
+impl TagFilter {
+    /// Parses the two list variables, comma-separated tag key names with
+    /// surrounding whitespace ignored. Setting both is rejected; their
+    /// combination has no single sensible meaning.
+    pub fn from_env() -> Result<TagFilter> {
+        let allowlist = env::var("tag_allowlist").ok();
+        let denylist = env::var("tag_denylist").ok();
+        match (allowlist, denylist) {
+            (Some(_), Some(_)) => Err(anyhow!(
+                "tag_allowlist and tag_denylist are mutually exclusive; set at most one"
+            )),
+            (Some(list), None) => Ok(TagFilter::Allow(parse_tag_key_list(&list))),
+            (None, Some(list)) => Ok(TagFilter::Deny(parse_tag_key_list(&list))),
+            (None, None) => Ok(TagFilter::None),
+        }
+    }
+
+    /// Returns whether a tag with this key becomes a dimension.
+    fn retains(&self, key: &str) -> bool {
+        match self {
+            TagFilter::None => true,
+            TagFilter::Allow(keys) => keys.contains(key),
+            TagFilter::Deny(keys) => !keys.contains(key),
+        }
+    }
+}
+
+fn parse_tag_key_list(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The process-wide tag filter. The lists are static deployment
+/// configuration and the lookup sits on the per-record hot path, so the
+/// environment is parsed once per process; unit tests parse fresh
+/// instead, so each test can vary the variables.
+pub fn tag_filter() -> Result<TagFilter> {
+    #[cfg(not(test))]
+    {
+        use std::sync::OnceLock;
+        static FILTER: OnceLock<std::result::Result<TagFilter, String>> = OnceLock::new();
+        FILTER
+            .get_or_init(|| TagFilter::from_env().map_err(|error| error.to_string()))
+            .clone()
+            .map_err(|message| anyhow!(message))
+    }
+    #[cfg(test)]
+    {
+        TagFilter::from_env()
+    }
+}
+
+/// Parses the optional `promote_fields_to_dimensions` environment
+/// variable: a comma-separated list of field keys whose values should be
+/// stored as dimensions rather than measure values.
+pub fn promoted_fields() -> Vec<String> {
+    match env::var("promote_fields_to_dimensions") {
+        Ok(fields) => fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether dimension name collisions error out instead of being resolved
+/// by the precedence order (incoming tags > renamed tags > promoted
+/// fields > static dimensions).
+fn strict_dimension_collisions() -> bool {
+    env_var_to_bool("strict_dimension_collisions")
+}
+
+/// Drops the tags the configured filter excludes. Filtering applies to
+/// the incoming tag keys, before renames, promotions, or static
+/// dimensions, so an allowlist cannot accidentally strip configured
+/// dimensions.
+fn filter_tags(
+    tags: Option<Vec<(String, String)>>,
+    filter: &TagFilter,
+) -> Option<Vec<(String, String)>> {
+    match filter {
+        TagFilter::None => tags,
+        _ => tags.map(|tags| {
+            tags.into_iter()
+                .filter(|tag| filter.retains(&tag.0))
+                .collect()
+        }),
+    }
+}
+
+/// Applies the configured tag key renames. A rename whose target name is
+/// already taken by another tag is skipped, since incoming tags take
+/// precedence over renamed ones; in strict mode it errors instead.
+fn apply_tag_renames(
+    tags: Option<Vec<(String, String)>>,
+    renames: &HashMap<String, String>,
+) -> Result<Option<Vec<(String, String)>>> {
+    if renames.is_empty() {
+        return Ok(tags);
+    }
+    let strict = strict_dimension_collisions();
+    let tags = tags.unwrap_or_default();
+    // Names that stay as-is are reserved up front so a rename cannot
+    // collide with an unrenamed tag appearing later in the set.
+    let mut used: Vec<&str> = tags
+        .iter()
+        .map(|tag| tag.0.as_str())
+        .filter(|key| !renames.contains_key(*key))
+        .collect();
+    let mut renamed = Vec::with_capacity(tags.len());
+    for (key, value) in &tags {
+        match renames.get(key) {
+            Some(new_name) if used.contains(&new_name.as_str()) => {
+                if strict {
+                    return Err(anyhow!(
+                        "Renaming tag {} to {} collides with another dimension of that name",
+                        key,
+                        new_name
+                    ));
+                }
+                tracing::warn!(
+                    "Skipping rename of tag {} to {}: the name is already taken",
+                    key,
+                    new_name
+                );
+                renamed.push((key.clone(), value.clone()));
+            }
+            Some(new_name) => {
+                used.push(new_name.as_str());
+                renamed.push((new_name.clone(), value.clone()));
+            }
+            None => renamed.push((key.clone(), value.clone())),
+        }
+    }
+    Ok(Some(renamed))
+}
+
+/// Promotes the configured fields from measure values to dimensions. A
+/// promotion whose name is already taken by a tag (renamed or not) is
+/// skipped and the field stays a measure value; in strict mode it errors
+/// instead.
+fn promote_fields_to_dimensions(
+    tags: Option<Vec<(String, String)>>,
+    fields: &mut Vec<(String, FieldValue)>,
+    promoted: &[String],
+) -> Result<Option<Vec<(String, String)>>> {
+    if promoted.is_empty() {
+        return Ok(tags);
+    }
+    let strict = strict_dimension_collisions();
+    let mut dimensions = tags.unwrap_or_default();
+    for field_key in promoted {
+        let Some(position) = fields.iter().position(|field| &field.0 == field_key) else {
+            continue;
+        };
+        if dimensions.iter().any(|dimension| &dimension.0 == field_key) {
+            if strict {
+                return Err(anyhow!(
+                    "Promoting field {} to a dimension collides with a tag of that name",
+                    field_key
+                ));
+            }
+            tracing::warn!(
+                "Skipping promotion of field {}: a tag of that name exists",
+                field_key
+            );
+            continue;
+        }
+        let (field_key, field_value) = fields.remove(position);
+        dimensions.push((field_key, field_value.to_string()));
+    }
+    Ok(Some(dimensions))
+}
+
+/// Merges the configured static dimensions into a metric's tags. On a
+/// key collision the incoming tag wins unless `static_dimensions_override`
+/// is set, in which case the configured value wins; in strict mode any
+/// collision errors instead.
+fn merge_static_dimensions(
+    tags: Option<Vec<(String, String)>>,
+    static_dimensions: &[(String, String)],
+) -> Result<Vec<(String, String)>> {
+    let strict = strict_dimension_collisions();
+    let config_wins = env_var_to_bool("static_dimensions_override");
+    let mut dimensions: Vec<(String, String)> = tags.unwrap_or_default();
+    for (name, value) in static_dimensions {
+        match dimensions
+            .iter_mut()
+            .find(|dimension| &dimension.0 == name)
+        {
+            Some(_) if strict => {
+                return Err(anyhow!(
+                    "Static dimension {} collides with an existing dimension",
+                    name
+                ))
+            }
+            Some(dimension) if config_wins => dimension.1 = value.clone(),
+            Some(_) => {}
+            None => dimensions.push((name.clone(), value.clone())),
+        }
+    }
+    if dimensions.len() > MAX_DIMENSIONS_PER_RECORD {
+        return Err(anyhow!(
+            "Record has {} dimensions after merging static_dimensions, exceeding the \
+            Timestream limit of {}",
+            dimensions.len(),
+            MAX_DIMENSIONS_PER_RECORD
+        ));
+    }
+    Ok(dimensions)
+}
+
+/// Checks that every built record carries the enforced custom partition
+/// key dimension, so a misconfigured payload is flagged with a clear
+/// message before the write instead of failing record-by-record with
+/// Timestream's RejectedRecords error. A no-op unless a dimension-type
+/// partition key is configured and enforced.
+pub fn validate_partition_key_present(
+    records: &HashMap<String, Vec<Record>>,
+    table_config: &crate::timestream_utils::TableConfig,
+) -> Result<(), ConnectorError> {
+    if table_config.custom_partition_key_type.as_deref() != Some("dimension")
+        || !table_config.enforce_custom_partition_key
+    {
+        return Ok(());
+    }
+    // A missing dimension name is reported by the table creation path.
+    let Some(dimension_name) = &table_config.custom_partition_key_dimension else {
+        return Ok(());
+    };
+    for (table_name, table_records) in records {
+        if table_records.iter().any(|record| {
+            !record
+                .dimensions()
+                .iter()
+                .any(|dimension| dimension.name() == dimension_name)
+        }) {
+            return Err(ConnectorError::Validation(format!(
+                "Records for table {} are missing the enforced partition key \
+                dimension {}",
+                table_name, dimension_name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the measure value type for a field, honoring a configured
+/// override after checking it is compatible with the parsed value.
+fn resolve_measure_type(
+    field_key: &str,
+    field_value: &FieldValue,
+    type_overrides: &HashMap<String, MeasureValueType>,
+) -> Result<MeasureValueType> {
+    let Some(override_type) = type_overrides.get(field_key) else {
+        return Ok(get_timestream_measure_type(field_value));
+    };
+    let compatible = match field_value {
+        // Integer values are valid epoch timestamps and can widen.
+        FieldValue::I64(_) | FieldValue::U64(_) => matches!(
+            override_type,
+            MeasureValueType::Bigint
+                | MeasureValueType::Double
+                | MeasureValueType::Timestamp
+                | MeasureValueType::Varchar
+        ),
+        FieldValue::F64(_) => matches!(
+            override_type,
+            MeasureValueType::Double | MeasureValueType::Varchar
+        ),
+        FieldValue::Boolean(_) => matches!(
+            override_type,
+            MeasureValueType::Boolean | MeasureValueType::Varchar
+        ),
+        FieldValue::String(_) => matches!(override_type, MeasureValueType::Varchar),
+        FieldValue::Json(_) => matches!(override_type, MeasureValueType::Varchar),
+    };
+    if !compatible {
+        return Err(anyhow!(
+            "field_type_overrides maps field {} to {}, which is incompatible with \
+            its parsed value type {}",
+            field_key,
+            override_type.as_str(),
+            get_timestream_measure_type(field_value).as_str()
+        ));
+    }
+    Ok(override_type.clone())
+}
+
+/// Validates the format of a KMS key identifier: a key or alias ARN, a
+/// key ID, or an alias name. Rejects empty values and embedded whitespace.
+pub fn validate_kms_key_id(kms_key_id: &str) -> Result<()> {
+    if kms_key_id.is_empty() {
+        return Err(anyhow!("kms_key_id must not be empty"));
+    }
+    if kms_key_id.chars().any(char::is_whitespace) {
+        return Err(anyhow!("kms_key_id must not contain whitespace"));
+    }
+    if kms_key_id.starts_with("arn:")
+        && !kms_key_id.contains(":key/")
+        && !kms_key_id.contains(":alias/")
+    {
+        return Err(anyhow!(
+            "kms_key_id ARN must reference a KMS key or alias: {}",
+            kms_key_id
+        ));
+    }
+    Ok(())
+}
+
+/// Interprets the value of an environment variable as a boolean.
+/// `"true"`, `"t"`, `"1"`, `"yes"`, `"on"`, and `"enabled"`
+/// (case-insensitive) are truthy; `"false"`, `"f"`, `"0"`, `"no"`,
+/// `"off"`, and `"disabled"` are falsy. Any other value logs a warning
+/// and is treated as false, as is an unset variable.
+pub fn env_var_to_bool(variable_name: &str) -> bool {
+    match env::var(variable_name) {
+        Ok(value) => str_to_bool(&value, variable_name),
+        Err(_) => false,
+    }
+}
+
+/// Interprets a configuration string as a boolean with the same value set
+/// as [`env_var_to_bool`]; `setting_name` names the setting in the
+/// warning logged for unrecognized values.
+pub fn str_to_bool(value: &str, setting_name: &str) -> bool {
+    match value.to_lowercase().as_str() {
+        "true" | "t" | "1" | "yes" | "on" | "enabled" => true,
+        "false" | "f" | "0" | "no" | "off" | "disabled" => false,
+        _ => {
+            tracing::warn!(
+                "Unrecognized boolean value {:?} for {}; defaulting to false",
+                value,
+                setting_name
+            );
+            false
+        }
+    }
+}
+
+/// Builds Timestream records from parsed metrics, grouped by target table
+/// name (the measurement name). Takes the metrics by value so their
+/// strings move straight into the record builders; callers that need to
+/// retain their metrics can use `build_records_borrowed`.
+#[tracing::instrument(level = "trace", skip_all, fields(metrics = metrics.len()))]
+pub fn build_records(
+    metrics: Vec<Metric>,
+    precision: &TimeUnit,
+    measure_name: &str,
+) -> Result<HashMap<String, Vec<Record>>, ConnectorError> {
+    build_multi_measure_records(metrics, precision, measure_name)
+}
+
+/// `build_records` for callers that keep their metrics, at the cost of
+/// cloning them.
+pub fn build_records_borrowed(
+    metrics: &[Metric],
+    precision: &TimeUnit,
+    measure_name: &str,
+) -> Result<HashMap<String, Vec<Record>>, ConnectorError> {
+    build_records(metrics.to_vec(), precision, measure_name)
+}
+
+/// Builds multi-measure records, one per metric, grouped by table name.
+/// Points sharing a series (dimensions and timestamp) within the batch
+/// are resolved according to the `on_duplicate` environment variable.
+pub fn build_multi_measure_records(
+    metrics: Vec<Metric>,
+    precision: &TimeUnit,
+    measure_name: &str,
+) -> Result<HashMap<String, Vec<Record>>, ConnectorError> {
+    build_multi_measure_records_with_threshold(metrics, precision, measure_name, PARALLEL_BUILD_THRESHOLD)
+}
+
+/// `build_multi_measure_records` with an explicit parallelism threshold,
+/// exposed so tests and benchmarks can force either path.
+pub fn build_multi_measure_records_with_threshold(
+    metrics: Vec<Metric>,
+    precision: &TimeUnit,
+    measure_name: &str,
+    parallel_threshold: usize,
+) -> Result<HashMap<String, Vec<Record>>, ConnectorError> {
+    let on_duplicate = duplicate_mode().map_err(ConnectorError::configuration)?;
+    // Converting a metric to a record is the allocation-heavy step; run it
+    // on the rayon pool for large batches. The indexed collect preserves
+    // input order, so the sequential grouping below keeps within-table
+    // record order stable either way.
+    let converted: Result<Vec<(String, Record)>, ConnectorError> = if metrics.len()
+        >= parallel_threshold
+    {
+        metrics
+            .into_par_iter()
+            .map(|metric| convert_metric(metric, precision, measure_name))
+            .collect()
+    } else {
+        metrics
+            .into_iter()
+            .map(|metric| convert_metric(metric, precision, measure_name))
+            .collect()
+    };
+    let converted = converted?;
+    // Quick pre-pass over the table names so the map and each table's Vec
+    // are sized up front instead of rehashing and regrowing while records
+    // are inserted. Each unique table name is cloned exactly once here;
+    // the grouping below moves the per-record names into `entry` without
+    // allocating.
+    let mut table_sizes: HashMap<String, usize> = HashMap::new();
+    for (name, _) in &converted {
+        if let Some(count) = table_sizes.get_mut(name.as_str()) {
+            *count += 1;
+        } else {
+            table_sizes.insert(name.clone(), 1);
+        }
+    }
+    let overflow_behavior =
+        dimension_overflow_behavior().map_err(ConnectorError::configuration)?;
+    let split_suffix_format =
+        overflow_split_suffix_format().map_err(ConnectorError::configuration)?;
+    let split_on_measure_count = env_var_to_bool("batch_split_on_measure_count");
+    let mut multi_measure_records: HashMap<String, Vec<Record>> =
+        HashMap::with_capacity(table_sizes.len());
+    // Distinct dimension names accumulated per output table, so a batch
+    // cannot push one table past the per-table dimension limit; measure
+    // names likewise when measure-count splitting is enabled.
+    let mut table_dimension_names: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut table_measure_names: HashMap<String, HashSet<String>> = HashMap::new();
+    for (name, record) in converted {
+        let capacity = table_sizes.get(name.as_str()).copied().unwrap_or(0);
+        let Some(name) = resolve_dimension_overflow(
+            name,
+            &record,
+            &mut table_dimension_names,
+            overflow_behavior,
+            &split_suffix_format,
+        )?
+        else {
+            continue;
+        };
+        let name = if split_on_measure_count {
+            resolve_measure_overflow(name, &record, &mut table_measure_names, &split_suffix_format)
+        } else {
+            name
+        };
+        multi_measure_records
+            .entry(name)
+            .or_insert_with(|| Vec::with_capacity(capacity))
+            .push(record);
+    }
+    let mut duplicates = 0;
+    for (table_name, table_records) in multi_measure_records.iter_mut() {
+        duplicates += deduplicate_records(table_name, table_records, on_duplicate)?;
+    }
+    if duplicates > 0 {
+        tracing::warn!(
+            "Resolved {} duplicate points within the batch ({:?} mode)",
+            duplicates,
+            on_duplicate
+        );
+    }
+    Ok(multi_measure_records)
+}
+
+/// Returns whether a built record carries at least one measure value.
+/// The skip policies (`empty_string_behavior = skip`,
+/// `u64_overflow_behavior = skip`) can empty a metric whose only fields
+/// they skipped.
+fn record_has_measures(record: &Record) -> bool {
+    !record.measure_values().is_empty() || record.measure_value().is_some()
+}
+
+/// Removes records left without any measure values by the skip
+/// policies, returning how many were dropped; tables emptied with them
+/// are removed too. Timestream rejects measure-less records, and one
+/// rejected record fails its whole WriteRecords batch, so keeping such a
+/// record would turn one skipped field into a full-batch ingestion
+/// failure.
+pub fn drop_measureless_records(records: &mut HashMap<String, Vec<Record>>) -> usize {
+    let mut dropped = 0;
+    records.retain(|table_name, table_records| {
+        let before = table_records.len();
+        table_records.retain(record_has_measures);
+        let removed = before - table_records.len();
+        if removed > 0 {
+            tracing::warn!(
+                "Dropping {} record(s) for table {} left without measure values after \
+                skipped fields",
+                removed,
+                table_name
+            );
+            dropped += removed;
+        }
+        !table_records.is_empty()
+    });
+    dropped
+}
+
+/// How points that share a table, dimensions, and timestamp within one
+/// batch are resolved. `KeepLast` mirrors what Timestream itself does
+/// with colliding writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateMode {
+    KeepLast,
+    KeepFirst,
+    Error,
+}
+
+/// Parses the optional `on_duplicate` environment variable; unset
+/// defaults to `keep_last`.
+pub fn duplicate_mode() -> Result<DuplicateMode> {
+    match env::var("on_duplicate").ok().as_deref() {
+        None | Some("keep_last") => Ok(DuplicateMode::KeepLast),
+        Some("keep_first") => Ok(DuplicateMode::KeepFirst),
+        Some("error") => Ok(DuplicateMode::Error),
+        Some(other) => Err(anyhow!(
+            "Invalid on_duplicate value {}; expected keep_last, keep_first, or error",
+            other
+        )),
+    }
+}
+
+/// How a batch that accumulates more than `MAX_DIMENSIONS_PER_TABLE`
+/// distinct dimension names for one table is handled: reject the batch,
+/// split the overflow into suffixed tables, or drop the overflowing
+/// records with a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DimensionOverflowBehavior {
+    Error,
+    Split,
+    Warn,
+}
+
+/// Parses the optional `dimension_overflow_behavior` environment
+/// variable; unset defaults to `error`.
+pub fn dimension_overflow_behavior() -> Result<DimensionOverflowBehavior> {
+    match env::var("dimension_overflow_behavior").ok().as_deref() {
+        None | Some("error") => Ok(DimensionOverflowBehavior::Error),
+        Some("split") => Ok(DimensionOverflowBehavior::Split),
+        Some("warn") => Ok(DimensionOverflowBehavior::Warn),
+        Some(other) => Err(anyhow!(
+            "Invalid dimension_overflow_behavior value {}; expected error, split, \
+            or warn",
+            other
+        )),
+    }
+}
+
+/// Routes one record to a table that can accept its dimension names
+/// without crossing the per-table limit, updating the accumulated name
+/// sets. Returns `None` when the `warn` behavior drops the record. Under
+/// `split`, overflowing records go to suffixed tables (`readings_2`,
+/// `readings_3`, ...), each tracked against the limit in turn; a fresh
+/// table always fits because a single record is already capped at
+/// `MAX_DIMENSIONS_PER_RECORD`.
+fn resolve_dimension_overflow(
+    table_name: String,
+    record: &Record,
+    table_dimension_names: &mut HashMap<String, HashSet<String>>,
+    behavior: DimensionOverflowBehavior,
+    suffix_format: &str,
+) -> Result<Option<String>, ConnectorError> {
+    let record_names: Vec<&str> = record
+        .dimensions()
+        .iter()
+        .map(|dimension| dimension.name())
+        .collect();
+
+    let mut target = table_name.clone();
+    let mut suffix = 2;
+    loop {
+        let accumulated = table_dimension_names.entry(target.clone()).or_default();
+        let new_names = record_names
+            .iter()
+            .filter(|name| !accumulated.contains(**name))
+            .count();
+        if accumulated.len() + new_names <= MAX_DIMENSIONS_PER_TABLE {
+            for name in &record_names {
+                if !accumulated.contains(*name) {
+                    accumulated.insert((*name).to_string());
+                }
+            }
+            return Ok(Some(target));
+        }
+        match behavior {
+            DimensionOverflowBehavior::Error => {
+                return Err(ConnectorError::Validation(format!(
+                    "Batch for table {} accumulates more than {} distinct dimension \
+                    names, Timestream's per-table limit; set \
+                    dimension_overflow_behavior to split or warn",
+                    table_name, MAX_DIMENSIONS_PER_TABLE
+                )))
+            }
+            DimensionOverflowBehavior::Warn => {
+                tracing::warn!(
+                    "Skipping a record for table {}: the batch already carries the \
+                    maximum {} distinct dimension names",
+                    table_name,
+                    MAX_DIMENSIONS_PER_TABLE
+                );
+                return Ok(None);
+            }
+            DimensionOverflowBehavior::Split => {
+                target = split_table_name(&table_name, suffix, suffix_format);
+                suffix += 1;
+            }
+        }
+    }
+}
+
+/// The template appended to a table name when an overflowing batch is
+/// split, from the optional `overflow_split_suffix_format` environment
+/// variable. `{}` stands for the split index; unset defaults to `_{}`,
+/// producing `readings_2`, `readings_3`, and so on. The template must
+/// contain `{}` so each split table gets a distinct name.
+pub fn overflow_split_suffix_format() -> Result<String> {
+    let format = env::var("overflow_split_suffix_format").unwrap_or_else(|_| "_{}".to_string());
+    if !format.contains("{}") {
+        return Err(anyhow!(
+            "Invalid overflow_split_suffix_format value {}; the template must \
+            contain {{}} for the split index",
+            format
+        ));
+    }
+    Ok(format)
+}
+
+/// Formats the name of the `index`-th split table for `base_name`.
+fn split_table_name(base_name: &str, index: usize, suffix_format: &str) -> String {
+    format!(
+        "{}{}",
+        base_name,
+        suffix_format.replace("{}", &index.to_string())
+    )
+}
+
+/// Routes one record to a table that can accept its measure names
+/// without crossing `MAX_UNIQUE_MEASURES_PER_TABLE`, updating the
+/// accumulated name sets; overflowing records go to suffixed tables the
+/// way the dimension split does. Infallible, because Timestream caps
+/// the measures in a single record well below the per-table limit, so a
+/// fresh table always fits.
+fn resolve_measure_overflow(
+    table_name: String,
+    record: &Record,
+    table_measure_names: &mut HashMap<String, HashSet<String>>,
+    suffix_format: &str,
+) -> String {
+    // Multi-measure records carry their field names in measure_values;
+    // single-measure records carry the one name in measure_name.
+    let record_names: Vec<&str> = if record.measure_values().is_empty() {
+        record.measure_name().into_iter().collect()
+    } else {
+        record
+            .measure_values()
+            .iter()
+            .map(|measure| measure.name())
+            .collect()
+    };
+
+    let mut target = table_name.clone();
+    let mut suffix = 2;
+    loop {
+        let accumulated = table_measure_names.entry(target.clone()).or_default();
+        let new_names = record_names
+            .iter()
+            .filter(|name| !accumulated.contains(**name))
+            .count();
+        if accumulated.len() + new_names <= MAX_UNIQUE_MEASURES_PER_TABLE {
+            for name in &record_names {
+                if !accumulated.contains(*name) {
+                    accumulated.insert((*name).to_string());
+                }
+            }
+            return target;
+        }
+        target = split_table_name(&table_name, suffix, suffix_format);
+        suffix += 1;
+    }
+}
+
+/// Resolves duplicate points in one table's records according to `mode`,
+/// returning how many duplicates were removed. The first occurrence's
+/// position is kept either way so record order stays stable.
+pub fn deduplicate_records(
+    table_name: &str,
+    records: &mut Vec<Record>,
+    mode: DuplicateMode,
+) -> Result<usize, ConnectorError> {
+    use std::collections::hash_map::Entry;
+    let original_len = records.len();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut deduplicated: Vec<Record> = Vec::with_capacity(original_len);
+    for record in records.drain(..) {
+        match seen.entry(record_series_key(&record)) {
+            Entry::Vacant(entry) => {
+                entry.insert(deduplicated.len());
+                deduplicated.push(record);
+            }
+            Entry::Occupied(entry) => match mode {
+                DuplicateMode::KeepLast => deduplicated[*entry.get()] = record,
+                DuplicateMode::KeepFirst => {}
+                DuplicateMode::Error => {
+                    return Err(ConnectorError::Validation(format!(
+                        "Duplicate point for table {} at timestamp {}: the same \
+                        dimensions and timestamp appear more than once in the batch",
+                        table_name,
+                        record.time().unwrap_or_default()
+                    )))
+                }
+            },
+        }
+    }
+    let removed = original_len - deduplicated.len();
+    *records = deduplicated;
+    Ok(removed)
+}
+
+/// A record's series identity: its sorted dimensions plus its timestamp.
+fn record_series_key(record: &Record) -> String {
+    let mut dimensions: Vec<String> = record
+        .dimensions()
+        .iter()
+        .map(|dimension| format!("{}={}", dimension.name(), dimension.value()))
+        .collect();
+    dimensions.sort_unstable();
+    format!(
+        "{}|{}|{:?}",
+        dimensions.join(","),
+        record.time().unwrap_or_default(),
+        record.time_unit()
+    )
+}
+
+/// Sorts each table's records by timestamp ascending. Timestream writes
+/// are more efficient when records for the same partition arrive
+/// time-ordered. Timestamps are normalized to nanoseconds for comparison
+/// so batches with mixed time units sort correctly.
+pub fn sort_records_by_time(records: &mut HashMap<String, Vec<Record>>) {
+    for table_records in records.values_mut() {
+        table_records.sort_by_key(record_time_nanos);
+    }
+}
+
+/// Returns a record's timestamp normalized to nanoseconds, saturating on
+/// overflow. Records without a parseable timestamp sort first.
+fn record_time_nanos(record: &Record) -> i64 {
+    let Some(time) = record.time().and_then(|time| time.parse::<i64>().ok()) else {
+        return i64::MIN;
+    };
+    let per_unit = match record.time_unit() {
+        Some(TimeUnit::Seconds) => 1_000_000_000,
+        Some(TimeUnit::Milliseconds) => 1_000_000,
+        Some(TimeUnit::Microseconds) => 1_000,
+        _ => 1,
+    };
+    time.saturating_mul(per_unit)
+}
+
+/// Returns the timestamp normalized to nanoseconds, erroring when the
+/// conversion from the given precision would overflow `i64` instead of
+/// wrapping. Used to reject far-future or far-past timestamps at build
+/// time; sorting and deduplication rely on the nanosecond normalization
+/// being representable.
+fn timestamp_nanos_checked(timestamp: i64, precision: &TimeUnit) -> Result<i64> {
+    let per_unit: i64 = match precision {
+        TimeUnit::Seconds => 1_000_000_000,
+        TimeUnit::Milliseconds => 1_000_000,
+        TimeUnit::Microseconds => 1_000,
+        _ => 1,
+    };
+    timestamp.checked_mul(per_unit).ok_or_else(|| {
+        anyhow!(
+            "Timestamp {} with {:?} precision overflows the nanosecond range",
+            timestamp,
+            precision
+        )
+    })
+}
+
+/// Converts a single metric to a Timestream multi-measure record. Tags
+/// become dimensions and fields become measure values. Borrowing wrapper
+/// around `build_record_from_parts` for callers that keep their metric.
+pub fn metric_to_timestream_record(
+    precision: &TimeUnit,
+    metric: &Metric,
+    measure_name: &str,
+) -> Result<Record, ConnectorError> {
+    let (_, tags, fields, timestamp) = metric.clone().into_parts();
+    build_record_from_parts(precision, tags, fields, timestamp, measure_name)
+}
+
+/// Converts one metric into its table name and record.
+fn convert_metric(
+    metric: Metric,
+    precision: &TimeUnit,
+    measure_name: &str,
+) -> Result<(String, Record), ConnectorError> {
+    let (name, tags, fields, timestamp) = metric.into_parts();
+    let record = build_record_from_parts(precision, tags, fields, timestamp, measure_name)?;
+    Ok((name, record))
+}
+
+/// Builds a multi-measure record from a metric's owned parts, moving tag
+/// and field strings straight into the dimension and measure builders.
+/// Environment parse failures surface as `Configuration` errors; value
+/// problems as `Validation`.
+fn build_record_from_parts(
+    precision: &TimeUnit,
+    tags: Option<Vec<(String, String)>>,
+    fields: Vec<(String, FieldValue)>,
+    timestamp: i64,
+    measure_name: &str,
+) -> Result<Record, ConnectorError> {
+    let type_overrides = field_type_overrides().map_err(ConnectorError::configuration)?;
+    let static_dimensions = static_dimensions().map_err(ConnectorError::configuration)?;
+    let renames = tag_key_renames().map_err(ConnectorError::configuration)?;
+    let tag_filter = tag_filter().map_err(ConnectorError::configuration)?;
+    build_record_from_parts_inner(
+        precision,
+        tags,
+        fields,
+        timestamp,
+        measure_name,
+        &type_overrides,
+        &static_dimensions,
+        &renames,
+        &tag_filter,
+    )
+    .map_err(ConnectorError::validation)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_record_from_parts_inner(
+    precision: &TimeUnit,
+    tags: Option<Vec<(String, String)>>,
+    fields: Vec<(String, FieldValue)>,
+    timestamp: i64,
+    measure_name: &str,
+    type_overrides: &HashMap<String, MeasureValueType>,
+    static_dimensions: &[(String, String)],
+    renames: &HashMap<String, String>,
+    tag_filter: &TagFilter,
+) -> Result<Record> {
+    timestamp_nanos_checked(timestamp, precision)?;
+    let mut record_builder = Record::builder()
+        .measure_name(measure_name)
+        .measure_value_type(MeasureValueType::Multi)
+        .time(timestamp.to_string())
+        .time_unit(precision.clone());
+
+    // Dimension sources are applied in precedence order: incoming tags
+    // (filtered first, on their incoming keys), then renamed tags, then
+    // promoted fields, then static dimensions.
+    let mut fields = fields;
+    let tags = filter_tags(tags, tag_filter);
+    let tags = apply_tag_renames(tags, renames)?;
+    let tags = promote_fields_to_dimensions(tags, &mut fields, &promoted_fields())?;
+    for (name, value) in merge_static_dimensions(tags, static_dimensions)? {
+        record_builder =
+            record_builder.dimensions(Dimension::builder().name(name).value(value).build()?);
+    }
+
+    // With `single_field_uses_field_name`, a metric left with exactly one
+    // field (after any promotions) stores it as a single-measure record:
+    // the field key is more meaningful as the measure name than the
+    // static configured one. Counted after promotion so a promoted field
+    // does not keep a lone remaining field in multi-measure form.
+    let single_measure =
+        fields.len() == 1 && env_var_to_bool("single_field_uses_field_name");
+
+    for (field_key, mut field_value) in fields {
+        if let FieldValue::Json(value) = &field_value {
+            if !env_var_to_bool("stringify_unsupported_fields") {
+                return Err(anyhow!(
+                    "Field {} has an unsupported value type; set \
+                    stringify_unsupported_fields to store it as a JSON string",
+                    field_key
+                ));
+            }
+            tracing::warn!(
+                "Storing unsupported field {} as a JSON string: {}",
+                field_key,
+                value
+            );
+        }
+        if let FieldValue::String(value) = &field_value {
+            if value.is_empty() {
+                match env::var("empty_string_behavior").ok().as_deref() {
+                    Some("skip") => {
+                        tracing::warn!("Skipping empty string field {}", field_key);
+                        continue;
+                    }
+                    Some("replace_with_null") => {
+                        field_value = FieldValue::String("null".to_string());
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "String field {} is empty; Timestream rejects empty VARCHAR \
+                            values",
+                            field_key
+                        ))
+                    }
+                }
+            }
+        }
+        if env_var_to_bool("compress_string_fields") {
+            if let FieldValue::String(value) = &field_value {
+                if value.len() > COMPRESS_STRING_MIN_BYTES {
+                    field_value = FieldValue::String(compress_string_field(&field_key, value)?);
+                }
+            }
+        }
+        if let FieldValue::F64(value) = field_value {
+            // Scientific notation like 1e309 overflows to infinity during
+            // parsing; Timestream rejects non-finite doubles.
+            if !value.is_finite() {
+                let replacement = env::var("nan_replacement")
+                    .ok()
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .filter(|value| value.is_finite());
+                match replacement {
+                    Some(replacement) => {
+                        tracing::warn!(
+                            "Replacing non-finite float field {} value {} with {}",
+                            field_key,
+                            value,
+                            replacement
+                        );
+                        field_value = FieldValue::F64(replacement);
+                    }
+                    None => {
+                        return Err(anyhow!(
+                            "Float field {} has a non-finite value {}; set \
+                            nan_replacement to substitute a finite value",
+                            field_key,
+                            value
+                        ))
+                    }
+                }
+            }
+        }
+        if let FieldValue::U64(value) = field_value {
+            if value > i64::MAX as u64 {
+                match env::var("u64_overflow_behavior").ok().as_deref() {
+                    Some("clamp") => {
+                        tracing::warn!(
+                            "Clamping u64 field {} value {} to the Timestream BIGINT \
+                            maximum {}",
+                            field_key,
+                            value,
+                            i64::MAX
+                        );
+                        field_value = FieldValue::U64(i64::MAX as u64);
+                    }
+                    Some("skip") => {
+                        tracing::warn!(
+                            "Skipping u64 field {} value {} exceeding the Timestream \
+                            BIGINT maximum {}",
+                            field_key,
+                            value,
+                            i64::MAX
+                        );
+                        continue;
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "u64 field {} value {} exceeds the Timestream BIGINT \
+                            maximum {}",
+                            field_key,
+                            value,
+                            i64::MAX
+                        ))
+                    }
+                }
+            }
+        }
+        let measure_type = resolve_measure_type(&field_key, &field_value, type_overrides)?;
+        if single_measure {
+            record_builder = record_builder
+                .measure_name(field_key)
+                .measure_value(field_value.to_string())
+                .measure_value_type(measure_type);
+        } else {
+            record_builder = record_builder.measure_values(
+                MeasureValue::builder()
+                    .name(field_key)
+                    .value(field_value.to_string())
+                    .r#type(measure_type)
+                    .build()?,
+            );
+        }
+    }
+
+    Ok(record_builder.build())
+}
+
+/// Gzips and base64-encodes a large string field value, marking it with
+/// [`COMPRESSED_STRING_PREFIX`]. Errors if the encoded value still
+/// exceeds Timestream's VARCHAR limit.
+fn compress_string_field(field_key: &str, value: &str) -> Result<String> {
+    use std::io::Write as _;
+
+    let mut encoder =
+        flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(value.as_bytes())
+        .and_then(|()| encoder.finish())
+        .map_err(|error| anyhow!("Failed to compress string field {}: {}", field_key, error))
+        .map(|compressed| {
+            format!(
+                "{}{}",
+                COMPRESSED_STRING_PREFIX,
+                base64::engine::general_purpose::STANDARD.encode(compressed)
+            )
+        })
+        .and_then(|encoded| {
+            if encoded.len() > MAX_VARCHAR_BYTES {
+                return Err(anyhow!(
+                    "String field {} is still {} bytes after compression, above the \
+                    {}-byte VARCHAR limit",
+                    field_key,
+                    encoded.len(),
+                    MAX_VARCHAR_BYTES
+                ));
+            }
+            Ok(encoded)
+        })
+}
+
+/// Maps a parsed field value to its Timestream measure value type.
+pub fn get_timestream_measure_type(field_value: &FieldValue) -> MeasureValueType {
+    match field_value {
+        FieldValue::I64(_) => MeasureValueType::Bigint,
+        FieldValue::U64(_) => MeasureValueType::Bigint,
+        FieldValue::F64(_) => MeasureValueType::Double,
+        FieldValue::Boolean(_) => MeasureValueType::Boolean,
+        FieldValue::String(_) => MeasureValueType::Varchar,
+        FieldValue::Json(_) => MeasureValueType::Varchar,
+    }
+}
@@ -0,0 +1,1423 @@
+use super::*;
+use crate::metric::Metric;
+use crate::test_support::EnvVarGuard;
+
+/// Sets the baseline variables shared across the test binary and
+/// returns the guard any further variable changes must be routed
+/// through; see [`crate::test_support`] for the pattern. Baseline
+/// values are deliberately set without restore so a guard drop cannot
+/// unset them under a concurrently running test.
+#[must_use]
+pub fn setup_minimal_env_vars() -> EnvVarGuard {
+    let guard = EnvVarGuard::acquire();
+    env::set_var("region", "us-east-1");
+    env::set_var("database_name", "records_builder_test_db");
+    env::set_var("enable_database_creation", "true");
+    env::set_var("enable_table_creation", "true");
+    env::set_var("enable_mag_store_writes", "true");
+    env::set_var("mag_store_retention_period", "7");
+    env::set_var("mem_store_retention_period", "24");
+    env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+    guard
+}
+
+#[must_use]
+pub fn setup_multi_measure_env_vars() -> EnvVarGuard {
+    let guard = EnvVarGuard::acquire();
+    env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+    guard
+}
+
+#[test]
+fn test_validate_env_variables() {
+    let _env = setup_minimal_env_vars();
+    validate_env_variables().expect("Validation must pass with all variables set");
+}
+
+#[test]
+fn test_validate_all_env_variables() {
+    let _env = setup_minimal_env_vars();
+    validate_all_env_variables().expect("Validation must pass with all variables set");
+}
+
+#[test]
+fn test_retention_only_required_for_table_creation() {
+    let retention_unset = |name: &str| !TABLE_CREATION_ENV_VARIABLES.contains(&name);
+
+    // Writing to existing tables never needs the retention periods.
+    assert!(table_creation_variable_problems(&retention_unset, false).is_empty());
+
+    // With creation enabled the misconfiguration must surface at
+    // startup, not when the first new measurement appears mid-request.
+    let problems = table_creation_variable_problems(&retention_unset, true);
+    assert_eq!(problems.len(), 2);
+    for variable_name in TABLE_CREATION_ENV_VARIABLES {
+        assert!(
+            problems.iter().any(|problem| problem.contains(variable_name)),
+            "Problems must mention {}: {:?}",
+            variable_name,
+            problems
+        );
+    }
+
+    assert!(table_creation_variable_problems(&|_| true, true).is_empty());
+}
+
+#[test]
+fn test_validate_all_reports_every_missing_variable() {
+    // Three missing variables must surface in one error rather than one
+    // per redeploy.
+    let missing = [
+        "database_name",
+        "enable_table_creation",
+        "measure_name_for_multi_measure_records",
+    ];
+    let problems = missing_variable_problems(&|name| !missing.contains(&name));
+    let error = combined_problems_error(problems)
+        .expect_err("Validation must fail with variables missing");
+    let message = error.to_string();
+    for variable_name in missing {
+        assert!(
+            message.contains(variable_name),
+            "Error must mention {}: {}",
+            variable_name,
+            message
+        );
+    }
+
+    assert!(combined_problems_error(Vec::new()).is_ok());
+}
+
+#[test]
+fn test_mtmm_basic() {
+    let _env = setup_multi_measure_env_vars();
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+    let records = build_records(vec![metric], &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Failed to build records from valid metric");
+    assert_eq!(records.len(), 1);
+    let table_records = records.get("readings").expect("Missing table records");
+    assert_eq!(table_records.len(), 1);
+    let record = &table_records[0];
+    assert_eq!(record.dimensions().len(), 1);
+    assert_eq!(record.dimensions()[0].name(), "fleet");
+    assert_eq!(record.dimensions()[0].value(), "Alberta");
+    assert_eq!(record.measure_name(), Some("influxdb-measure"));
+    assert_eq!(record.measure_value_type(), Some(&MeasureValueType::Multi));
+    assert_eq!(record.measure_values().len(), 1);
+    assert_eq!(record.measure_values()[0].name(), "fuel");
+    assert_eq!(record.measure_values()[0].value(), "30");
+    assert_eq!(
+        record.measure_values()[0].r#type(),
+        &MeasureValueType::Bigint
+    );
+    assert_eq!(record.time(), Some("1677605771000000000"));
+    assert_eq!(record.time_unit(), Some(&TimeUnit::Nanoseconds));
+}
+
+#[test]
+fn test_mtmm_empty_dimensions() {
+    let _env = setup_multi_measure_env_vars();
+    let metric = Metric::new(
+        "readings".to_string(),
+        None,
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+    let records = build_records(vec![metric], &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Failed to build records from valid metric");
+    assert!(records.get("readings").expect("Missing table records")[0]
+        .dimensions()
+        .is_empty());
+}
+
+#[test]
+fn test_mtmm_multiple_tables() {
+    let _env = setup_multi_measure_env_vars();
+    let metrics = vec![
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        ),
+        Metric::new(
+            "diagnostics".to_string(),
+            None,
+            vec![("load".to_string(), FieldValue::F64(0.5))],
+            1677605771000000000,
+        ),
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(31))],
+            1677605772000000000,
+        ),
+    ];
+    let records = build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Failed to build records from valid metrics");
+    assert_eq!(records.len(), 2);
+    assert_eq!(records.get("readings").unwrap().len(), 2);
+    assert_eq!(records.get("diagnostics").unwrap().len(), 1);
+}
+
+#[test]
+fn test_sort_records_by_time() {
+    let _env = setup_multi_measure_env_vars();
+    let metric = |timestamp: i64| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            timestamp,
+        )
+    };
+    let metrics = vec![
+        metric(1677605773000000000),
+        metric(1677605771000000000),
+        metric(1677605772000000000),
+    ];
+    let mut records = build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Failed to build records from valid metrics");
+    sort_records_by_time(&mut records);
+    let times: Vec<_> = records.get("readings").expect("Missing table records")
+        .iter()
+        .map(|record| record.time().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        times,
+        vec![
+            "1677605771000000000",
+            "1677605772000000000",
+            "1677605773000000000"
+        ]
+    );
+}
+
+#[test]
+fn test_sort_records_by_time_mixed_units() {
+    let _env = setup_multi_measure_env_vars();
+    let metric = |timestamp: i64| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            timestamp,
+        )
+    };
+    // 1677605772 s falls between the two millisecond timestamps once
+    // normalized to a common unit.
+    let mut records = HashMap::from([(
+        "readings".to_string(),
+        vec![
+            metric_to_timestream_record(
+                &TimeUnit::Milliseconds,
+                &metric(1677605773000),
+                "influxdb-measure",
+            )
+            .unwrap(),
+            metric_to_timestream_record(
+                &TimeUnit::Seconds,
+                &metric(1677605772),
+                "influxdb-measure",
+            )
+            .unwrap(),
+            metric_to_timestream_record(
+                &TimeUnit::Milliseconds,
+                &metric(1677605771000),
+                "influxdb-measure",
+            )
+            .unwrap(),
+        ],
+    )]);
+    sort_records_by_time(&mut records);
+    let times: Vec<_> = records.get("readings").expect("Missing table records")
+        .iter()
+        .map(|record| record.time().unwrap().to_string())
+        .collect();
+    assert_eq!(times, vec!["1677605771000", "1677605772", "1677605773000"]);
+}
+
+#[test]
+fn test_get_timestream_measure_type() {
+    assert_eq!(
+        get_timestream_measure_type(&FieldValue::I64(1)),
+        MeasureValueType::Bigint
+    );
+    assert_eq!(
+        get_timestream_measure_type(&FieldValue::U64(1)),
+        MeasureValueType::Bigint
+    );
+    assert_eq!(
+        get_timestream_measure_type(&FieldValue::F64(1.0)),
+        MeasureValueType::Double
+    );
+    assert_eq!(
+        get_timestream_measure_type(&FieldValue::Boolean(true)),
+        MeasureValueType::Boolean
+    );
+    assert_eq!(
+        get_timestream_measure_type(&FieldValue::String("ok".to_string())),
+        MeasureValueType::Varchar
+    );
+}
+
+#[test]
+fn test_u64_overflow_behavior() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let overflowing_metric = |value: u64| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![
+                ("total".to_string(), FieldValue::U64(value)),
+                ("fuel".to_string(), FieldValue::I64(30)),
+            ],
+            1677605771000000000,
+        )
+    };
+
+    // Exactly i64::MAX is representable and unaffected by any policy.
+    env_vars.remove("u64_overflow_behavior");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &overflowing_metric(i64::MAX as u64), "influxdb-measure")
+            .expect("i64::MAX must be accepted");
+    assert_eq!(record.measure_values()[0].value(), i64::MAX.to_string());
+
+    for value in [i64::MAX as u64 + 1, u64::MAX] {
+        // Default policy is error.
+        env_vars.remove("u64_overflow_behavior");
+        assert!(metric_to_timestream_record(
+            &TimeUnit::Nanoseconds,
+            &overflowing_metric(value),
+            "influxdb-measure"
+        )
+        .is_err());
+
+        env_vars.set("u64_overflow_behavior", "error");
+        assert!(metric_to_timestream_record(
+            &TimeUnit::Nanoseconds,
+            &overflowing_metric(value),
+            "influxdb-measure"
+        )
+        .is_err());
+
+        env_vars.set("u64_overflow_behavior", "clamp");
+        let record =
+            metric_to_timestream_record(&TimeUnit::Nanoseconds, &overflowing_metric(value), "influxdb-measure")
+                .expect("Clamp policy must not error");
+        assert_eq!(record.measure_values()[0].name(), "total");
+        assert_eq!(record.measure_values()[0].value(), i64::MAX.to_string());
+
+        env_vars.set("u64_overflow_behavior", "skip");
+        let record =
+            metric_to_timestream_record(&TimeUnit::Nanoseconds, &overflowing_metric(value), "influxdb-measure")
+                .expect("Skip policy must not error");
+        assert_eq!(record.measure_values().len(), 1);
+        assert_eq!(record.measure_values()[0].name(), "fuel");
+    }
+    env_vars.remove("u64_overflow_behavior");
+}
+
+#[test]
+fn test_nan_replacement_policy() {
+    let mut env_vars = EnvVarGuard::acquire();
+    let metric = |value: f64| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::F64(value))],
+            1677605771000000000,
+        )
+    };
+
+    env_vars.remove("nan_replacement");
+    for value in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        assert!(metric_to_timestream_record(
+            &TimeUnit::Nanoseconds,
+            &metric(value),
+            "influxdb-measure"
+        )
+        .is_err());
+    }
+
+    env_vars.set("nan_replacement", "0");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric(f64::INFINITY), "influxdb-measure")
+            .expect("Replacement policy must not error");
+    assert_eq!(record.measure_values()[0].value(), "0");
+
+    // A non-finite replacement value is ignored.
+    env_vars.set("nan_replacement", "inf");
+    assert!(metric_to_timestream_record(
+        &TimeUnit::Nanoseconds,
+        &metric(f64::NAN),
+        "influxdb-measure"
+    )
+    .is_err());
+
+    // Finite values are untouched by the policy.
+    env_vars.set("nan_replacement", "0");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric(40.5), "influxdb-measure")
+            .expect("Finite value must not error");
+    assert_eq!(record.measure_values()[0].value(), "40.5");
+
+    env_vars.remove("nan_replacement");
+}
+
+#[test]
+fn test_empty_string_behavior() {
+    let mut env_vars = EnvVarGuard::acquire();
+    let metric = Metric::new(
+        "readings".to_string(),
+        None,
+        vec![
+            ("note".to_string(), FieldValue::String(String::new())),
+            ("state".to_string(), FieldValue::String("ok".to_string())),
+        ],
+        1677605771000000000,
+    );
+
+    // Default policy is error.
+    env_vars.remove("empty_string_behavior");
+    assert!(
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .is_err()
+    );
+
+    env_vars.set("empty_string_behavior", "error");
+    assert!(
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .is_err()
+    );
+
+    env_vars.set("empty_string_behavior", "skip");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Skip policy must not error");
+    assert_eq!(record.measure_values().len(), 1);
+    assert_eq!(record.measure_values()[0].name(), "state");
+    assert_eq!(record.measure_values()[0].value(), "ok");
+
+    env_vars.set("empty_string_behavior", "replace_with_null");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Replace policy must not error");
+    assert_eq!(record.measure_values().len(), 2);
+    assert_eq!(record.measure_values()[0].name(), "note");
+    assert_eq!(record.measure_values()[0].value(), "null");
+    // Non-empty strings in the same record are unaffected.
+    assert_eq!(record.measure_values()[1].value(), "ok");
+
+    env_vars.remove("empty_string_behavior");
+}
+
+#[test]
+fn test_single_field_uses_field_name() {
+    let mut env_vars = EnvVarGuard::acquire();
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+        vec![("fuel".to_string(), FieldValue::F64(40.5))],
+        1677605771000000000,
+    );
+
+    // Off by default: a lone field still builds a multi-measure record
+    // under the static measure name.
+    env_vars.remove("single_field_uses_field_name");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Failed to build record");
+    assert_eq!(record.measure_name(), Some("influxdb-measure"));
+    assert_eq!(record.measure_value_type(), Some(&MeasureValueType::Multi));
+    assert_eq!(record.measure_values().len(), 1);
+
+    // With the flag the field key becomes the measure name and the value
+    // is stored in single-measure form.
+    env_vars.set("single_field_uses_field_name", "true");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Failed to build record");
+    assert_eq!(record.measure_name(), Some("fuel"));
+    assert_eq!(record.measure_value(), Some("40.5"));
+    assert_eq!(
+        record.measure_value_type(),
+        Some(&MeasureValueType::Double)
+    );
+    assert!(record.measure_values().is_empty());
+    // Dimensions are built the same either way.
+    assert_eq!(record.dimensions()[0].name(), "fleet");
+}
+
+#[test]
+fn test_single_field_flag_leaves_multi_field_metrics_unchanged() {
+    let mut env_vars = EnvVarGuard::acquire();
+    env_vars.set("single_field_uses_field_name", "true");
+    let metric = Metric::new(
+        "readings".to_string(),
+        None,
+        vec![
+            ("fuel".to_string(), FieldValue::F64(40.5)),
+            ("load".to_string(), FieldValue::I64(512)),
+        ],
+        1677605771000000000,
+    );
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Failed to build record");
+    assert_eq!(record.measure_name(), Some("influxdb-measure"));
+    assert_eq!(record.measure_value_type(), Some(&MeasureValueType::Multi));
+    assert_eq!(record.measure_values().len(), 2);
+}
+
+#[test]
+fn test_validate_kms_key_id() {
+    assert!(validate_kms_key_id(
+        "arn:aws:kms:us-east-1:123456789012:key/1234abcd-12ab-34cd-56ef-1234567890ab"
+    )
+    .is_ok());
+    assert!(
+        validate_kms_key_id("arn:aws:kms:us-east-1:123456789012:alias/my-key").is_ok()
+    );
+    assert!(validate_kms_key_id("1234abcd-12ab-34cd-56ef-1234567890ab").is_ok());
+    assert!(validate_kms_key_id("alias/my-key").is_ok());
+
+    assert!(validate_kms_key_id("").is_err());
+    assert!(validate_kms_key_id("key id with spaces").is_err());
+    assert!(
+        validate_kms_key_id("arn:aws:iam::123456789012:role/my-role").is_err(),
+        "Non-KMS ARN should be rejected"
+    );
+}
+
+#[test]
+fn test_field_type_overrides() {
+    let mut env_vars = EnvVarGuard::acquire();
+    let metric = Metric::new(
+        "readings".to_string(),
+        None,
+        vec![
+            ("last_updated".to_string(), FieldValue::I64(1677605771)),
+            ("active".to_string(), FieldValue::Boolean(true)),
+        ],
+        1677605771000000000,
+    );
+
+    // Unset means no overrides.
+    env_vars.remove("field_type_overrides");
+    assert!(field_type_overrides().unwrap().is_empty());
+
+    // A valid override changes the emitted measure value type.
+    env_vars.set("field_type_overrides", r#"{"last_updated": "TIMESTAMP"}"#);
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Valid override must not error");
+    assert_eq!(
+        record.measure_values()[0].r#type(),
+        &MeasureValueType::Timestamp
+    );
+    // Fields without an override keep their default mapping.
+    assert_eq!(
+        record.measure_values()[1].r#type(),
+        &MeasureValueType::Boolean
+    );
+
+    // An override incompatible with the parsed value type is rejected.
+    env_vars.set("field_type_overrides", r#"{"active": "BIGINT"}"#);
+    let error =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect_err("Incompatible override must be rejected");
+    assert!(error.to_string().contains("incompatible"));
+
+    // Malformed JSON and unknown type names are rejected up front.
+    env_vars.set("field_type_overrides", "not json");
+    assert!(field_type_overrides().is_err());
+    env_vars.set("field_type_overrides", r#"{"fuel": "DECIMAL"}"#);
+    assert!(field_type_overrides().is_err());
+
+    env_vars.remove("field_type_overrides");
+}
+
+#[test]
+fn test_static_dimensions() {
+    let mut env_vars = EnvVarGuard::acquire();
+    // Unset means no injected dimensions; set, the JSON object is parsed
+    // into sorted pairs.
+    env_vars.remove("static_dimensions");
+    env_vars.remove("static_dimensions_override");
+    assert!(static_dimensions().unwrap().is_empty());
+    env_vars.set(
+        "static_dimensions",
+        r#"{"cluster": "us-west", "env": "prod"}"#,
+    );
+    let parsed = static_dimensions().expect("Failed to parse static_dimensions");
+    env_vars.remove("static_dimensions");
+    assert_eq!(
+        parsed,
+        vec![
+            ("cluster".to_string(), "us-west".to_string()),
+            ("env".to_string(), "prod".to_string()),
+        ]
+    );
+
+    // Injection: configured dimensions are appended after the tags; on a
+    // key collision the incoming tag wins by default.
+    let tags = vec![("env".to_string(), "staging".to_string())];
+    let merged = merge_static_dimensions(Some(tags.clone()), &parsed)
+        .expect("Failed to merge static dimensions");
+    assert_eq!(
+        merged,
+        vec![
+            ("env".to_string(), "staging".to_string()),
+            ("cluster".to_string(), "us-west".to_string()),
+        ]
+    );
+
+    // With the override flag, the configured value wins the collision.
+    env_vars.set("static_dimensions_override", "true");
+    let merged = merge_static_dimensions(Some(tags.clone()), &parsed)
+        .expect("Failed to merge overriding static dimensions");
+    env_vars.remove("static_dimensions_override");
+    assert_eq!(merged[0], ("env".to_string(), "prod".to_string()));
+
+    // The merged dimension count must stay within the Timestream limit.
+    let many_tags: Vec<(String, String)> = (0..127)
+        .map(|index| (format!("tag_{}", index), "value".to_string()))
+        .collect();
+    let error = merge_static_dimensions(Some(many_tags), &parsed)
+        .expect_err("Exceeding the dimension limit must be rejected");
+    assert!(error.to_string().contains("128"));
+}
+
+#[test]
+fn test_env_var_to_bool() {
+    let mut env_vars = EnvVarGuard::acquire();
+    env_vars.set("test_env_var_to_bool_truthy", "true");
+    assert!(env_var_to_bool("test_env_var_to_bool_truthy"));
+    env_vars.set("test_env_var_to_bool_truthy", "T");
+    assert!(env_var_to_bool("test_env_var_to_bool_truthy"));
+    env_vars.set("test_env_var_to_bool_truthy", "1");
+    assert!(env_var_to_bool("test_env_var_to_bool_truthy"));
+    env_vars.set("test_env_var_to_bool_falsy", "false");
+    assert!(!env_var_to_bool("test_env_var_to_bool_falsy"));
+    env_vars.set("test_env_var_to_bool_falsy", "anything-else");
+    assert!(!env_var_to_bool("test_env_var_to_bool_falsy"));
+    assert!(!env_var_to_bool("test_env_var_to_bool_unset"));
+}
+
+#[test]
+fn test_compress_string_fields() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let metric = |value: String| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("log".to_string(), FieldValue::String(value))],
+            1677605771000000000,
+        )
+    };
+
+    env_vars.set("compress_string_fields", "true");
+    // A large but compressible value is stored gzipped with the marker
+    // prefix and round-trips back to the original.
+    let original = "status=ok ".repeat(180);
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric(original.clone()), "influxdb-measure")
+            .expect("Compressible value must be accepted");
+    let stored = record.measure_values()[0].value();
+    let encoded = stored
+        .strip_prefix(COMPRESSED_STRING_PREFIX)
+        .expect("Compressed value must carry the marker prefix");
+    assert!(stored.len() < original.len());
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .expect("Compressed value must be valid base64");
+    let mut decompressed = String::new();
+    std::io::Read::read_to_string(
+        &mut flate2::read::GzDecoder::new(compressed.as_slice()),
+        &mut decompressed,
+    )
+    .expect("Compressed value must gunzip");
+    assert_eq!(decompressed, original);
+
+    // Small strings are stored untouched.
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric("short".to_string()), "influxdb-measure")
+            .expect("Small value must be accepted");
+    assert_eq!(record.measure_values()[0].value(), "short");
+
+    // An incompressible value that stays above the VARCHAR limit errors.
+    // Xorshift noise; gzip cannot shrink it below the VARCHAR limit.
+    let mut state: u32 = 0x9E37_79B9;
+    let incompressible: String = (0..4000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            char::from(b'!' + (state % 90) as u8)
+        })
+        .collect();
+    let error =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric(incompressible), "influxdb-measure")
+            .expect_err("Still-oversized value must be rejected");
+    assert!(error.to_string().contains("after compression"), "Got error: {}", error);
+    env_vars.remove("compress_string_fields");
+}
+
+#[test]
+fn test_validate_partition_key_present() {
+    let _env = setup_multi_measure_env_vars();
+    let table_config = |enforce: bool| crate::timestream_utils::TableConfig {
+        mem_store_retention_period: 24,
+        mag_store_retention_period: 7,
+        enable_mag_store_writes: true,
+        custom_partition_key_type: Some("dimension".to_string()),
+        custom_partition_key_dimension: Some("fleet".to_string()),
+        enforce_custom_partition_key: enforce,
+    };
+
+    let tagged = build_records(
+        vec![Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        )],
+        &TimeUnit::Nanoseconds,
+        "influxdb-measure",
+    )
+    .expect("Failed to build records");
+    validate_partition_key_present(&tagged, &table_config(true))
+        .expect("Records carrying the partition key dimension must pass");
+
+    let untagged = build_records(
+        vec![Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        )],
+        &TimeUnit::Nanoseconds,
+        "influxdb-measure",
+    )
+    .expect("Failed to build records");
+    let error = validate_partition_key_present(&untagged, &table_config(true))
+        .expect_err("Records missing the partition key dimension must be flagged");
+    assert!(error.to_string().contains("fleet"), "Got error: {}", error);
+    assert!(error.to_string().contains("readings"), "Got error: {}", error);
+
+    // Not enforced, or a measure-type partition key: nothing to check.
+    validate_partition_key_present(&untagged, &table_config(false))
+        .expect("Unenforced partition keys must not be checked");
+    let mut measure_config = table_config(true);
+    measure_config.custom_partition_key_type = Some("measure".to_string());
+    validate_partition_key_present(&untagged, &measure_config)
+        .expect("Measure-type partition keys must not be checked");
+}
+
+#[test]
+fn test_stringify_unsupported_fields() {
+    let mut env_vars = EnvVarGuard::acquire();
+    let metric = Metric::new(
+        "readings".to_string(),
+        None,
+        vec![(
+            "diagnostics".to_string(),
+            FieldValue::Json(serde_json::json!({"code": 7, "sensors": [1, 2]})),
+        )],
+        1677605771000000000,
+    );
+
+    env_vars.remove("stringify_unsupported_fields");
+    let error = metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+        .expect_err("Unsupported values must be rejected by default");
+    assert!(error.to_string().contains("stringify_unsupported_fields"));
+
+    env_vars.set("stringify_unsupported_fields", "true");
+    let record = metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+        .expect("Unsupported values must be stringified when enabled");
+    assert_eq!(record.measure_values()[0].r#type(), &MeasureValueType::Varchar);
+    assert_eq!(
+        record.measure_values()[0].value(),
+        "{\"code\":7,\"sensors\":[1,2]}"
+    );
+    env_vars.remove("stringify_unsupported_fields");
+}
+
+#[test]
+fn test_env_var_to_bool_extended_values() {
+    let mut env_vars = EnvVarGuard::acquire();
+    for value in ["yes", "on", "enabled", "YES", "On", "Enabled"] {
+        env_vars.set("test_env_var_to_bool_extended", value);
+        assert!(
+            env_var_to_bool("test_env_var_to_bool_extended"),
+            "{:?} must be truthy",
+            value
+        );
+    }
+    for value in ["no", "off", "disabled", "f", "0", "NO", "Off"] {
+        env_vars.set("test_env_var_to_bool_extended", value);
+        assert!(
+            !env_var_to_bool("test_env_var_to_bool_extended"),
+            "{:?} must be falsy",
+            value
+        );
+    }
+    env_vars.remove("test_env_var_to_bool_extended");
+}
+
+/// Two records for the same series (dimensions and timestamp) with
+/// distinguishable measure values.
+fn colliding_records() -> Vec<Record> {
+    let _env = setup_multi_measure_env_vars();
+    let metrics = [
+        Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        ),
+        Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+            vec![("fuel".to_string(), FieldValue::I64(31))],
+            1677605771000000000,
+        ),
+    ];
+    metrics
+        .iter()
+        .map(|metric| {
+            metric_to_timestream_record(&TimeUnit::Nanoseconds, metric, "influxdb-measure")
+                .expect("Failed to build record")
+        })
+        .collect()
+}
+
+#[test]
+fn test_deduplicate_keep_last() {
+    let mut records = colliding_records();
+    let removed = deduplicate_records("readings", &mut records, DuplicateMode::KeepLast)
+        .expect("keep_last must not error");
+    assert_eq!(removed, 1);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].measure_values()[0].value(), "31");
+}
+
+#[test]
+fn test_deduplicate_keep_first() {
+    let mut records = colliding_records();
+    let removed = deduplicate_records("readings", &mut records, DuplicateMode::KeepFirst)
+        .expect("keep_first must not error");
+    assert_eq!(removed, 1);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].measure_values()[0].value(), "30");
+}
+
+#[test]
+fn test_deduplicate_error_mode() {
+    let mut records = colliding_records();
+    let error = deduplicate_records("readings", &mut records, DuplicateMode::Error)
+        .expect_err("Colliding records must be rejected in error mode");
+    assert!(
+        matches!(error, ConnectorError::Validation(_)),
+        "Expected a Validation error, got: {}",
+        error
+    );
+    assert!(error.to_string().contains("readings"));
+    assert!(error.to_string().contains("1677605771000000000"));
+}
+
+#[test]
+fn test_deduplicate_distinct_series_untouched() {
+    let _env = setup_multi_measure_env_vars();
+    // Same timestamp but different dimension values: not duplicates.
+    let metrics = [
+        Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        ),
+        Metric::new(
+            "readings".to_string(),
+            Some(vec![("fleet".to_string(), "Ontario".to_string())]),
+            vec![("fuel".to_string(), FieldValue::I64(31))],
+            1677605771000000000,
+        ),
+    ];
+    let mut records: Vec<Record> = metrics
+        .iter()
+        .map(|metric| {
+            metric_to_timestream_record(&TimeUnit::Nanoseconds, metric, "influxdb-measure")
+                .expect("Failed to build record")
+        })
+        .collect();
+    let removed = deduplicate_records("readings", &mut records, DuplicateMode::Error)
+        .expect("Distinct series must not collide");
+    assert_eq!(removed, 0);
+    assert_eq!(records.len(), 2);
+}
+
+#[test]
+fn test_duplicate_mode_parsing() {
+    let mut env_vars = EnvVarGuard::acquire();
+    assert_eq!(duplicate_mode().unwrap(), DuplicateMode::KeepLast);
+    env_vars.set("on_duplicate", "keep_first");
+    assert_eq!(duplicate_mode().unwrap(), DuplicateMode::KeepFirst);
+    env_vars.set("on_duplicate", "error");
+    assert_eq!(duplicate_mode().unwrap(), DuplicateMode::Error);
+    env_vars.set("on_duplicate", "bogus");
+    let error = duplicate_mode().expect_err("Invalid mode must be rejected");
+    env_vars.remove("on_duplicate");
+    assert!(error.to_string().contains("bogus"));
+}
+
+#[test]
+fn test_dimension_overflow_behavior_parsing() {
+    let mut env_vars = EnvVarGuard::acquire();
+    assert_eq!(
+        dimension_overflow_behavior().unwrap(),
+        DimensionOverflowBehavior::Error
+    );
+    env_vars.set("dimension_overflow_behavior", "error");
+    assert_eq!(
+        dimension_overflow_behavior().unwrap(),
+        DimensionOverflowBehavior::Error
+    );
+    env_vars.set("dimension_overflow_behavior", "split");
+    assert_eq!(
+        dimension_overflow_behavior().unwrap(),
+        DimensionOverflowBehavior::Split
+    );
+    env_vars.set("dimension_overflow_behavior", "warn");
+    assert_eq!(
+        dimension_overflow_behavior().unwrap(),
+        DimensionOverflowBehavior::Warn
+    );
+    env_vars.set("dimension_overflow_behavior", "bogus");
+    let error = dimension_overflow_behavior().expect_err("Invalid value must be rejected");
+    assert!(error.to_string().contains("bogus"));
+}
+
+#[test]
+fn test_dimension_overflow_splits_into_suffixed_table() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let wide_metric = |range: std::ops::Range<usize>| {
+        Metric::new(
+            "readings".to_string(),
+            Some(
+                range
+                    .map(|index| (format!("tag_{}", index), "value".to_string()))
+                    .collect(),
+            ),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        )
+    };
+    // Two records that individually respect the per-record dimension
+    // limit but together carry 200 distinct names for one table.
+    let metrics = vec![wide_metric(0..100), wide_metric(100..200)];
+
+    // The default behavior rejects the batch with a pointer to the knob.
+    let error = build_records(metrics.clone(), &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect_err("Overflowing batch must be rejected by default");
+    assert!(
+        error.to_string().contains("dimension_overflow_behavior"),
+        "Got: {}",
+        error
+    );
+
+    // `split` routes the overflowing record to a suffixed table.
+    env_vars.set("dimension_overflow_behavior", "split");
+    let records = build_records(metrics.clone(), &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Split behavior must not error");
+    assert_eq!(records.len(), 2);
+    assert_eq!(records["readings"].len(), 1);
+    assert_eq!(records["readings_2"].len(), 1);
+
+    // `warn` drops the overflowing record and keeps the rest.
+    env_vars.set("dimension_overflow_behavior", "warn");
+    let records = build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Warn behavior must not error");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records["readings"].len(), 1);
+}
+
+#[test]
+fn test_measure_overflow_splits_into_suffixed_table() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    // Eleven records of 100 unique field keys each: 1100 distinct
+    // measure names for one table, past Timestream's 1024 limit.
+    let wide_metric = |index: usize| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            (0..100)
+                .map(|field| (format!("field_{}_{}", index, field), FieldValue::I64(30)))
+                .collect(),
+            1677605771000000000 + index as i64,
+        )
+    };
+    let metrics: Vec<Metric> = (0..11).map(wide_metric).collect();
+
+    // Without the knob the batch passes through untouched and the limit
+    // is left for Timestream to enforce.
+    let records = build_records(metrics.clone(), &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Build must not error without the knob");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records["readings"].len(), 11);
+
+    // With it, the eleventh record (which would push the table to 1100
+    // names) is routed to a suffixed table.
+    env_vars.set("batch_split_on_measure_count", "true");
+    let records = build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Split must not error");
+    assert_eq!(records.len(), 2);
+    assert_eq!(records["readings"].len(), 10);
+    assert_eq!(records["readings_2"].len(), 1);
+}
+
+#[test]
+fn test_overflow_split_suffix_format_is_shared_and_validated() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let wide_metric = |range: std::ops::Range<usize>| {
+        Metric::new(
+            "readings".to_string(),
+            Some(
+                range
+                    .map(|index| (format!("tag_{}", index), "value".to_string()))
+                    .collect(),
+            ),
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        )
+    };
+    let metrics = vec![wide_metric(0..100), wide_metric(100..200)];
+    env_vars.set("dimension_overflow_behavior", "split");
+
+    // The dimension split honors the custom template.
+    env_vars.set("overflow_split_suffix_format", "_part{}");
+    let records = build_records(metrics.clone(), &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Split behavior must not error");
+    assert_eq!(records["readings_part2"].len(), 1);
+
+    // A template without a `{}` placeholder would name every split table
+    // the same; it is rejected before any records are built.
+    env_vars.set("overflow_split_suffix_format", "_overflow");
+    let error = build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect_err("A template without a placeholder must be rejected");
+    assert!(
+        error.to_string().contains("overflow_split_suffix_format"),
+        "Got: {}",
+        error
+    );
+}
+
+#[test]
+fn test_parallel_build_matches_sequential() {
+    let _env = setup_multi_measure_env_vars();
+    let metrics: Vec<Metric> = (0..500)
+        .map(|index| {
+            Metric::new(
+                format!("readings_{}", index % 4),
+                Some(vec![("truck_id".to_string(), format!("truck_{}", index % 7))]),
+                vec![("fuel".to_string(), FieldValue::I64(index))],
+                1677605771000000000 + index,
+            )
+        })
+        .collect();
+
+    let sequential = build_multi_measure_records_with_threshold(
+        metrics.clone(),
+        &TimeUnit::Nanoseconds,
+        "influxdb-measure",
+        usize::MAX,
+    )
+    .expect("Failed to build records sequentially");
+    let parallel = build_multi_measure_records_with_threshold(
+        metrics,
+        &TimeUnit::Nanoseconds,
+        "influxdb-measure",
+        0,
+    )
+    .expect("Failed to build records in parallel");
+    assert_eq!(parallel, sequential);
+    // Within-table record order must match the input order.
+    let times: Vec<_> = parallel
+        .get("readings_0")
+        .expect("Missing table records")
+        .iter()
+        .map(|record| record.time().unwrap().to_string())
+        .collect();
+    let mut sorted = times.clone();
+    sorted.sort();
+    assert_eq!(times, sorted);
+}
+
+#[test]
+fn test_parallel_build_surfaces_errors() {
+    let _env = setup_multi_measure_env_vars();
+    let mut metrics: Vec<Metric> = (0..10)
+        .map(|index| {
+            Metric::new(
+                "readings".to_string(),
+                None,
+                vec![("fuel".to_string(), FieldValue::I64(index))],
+                1677605771000000000 + index,
+            )
+        })
+        .collect();
+    metrics.push(Metric::new(
+        "readings".to_string(),
+        None,
+        vec![("fuel".to_string(), FieldValue::F64(f64::NAN))],
+        1677605772000000000,
+    ));
+    build_multi_measure_records_with_threshold(
+        metrics,
+        &TimeUnit::Nanoseconds,
+        "influxdb-measure",
+        0,
+    )
+    .expect_err("NaN must be rejected on the parallel path");
+}
+
+#[test]
+fn test_tag_key_rename() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    env_vars.set("tag_key_rename", r#"{"host": "hostname"}"#);
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![
+            ("fleet".to_string(), "Alberta".to_string()),
+            ("host".to_string(), "host1".to_string()),
+        ]),
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Failed to build record with a tag rename");
+    env_vars.remove("tag_key_rename");
+    let names: Vec<_> = record
+        .dimensions()
+        .iter()
+        .map(|dimension| dimension.name())
+        .collect();
+    assert_eq!(names, vec!["fleet", "hostname"]);
+    assert_eq!(record.dimensions()[1].value(), "host1");
+}
+
+#[test]
+fn test_tag_filter_parsing() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    env_vars.remove("tag_allowlist");
+    env_vars.remove("tag_denylist");
+    assert_eq!(TagFilter::from_env().unwrap(), TagFilter::None);
+
+    env_vars.set("tag_denylist", "request_id, trace_id,,");
+    let filter = TagFilter::from_env().unwrap();
+    assert_eq!(
+        filter,
+        TagFilter::Deny(
+            ["request_id".to_string(), "trace_id".to_string()]
+                .into_iter()
+                .collect()
+        )
+    );
+
+    env_vars.set("tag_allowlist", "fleet");
+    let error = TagFilter::from_env().expect_err("Setting both lists must be rejected");
+    assert!(error.to_string().contains("mutually exclusive"));
+
+    env_vars.remove("tag_denylist");
+    let filter = TagFilter::from_env().unwrap();
+    assert_eq!(filter, TagFilter::Allow(["fleet".to_string()].into_iter().collect()));
+}
+
+#[test]
+fn test_tag_denylist_drops_dimensions() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    env_vars.set("tag_denylist", "request_id, trace_id");
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![
+            ("fleet".to_string(), "Alberta".to_string()),
+            ("request_id".to_string(), "b51ad95e".to_string()),
+            ("trace_id".to_string(), "1-67891233".to_string()),
+        ]),
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+    let record = metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+        .expect("Failed to build record with a tag denylist");
+    let names: Vec<_> = record
+        .dimensions()
+        .iter()
+        .map(|dimension| dimension.name())
+        .collect();
+    assert_eq!(names, vec!["fleet"]);
+    // Measure values are untouched by tag filtering.
+    assert_eq!(record.measure_values().len(), 1);
+    assert_eq!(record.measure_values()[0].name(), "fuel");
+}
+
+#[test]
+fn test_tag_allowlist_keeps_only_listed_dimensions() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    env_vars.set("tag_allowlist", "fleet, truck_id");
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![
+            ("fleet".to_string(), "Alberta".to_string()),
+            ("request_id".to_string(), "b51ad95e".to_string()),
+            ("truck_id".to_string(), "truck_1".to_string()),
+        ]),
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+    let record = metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+        .expect("Failed to build record with a tag allowlist");
+    let names: Vec<_> = record
+        .dimensions()
+        .iter()
+        .map(|dimension| dimension.name())
+        .collect();
+    assert_eq!(names, vec!["fleet", "truck_id"]);
+    assert_eq!(record.dimensions()[0].value(), "Alberta");
+    assert_eq!(record.dimensions()[1].value(), "truck_1");
+}
+
+#[test]
+fn test_tag_rename_collision_with_incoming_tag() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![
+            ("host".to_string(), "host1".to_string()),
+            ("hostname".to_string(), "host2".to_string()),
+        ]),
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+
+    // The incoming tag wins: the rename is skipped and both tags keep
+    // their original names.
+    env_vars.set("tag_key_rename", r#"{"host": "hostname"}"#);
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("A colliding rename must be skipped by default");
+    let names: Vec<_> = record
+        .dimensions()
+        .iter()
+        .map(|dimension| dimension.name())
+        .collect();
+    assert_eq!(names, vec!["host", "hostname"]);
+
+    env_vars.set("strict_dimension_collisions", "true");
+    let error =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect_err("A colliding rename must error in strict mode");
+    env_vars.remove("strict_dimension_collisions");
+    env_vars.remove("tag_key_rename");
+    assert!(error.to_string().contains("hostname"));
+}
+
+#[test]
+fn test_promote_field_to_dimension() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    env_vars.set("promote_fields_to_dimensions", "plate");
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![("fleet".to_string(), "Alberta".to_string())]),
+        vec![
+            ("fuel".to_string(), FieldValue::I64(30)),
+            ("plate".to_string(), FieldValue::String("ABC-123".to_string())),
+        ],
+        1677605771000000000,
+    );
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("Failed to build record with a promoted field");
+    env_vars.remove("promote_fields_to_dimensions");
+    assert_eq!(record.dimensions().len(), 2);
+    assert_eq!(record.dimensions()[1].name(), "plate");
+    assert_eq!(record.dimensions()[1].value(), "ABC-123");
+    // The promoted field must no longer appear as a measure value.
+    assert_eq!(record.measure_values().len(), 1);
+    assert_eq!(record.measure_values()[0].name(), "fuel");
+}
+
+#[test]
+fn test_promoted_field_collision_with_tag() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![("plate".to_string(), "XYZ-789".to_string())]),
+        vec![
+            ("fuel".to_string(), FieldValue::I64(30)),
+            ("plate".to_string(), FieldValue::String("ABC-123".to_string())),
+        ],
+        1677605771000000000,
+    );
+
+    // The tag wins: the promotion is skipped and the field stays a
+    // measure value.
+    env_vars.set("promote_fields_to_dimensions", "plate");
+    let record =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect("A colliding promotion must be skipped by default");
+    assert_eq!(record.dimensions().len(), 1);
+    assert_eq!(record.dimensions()[0].value(), "XYZ-789");
+    assert_eq!(record.measure_values().len(), 2);
+
+    env_vars.set("strict_dimension_collisions", "true");
+    let error =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect_err("A colliding promotion must error in strict mode");
+    env_vars.remove("strict_dimension_collisions");
+    env_vars.remove("promote_fields_to_dimensions");
+    assert!(error.to_string().contains("plate"));
+}
+
+#[test]
+fn test_static_dimension_collision_strict() {
+    let mut env_vars = setup_multi_measure_env_vars();
+    let metric = Metric::new(
+        "readings".to_string(),
+        Some(vec![("env".to_string(), "staging".to_string())]),
+        vec![("fuel".to_string(), FieldValue::I64(30))],
+        1677605771000000000,
+    );
+    env_vars.set("static_dimensions", r#"{"env": "prod"}"#);
+    env_vars.set("strict_dimension_collisions", "true");
+    let error =
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric, "influxdb-measure")
+            .expect_err("A colliding static dimension must error in strict mode");
+    env_vars.remove("strict_dimension_collisions");
+    env_vars.remove("static_dimensions");
+    assert!(error.to_string().contains("env"));
+}
+
+#[test]
+fn test_grouping_many_tables() {
+    let _env = setup_multi_measure_env_vars();
+    let metrics: Vec<Metric> = (0..200)
+        .map(|index| {
+            Metric::new(
+                format!("readings_{}", index % 50),
+                None,
+                vec![("fuel".to_string(), FieldValue::I64(index))],
+                1677605771000000000 + index,
+            )
+        })
+        .collect();
+    let records = build_records(metrics, &TimeUnit::Nanoseconds, "influxdb-measure")
+        .expect("Failed to build records from valid metrics");
+    assert_eq!(records.len(), 50);
+    for table_records in records.values() {
+        assert_eq!(table_records.len(), 4);
+    }
+    // Within-table order must still follow the input order.
+    let times: Vec<_> = records
+        .get("readings_0")
+        .expect("Missing table records")
+        .iter()
+        .map(|record| record.time().unwrap().to_string())
+        .collect();
+    let mut sorted = times.clone();
+    sorted.sort();
+    assert_eq!(times, sorted);
+}
+
+/// Captures subscriber output so tests can assert on emitted events.
+#[derive(Clone, Default)]
+struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_span_close_timing_emitted_at_trace() {
+    let _env = setup_multi_measure_env_vars();
+    let buffer = SharedBuffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .with_max_level(tracing::Level::TRACE)
+        .with_writer({
+            let buffer = buffer.clone();
+            move || buffer.clone()
+        })
+        .with_ansi(false)
+        .finish();
+    tracing::subscriber::with_default(subscriber, || {
+        let metric = Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            1677605771000000000,
+        );
+        build_records(vec![metric], &TimeUnit::Nanoseconds, "influxdb-measure")
+            .expect("Failed to build records from valid metric");
+    });
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone())
+        .expect("Subscriber output must be valid UTF-8");
+    // The span close event carries the duration fields.
+    assert!(output.contains("build_records"), "output: {}", output);
+    assert!(output.contains("close"), "output: {}", output);
+    assert!(output.contains("time.busy"), "output: {}", output);
+}
+
+#[test]
+fn test_timestamp_overflow_per_precision() {
+    let _env = setup_multi_measure_env_vars();
+    let metric = |timestamp: i64| {
+        Metric::new(
+            "readings".to_string(),
+            None,
+            vec![("fuel".to_string(), FieldValue::I64(30))],
+            timestamp,
+        )
+    };
+    // Nanosecond precision passes timestamps through, so the full i64
+    // range is representable.
+    for timestamp in [i64::MAX, i64::MIN] {
+        metric_to_timestream_record(&TimeUnit::Nanoseconds, &metric(timestamp), "influxdb-measure")
+            .expect("Nanosecond timestamps never overflow");
+    }
+    // For each coarser precision, the largest representable value passes
+    // and one step beyond errors instead of wrapping.
+    for (precision, per_unit) in [
+        (TimeUnit::Microseconds, 1_000),
+        (TimeUnit::Milliseconds, 1_000_000),
+        (TimeUnit::Seconds, 1_000_000_000),
+    ] {
+        let max = i64::MAX / per_unit;
+        let min = i64::MIN / per_unit;
+        metric_to_timestream_record(&precision, &metric(max), "influxdb-measure")
+            .expect("Boundary timestamp must be accepted");
+        metric_to_timestream_record(&precision, &metric(min), "influxdb-measure")
+            .expect("Boundary timestamp must be accepted");
+        let error =
+            metric_to_timestream_record(&precision, &metric(max + 1), "influxdb-measure")
+                .expect_err("Overflowing timestamp must be rejected");
+        assert!(error.to_string().contains("overflows"));
+        assert!(
+            metric_to_timestream_record(&precision, &metric(min - 1), "influxdb-measure")
+                .is_err()
+        );
+    }
+}
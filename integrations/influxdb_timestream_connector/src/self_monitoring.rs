@@ -0,0 +1,228 @@
+//! Per-invocation self-monitoring records.
+//!
+//! Behind the `enable_self_monitoring` flag, the connector writes its own
+//! operational stats into a meta table (default `connector_metrics`)
+//! after each payload, so dashboards can chart ingestion volume per table
+//! straight from Timestream. The meta table is created under the same
+//! `TableConfig` rules as data tables, and a failed meta write only logs
+//! a warning — it never fails the request that produced it.
+
+use crate::timestream_utils::TimestreamWriteClient;
+use crate::{records_builder, ConnectorConfig};
+use anyhow::Result;
+use aws_sdk_timestreamwrite::types::{
+    Dimension, MeasureValue, MeasureValueType, Record, TimeUnit,
+};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Table the self-monitoring records are written to when
+/// `self_monitoring_table` is not configured.
+pub const DEFAULT_SELF_MONITORING_TABLE: &str = "connector_metrics";
+
+/// Measure name of the multi-measure self-monitoring records.
+pub const SELF_MONITORING_MEASURE_NAME: &str = "connector-stats";
+
+/// Whether self-monitoring records are written after each payload.
+pub fn self_monitoring_enabled() -> bool {
+    records_builder::env_var_to_bool("enable_self_monitoring")
+}
+
+/// The configured meta table name.
+pub fn self_monitoring_table() -> String {
+    env::var("self_monitoring_table").unwrap_or_else(|_| DEFAULT_SELF_MONITORING_TABLE.to_string())
+}
+
+/// The Lambda function name used as a dimension, so several deployed
+/// connectors can share one meta table.
+fn function_name() -> String {
+    env::var("AWS_LAMBDA_FUNCTION_NAME")
+        .unwrap_or_else(|_| "influxdb_timestream_connector".to_string())
+}
+
+/// Builds one self-monitoring record per written table, with the function
+/// and table names as dimensions and the invocation's stats as measures.
+/// `rejected` marks whether the ingestion failed, in which case the
+/// table's records count as rejected rather than written.
+pub fn build_self_monitoring_records(
+    table_counts: &[(String, usize)],
+    write_latency: Duration,
+    rejected: bool,
+    batch_bytes: usize,
+) -> Result<Vec<Record>> {
+    let function_name = function_name();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let measure = |name: &str, value: String| {
+        MeasureValue::builder()
+            .name(name)
+            .value(value)
+            .r#type(MeasureValueType::Bigint)
+            .build()
+    };
+    table_counts
+        .iter()
+        .map(|(table_name, count)| {
+            let (written, rejected_count) = if rejected { (0, *count) } else { (*count, 0) };
+            Ok(Record::builder()
+                .dimensions(
+                    Dimension::builder()
+                        .name("function_name")
+                        .value(&function_name)
+                        .build()?,
+                )
+                .dimensions(
+                    Dimension::builder()
+                        .name("table_name")
+                        .value(table_name)
+                        .build()?,
+                )
+                .measure_name(SELF_MONITORING_MEASURE_NAME)
+                .measure_value_type(MeasureValueType::Multi)
+                .measure_values(measure("records_written", written.to_string())?)
+                .measure_values(measure(
+                    "write_latency_ms",
+                    write_latency.as_millis().to_string(),
+                )?)
+                .measure_values(measure("rejected_records", rejected_count.to_string())?)
+                .measure_values(measure("batch_bytes", batch_bytes.to_string())?)
+                .time(timestamp.to_string())
+                .time_unit(TimeUnit::Milliseconds)
+                .build())
+        })
+        .collect()
+}
+
+/// Writes the self-monitoring records for one payload through the normal
+/// ingestion path, so the meta table is auto-created under the same
+/// `TableConfig` rules as data tables. Any failure is logged and
+/// swallowed: the meta write must never fail the request it describes.
+pub async fn write_self_monitoring_records<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    config: &ConnectorConfig,
+    table_counts: &[(String, usize)],
+    write_latency: Duration,
+    rejected: bool,
+    batch_bytes: usize,
+) {
+    let table_name = self_monitoring_table();
+    let records =
+        match build_self_monitoring_records(table_counts, write_latency, rejected, batch_bytes) {
+            Ok(records) => records,
+            Err(error) => {
+                tracing::warn!("Failed to build self-monitoring records: {:#}", error);
+                return;
+            }
+        };
+    if records.is_empty() {
+        return;
+    }
+    if let Err(error) = crate::handle_multi_table_ingestion(
+        client,
+        config,
+        HashMap::from([(table_name.clone(), records)]),
+    )
+    .await
+    {
+        tracing::warn!(
+            "Self-monitoring write to table {} failed: {:#}",
+            table_name,
+            error
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestream_utils::mock::MockTimestreamClient;
+    use crate::timestream_utils::ClientError;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_build_self_monitoring_records_contents() {
+        let table_counts = vec![("readings".to_string(), 42), ("diagnostics".to_string(), 7)];
+        let records = build_self_monitoring_records(
+            &table_counts,
+            Duration::from_millis(150),
+            false,
+            2048,
+        )
+        .expect("Failed to build self-monitoring records");
+        assert_eq!(records.len(), 2);
+
+        let record = &records[0];
+        assert_eq!(record.measure_name(), Some(SELF_MONITORING_MEASURE_NAME));
+        assert_eq!(record.dimensions()[0].name(), "function_name");
+        assert_eq!(record.dimensions()[1].name(), "table_name");
+        assert_eq!(record.dimensions()[1].value(), "readings");
+        let measures: Vec<(&str, &str)> = record
+            .measure_values()
+            .iter()
+            .map(|measure| (measure.name(), measure.value()))
+            .collect();
+        assert_eq!(
+            measures,
+            vec![
+                ("records_written", "42"),
+                ("write_latency_ms", "150"),
+                ("rejected_records", "0"),
+                ("batch_bytes", "2048"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_self_monitoring_records_rejected() {
+        let records = build_self_monitoring_records(
+            &[("readings".to_string(), 42)],
+            Duration::from_millis(10),
+            true,
+            512,
+        )
+        .expect("Failed to build self-monitoring records");
+        let measures: Vec<(&str, &str)> = records[0]
+            .measure_values()
+            .iter()
+            .map(|measure| (measure.name(), measure.value()))
+            .collect();
+        assert_eq!(measures[0], ("records_written", "0"));
+        assert_eq!(measures[2], ("rejected_records", "42"));
+    }
+
+    #[tokio::test]
+    async fn test_meta_write_failure_is_swallowed() {
+        let client = Arc::new(MockTimestreamClient::new());
+        // The database lookup fails outright; the meta write must only
+        // warn, not propagate the error.
+        client
+            .describe_database_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::Other(anyhow!("connection reset"))));
+        let config = ConnectorConfig {
+            database_name: "meta_db".to_string(),
+            enable_database_creation: false,
+            enable_table_creation: false,
+            fail_fast: false,
+            measure_name_for_multi_measure_records: "influxdb-measure".to_string(),
+            sort_records_by_time: false,
+            skip_invalid_lines: false,
+            skip_missing_tables: false,
+            database_from_tag: None,
+        };
+        write_self_monitoring_records(
+            &client,
+            &config,
+            &[("readings".to_string(), 1)],
+            Duration::from_millis(5),
+            false,
+            64,
+        )
+        .await;
+    }
+}
@@ -0,0 +1,349 @@
+//! Optional connector configuration from SSM Parameter Store.
+//!
+//! When the `config_ssm_prefix` environment variable is set, the Lambda
+//! handler reads every parameter under that path prefix at cold start and
+//! again once the `config_ssm_refresh_seconds` TTL elapses, overlaying
+//! the values onto the env-derived [`ConnectorConfig`]. This lets
+//! settings rotate (allow-lists, retention periods, the auth token as a
+//! decrypted SecureString) without redeploying the function. When SSM is
+//! unreachable the last-known-good values are kept and a warning is
+//! logged, so ingestion never fails on a configuration refresh.
+
+use crate::ConnectorConfig;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Default interval between SSM refreshes, in seconds.
+pub const DEFAULT_SSM_REFRESH_SECONDS: u64 = 300;
+
+/// The parameter names overlaid onto [`ConnectorConfig`] fields. Anything
+/// else fetched from SSM is exported as an environment variable so the
+/// settings read directly from the environment (retention periods,
+/// allow-lists, the auth token) pick it up too.
+const CONFIG_FIELD_NAMES: [&str; 9] = [
+    "database_name",
+    "database_from_tag",
+    "enable_database_creation",
+    "enable_table_creation",
+    "fail_fast",
+    "measure_name_for_multi_measure_records",
+    "sort_records_by_time",
+    "skip_invalid_lines",
+    "skip_missing_tables",
+];
+
+/// Source of configuration parameters under a path prefix. Implemented
+/// for the SSM client; tests substitute a mock.
+#[async_trait]
+pub trait SsmParameterSource {
+    /// Returns the parameters under `prefix`, keyed by their name with
+    /// the prefix (and any leading slash) stripped.
+    async fn parameters_by_path(&self, prefix: &str) -> Result<HashMap<String, String>>;
+}
+
+#[async_trait]
+impl SsmParameterSource for aws_sdk_ssm::Client {
+    async fn parameters_by_path(&self, prefix: &str) -> Result<HashMap<String, String>> {
+        let mut parameters = HashMap::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let mut request = self
+                .get_parameters_by_path()
+                .path(prefix)
+                .recursive(true)
+                .with_decryption(true);
+            if let Some(token) = &next_token {
+                request = request.next_token(token);
+            }
+            let output = request.send().await.map_err(|error| {
+                anyhow!("Failed to read SSM parameters under {}: {}", prefix, error)
+            })?;
+            for parameter in output.parameters() {
+                let (Some(name), Some(value)) = (parameter.name(), parameter.value()) else {
+                    continue;
+                };
+                let name = name.strip_prefix(prefix).unwrap_or(name);
+                parameters.insert(
+                    name.trim_start_matches('/').to_string(),
+                    value.to_string(),
+                );
+            }
+            next_token = output.next_token().map(str::to_string);
+            if next_token.is_none() {
+                return Ok(parameters);
+            }
+        }
+    }
+}
+
+/// The last-known-good SSM parameters and when they were fetched.
+struct CacheState {
+    parameters: HashMap<String, String>,
+    fetched_at: Option<Instant>,
+}
+
+/// TTL-bounded cache of the SSM parameters. A failed refresh keeps the
+/// previous values so a Parameter Store outage cannot take down
+/// ingestion.
+pub struct SsmConfigCache {
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+impl SsmConfigCache {
+    pub fn new(ttl: Duration) -> Self {
+        SsmConfigCache {
+            ttl,
+            state: Mutex::new(CacheState {
+                parameters: HashMap::new(),
+                fetched_at: None,
+            }),
+        }
+    }
+
+    /// Fetches the parameters under `prefix` if the TTL has elapsed (or
+    /// nothing has been fetched yet), then returns the current values.
+    /// Fetch failures log a warning and leave the last-known-good values
+    /// in place; the failed attempt still counts against the TTL so a
+    /// persistent outage is retried once per interval, not per request.
+    pub async fn refresh(
+        &self,
+        source: &impl SsmParameterSource,
+        prefix: &str,
+    ) -> HashMap<String, String> {
+        {
+            let state = self.state.lock().unwrap();
+            if let Some(fetched_at) = state.fetched_at {
+                if fetched_at.elapsed() < self.ttl {
+                    return state.parameters.clone();
+                }
+            }
+        }
+        match source.parameters_by_path(prefix).await {
+            Ok(parameters) => {
+                let mut state = self.state.lock().unwrap();
+                state.parameters = parameters.clone();
+                state.fetched_at = Some(Instant::now());
+                parameters
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to refresh configuration from SSM prefix {}: {:#}; keeping \
+                    the last known good values",
+                    prefix,
+                    error
+                );
+                let mut state = self.state.lock().unwrap();
+                state.fetched_at = Some(Instant::now());
+                state.parameters.clone()
+            }
+        }
+    }
+
+    /// The cached parameters, empty until the first successful refresh.
+    pub fn parameters(&self) -> HashMap<String, String> {
+        self.state.lock().unwrap().parameters.clone()
+    }
+}
+
+static SSM_CACHE: OnceLock<SsmConfigCache> = OnceLock::new();
+
+/// The process-wide SSM configuration cache, sized to the configured TTL.
+pub fn ssm_cache() -> &'static SsmConfigCache {
+    SSM_CACHE.get_or_init(|| SsmConfigCache::new(refresh_ttl()))
+}
+
+static SSM_CLIENT: OnceLock<aws_sdk_ssm::Client> = OnceLock::new();
+
+/// The process-wide SSM client, built on first use.
+pub async fn shared_client() -> &'static aws_sdk_ssm::Client {
+    if let Some(client) = SSM_CLIENT.get() {
+        return client;
+    }
+    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let client = aws_sdk_ssm::Client::new(&config);
+    SSM_CLIENT.get_or_init(|| client)
+}
+
+/// The configured SSM path prefix, or `None` when SSM configuration is
+/// disabled.
+pub fn ssm_prefix() -> Option<String> {
+    env::var("config_ssm_prefix")
+        .ok()
+        .filter(|prefix| !prefix.is_empty())
+}
+
+/// The refresh interval, from the optional `config_ssm_refresh_seconds`
+/// environment variable. Unparsable values fall back to the default.
+fn refresh_ttl() -> Duration {
+    let seconds = env::var("config_ssm_refresh_seconds")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SSM_REFRESH_SECONDS);
+    Duration::from_secs(seconds)
+}
+
+/// Refreshes the cached parameters when an SSM prefix is configured and
+/// exports any parameter that is not a [`ConnectorConfig`] field as an
+/// environment variable, so settings read directly from the environment
+/// pick up rotated values too.
+pub async fn refresh_from_ssm() {
+    let Some(prefix) = ssm_prefix() else {
+        return;
+    };
+    let parameters = ssm_cache().refresh(shared_client().await, &prefix).await;
+    for (name, value) in &parameters {
+        if !CONFIG_FIELD_NAMES.contains(&name.as_str()) {
+            env::set_var(name, value);
+        }
+    }
+}
+
+/// Overlays SSM parameters onto the env-derived configuration. SSM
+/// values win over the environment, since rotation through Parameter
+/// Store is the whole point of configuring a prefix.
+pub fn overlay_config(
+    mut config: ConnectorConfig,
+    parameters: &HashMap<String, String>,
+) -> ConnectorConfig {
+    for (name, value) in parameters {
+        match name.as_str() {
+            "database_name" => config.database_name = value.clone(),
+            "database_from_tag" => config.database_from_tag = Some(value.clone()),
+            "measure_name_for_multi_measure_records" => {
+                config.measure_name_for_multi_measure_records = value.clone()
+            }
+            "enable_database_creation" => {
+                config.enable_database_creation = crate::records_builder::str_to_bool(value, name)
+            }
+            "enable_table_creation" => {
+                config.enable_table_creation = crate::records_builder::str_to_bool(value, name)
+            }
+            "fail_fast" => config.fail_fast = crate::records_builder::str_to_bool(value, name),
+            "sort_records_by_time" => {
+                config.sort_records_by_time = crate::records_builder::str_to_bool(value, name)
+            }
+            "skip_invalid_lines" => {
+                config.skip_invalid_lines = crate::records_builder::str_to_bool(value, name)
+            }
+            "skip_missing_tables" => {
+                config.skip_missing_tables = crate::records_builder::str_to_bool(value, name)
+            }
+            _ => {}
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct MockSsmSource {
+        results: Mutex<VecDeque<Result<HashMap<String, String>>>>,
+        calls: AtomicUsize,
+    }
+
+    impl MockSsmSource {
+        fn new() -> Self {
+            MockSsmSource {
+                results: Mutex::new(VecDeque::new()),
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SsmParameterSource for MockSsmSource {
+        async fn parameters_by_path(&self, _prefix: &str) -> Result<HashMap<String, String>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| Err(anyhow!("No mock result queued")))
+        }
+    }
+
+    fn base_config() -> ConnectorConfig {
+        ConnectorConfig {
+            database_name: "env_db".to_string(),
+            enable_database_creation: false,
+            enable_table_creation: false,
+            fail_fast: false,
+            measure_name_for_multi_measure_records: "influxdb-measure".to_string(),
+            sort_records_by_time: false,
+            skip_invalid_lines: false,
+            skip_missing_tables: false,
+            database_from_tag: None,
+        }
+    }
+
+    #[test]
+    fn test_overlay_config_precedence() {
+        let parameters = HashMap::from([
+            ("database_name".to_string(), "ssm_db".to_string()),
+            ("enable_table_creation".to_string(), "yes".to_string()),
+            ("ignored_setting".to_string(), "whatever".to_string()),
+        ]);
+        let config = overlay_config(base_config(), &parameters);
+        // SSM values win; untouched fields keep their env-derived values.
+        assert_eq!(config.database_name, "ssm_db");
+        assert!(config.enable_table_creation);
+        assert!(!config.enable_database_creation);
+        assert_eq!(config.measure_name_for_multi_measure_records, "influxdb-measure");
+    }
+
+    #[test]
+    fn test_overlay_config_empty_parameters_is_identity() {
+        let config = overlay_config(base_config(), &HashMap::new());
+        assert_eq!(config.database_name, "env_db");
+        assert!(!config.fail_fast);
+    }
+
+    #[tokio::test]
+    async fn test_cache_refreshes_only_after_ttl() {
+        let source = MockSsmSource::new();
+        source.results.lock().unwrap().extend([
+            Ok(HashMap::from([("database_name".to_string(), "first".to_string())])),
+            Ok(HashMap::from([("database_name".to_string(), "second".to_string())])),
+        ]);
+
+        let cache = SsmConfigCache::new(Duration::from_secs(60));
+        let parameters = cache.refresh(&source, "/connector/").await;
+        assert_eq!(parameters.get("database_name").unwrap(), "first");
+        // Within the TTL the cached values are returned without a fetch.
+        let parameters = cache.refresh(&source, "/connector/").await;
+        assert_eq!(parameters.get("database_name").unwrap(), "first");
+        assert_eq!(source.calls.load(Ordering::SeqCst), 1);
+
+        // A zero TTL refetches on every refresh.
+        let cache = SsmConfigCache::new(Duration::ZERO);
+        let parameters = cache.refresh(&source, "/connector/").await;
+        assert_eq!(parameters.get("database_name").unwrap(), "second");
+        cache.refresh(&source, "/connector/").await;
+        assert_eq!(source.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_keeps_last_known_good() {
+        let source = MockSsmSource::new();
+        source.results.lock().unwrap().extend([
+            Ok(HashMap::from([("database_name".to_string(), "good".to_string())])),
+            Err(anyhow!("SSM is unreachable")),
+        ]);
+
+        let cache = SsmConfigCache::new(Duration::ZERO);
+        cache.refresh(&source, "/connector/").await;
+        let parameters = cache.refresh(&source, "/connector/").await;
+        assert_eq!(parameters.get("database_name").unwrap(), "good");
+        assert_eq!(source.calls.load(Ordering::SeqCst), 2);
+    }
+}
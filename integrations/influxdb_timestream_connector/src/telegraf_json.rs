@@ -0,0 +1,202 @@
+use crate::metric::{FieldValue, Metric};
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::env;
+
+/// Returns whether a JSON body has the Telegraf `outputs.http` JSON
+/// shape: an object with a top-level `metrics` array.
+pub fn is_telegraf_payload(body: &str) -> bool {
+    serde_json::from_str::<Value>(body)
+        .map(|payload| payload["metrics"].is_array())
+        .unwrap_or(false)
+}
+
+/// Parses a Telegraf JSON payload
+/// (`{"metrics": [{"fields": {...}, "name": "cpu", "tags": {...},
+/// "timestamp": 1458229140}]}`) into owned `Metric`s. Telegraf emits
+/// timestamps in seconds unless its `json_timestamp_units` setting is
+/// changed; mirror that setting in the connector's `json_timestamp_units`
+/// environment variable. Timestamps are normalized to nanoseconds.
+pub fn parse_telegraf_json(body: &str) -> Result<Vec<Metric>> {
+    let payload: Value =
+        serde_json::from_str(body).context("Request body is not valid JSON")?;
+    let elements = payload["metrics"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Telegraf payload has no top-level metrics array"))?;
+    let nanos_per_unit = timestamp_nanos_per_unit()?;
+
+    let mut metrics: Vec<Metric> = Vec::new();
+    for (index, element) in elements.iter().enumerate() {
+        metrics.push(
+            parse_telegraf_metric(element, nanos_per_unit)
+                .with_context(|| format!("Metric {}", index))?,
+        );
+    }
+    Ok(metrics)
+}
+
+/// Returns the nanoseconds per timestamp unit from the optional
+/// `json_timestamp_units` variable, accepting Telegraf's `1s`/`1ms`/
+/// `1us`/`1ns` forms as well as bare unit names. Defaults to seconds.
+fn timestamp_nanos_per_unit() -> Result<i64> {
+    let units = match env::var("json_timestamp_units") {
+        Ok(units) => units,
+        Err(_) => return Ok(1_000_000_000),
+    };
+    match units.trim_start_matches('1') {
+        "s" => Ok(1_000_000_000),
+        "ms" => Ok(1_000_000),
+        "us" => Ok(1_000),
+        "ns" => Ok(1),
+        _ => Err(anyhow!(
+            "json_timestamp_units must be one of 1s, 1ms, 1us, 1ns, got {}",
+            units
+        )),
+    }
+}
+
+fn parse_telegraf_metric(element: &Value, nanos_per_unit: i64) -> Result<Metric> {
+    let name = element["name"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Metric is missing a name"))?;
+
+    let tags = match &element["tags"] {
+        Value::Null => None,
+        Value::Object(tags) => Some(
+            tags.iter()
+                .map(|(key, value)| {
+                    let value = value
+                        .as_str()
+                        .ok_or_else(|| anyhow!("Tag {} is not a string", key))?;
+                    Ok((key.to_string(), value.to_string()))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        _ => return Err(anyhow!("Metric tags must be an object")),
+    };
+
+    let fields = element["fields"]
+        .as_object()
+        .ok_or_else(|| anyhow!("Metric is missing a fields object"))?
+        .iter()
+        .map(|(key, value)| (key.to_string(), json_value_to_field_value(value)))
+        .collect::<Vec<_>>();
+
+    let timestamp = element["timestamp"]
+        .as_i64()
+        .ok_or_else(|| anyhow!("Metric is missing an integer timestamp"))?;
+    let timestamp = timestamp.checked_mul(nanos_per_unit).ok_or_else(|| {
+        anyhow!("Timestamp {} overflows the nanosecond range", timestamp)
+    })?;
+
+    let metric = Metric::new(name.to_string(), tags, fields, timestamp);
+    metric.validate()?;
+    Ok(metric)
+}
+
+/// Maps a JSON field value to a `FieldValue`, preserving the integer vs
+/// float distinction JSON carries. Nested arrays and objects become
+/// `FieldValue::Json`; the record builder decides whether to stringify or
+/// reject them.
+fn json_value_to_field_value(value: &Value) -> FieldValue {
+    match value {
+        Value::Bool(value) => FieldValue::Boolean(*value),
+        Value::String(value) => FieldValue::String(value.to_string()),
+        Value::Number(number) => {
+            if let Some(value) = number.as_i64() {
+                FieldValue::I64(value)
+            } else if let Some(value) = number.as_u64() {
+                FieldValue::U64(value)
+            } else {
+                FieldValue::F64(number.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        _ => FieldValue::Json(value.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::EnvVarGuard;
+
+    #[test]
+    fn test_parse_single_metric() {
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.remove("json_timestamp_units");
+        let body = r#"{"metrics": [{
+            "fields": {"usage_idle": 98.5, "usage_steal": 0, "state": "ok", "up": true},
+            "name": "cpu",
+            "tags": {"host": "alpha"},
+            "timestamp": 1458229140
+        }]}"#;
+        let metrics = parse_telegraf_json(body).expect("Failed to parse Telegraf JSON");
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].name(), "cpu");
+        assert_eq!(
+            metrics[0].tags(),
+            &Some(vec![("host".to_string(), "alpha".to_string())])
+        );
+        // Integer vs float distinction is preserved; keys arrive sorted.
+        assert_eq!(
+            metrics[0].fields(),
+            &vec![
+                ("state".to_string(), FieldValue::String("ok".to_string())),
+                ("up".to_string(), FieldValue::Boolean(true)),
+                ("usage_idle".to_string(), FieldValue::F64(98.5)),
+                ("usage_steal".to_string(), FieldValue::I64(0)),
+            ]
+        );
+        // Telegraf timestamps are seconds by default.
+        assert_eq!(metrics[0].timestamp(), 1458229140000000000);
+    }
+
+    #[test]
+    fn test_parse_batched_metrics() {
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.remove("json_timestamp_units");
+        let body = r#"{"metrics": [
+            {"fields": {"usage_idle": 98.5}, "name": "cpu", "timestamp": 1458229140},
+            {"fields": {"used_percent": 41.2}, "name": "mem", "timestamp": 1458229141}
+        ]}"#;
+        let metrics = parse_telegraf_json(body).expect("Failed to parse Telegraf JSON");
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].name(), "cpu");
+        assert_eq!(metrics[1].name(), "mem");
+    }
+
+    #[test]
+    fn test_timestamp_unit_conversion() {
+        let body = r#"{"metrics": [
+            {"fields": {"usage_idle": 98.5}, "name": "cpu", "timestamp": 1458229140000}
+        ]}"#;
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.set("json_timestamp_units", "1ms");
+        let metrics = parse_telegraf_json(body).expect("Failed to parse Telegraf JSON");
+        assert_eq!(metrics[0].timestamp(), 1458229140000000000);
+
+        env_vars.set("json_timestamp_units", "1fortnight");
+        assert!(parse_telegraf_json(body).is_err());
+    }
+
+    #[test]
+    fn test_malformed_entry_reports_index() {
+        let mut env_vars = EnvVarGuard::acquire();
+        env_vars.remove("json_timestamp_units");
+        let body = r#"{"metrics": [
+            {"fields": {"usage_idle": 98.5}, "name": "cpu", "timestamp": 1458229140},
+            {"name": "mem", "timestamp": 1458229141}
+        ]}"#;
+        let error = parse_telegraf_json(body)
+            .expect_err("Entry without fields must be rejected");
+        let message = format!("{:#}", error);
+        assert!(message.contains("Metric 1"), "Got error: {}", message);
+    }
+
+    #[test]
+    fn test_is_telegraf_payload() {
+        assert!(is_telegraf_payload(r#"{"metrics": []}"#));
+        assert!(!is_telegraf_payload(r#"[{"measurement": "cpu"}]"#));
+        assert!(!is_telegraf_payload("not json"));
+    }
+}
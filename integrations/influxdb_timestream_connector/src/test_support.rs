@@ -0,0 +1,157 @@
+//! Scoped environment-variable handling for tests.
+//!
+//! The process environment is shared state, so under the default
+//! parallel test harness a test that changes a configuration variable
+//! can clobber another test mid-flight. Tests follow two rules:
+//!
+//! - Baseline variables every test agrees on (`region`,
+//!   `database_name`, the retention periods, the measure name) are set
+//!   with plain `env::set_var` and never removed, so their values are
+//!   stable once any test has run.
+//! - Any other variable goes through an [`EnvVarGuard`]: `set` and
+//!   `remove` record the previous value and restore it on drop (also on
+//!   panic), and constructing the guard takes a process-wide lock so
+//!   two tests deviating from the baseline never interleave. Create at
+//!   most one guard per test, at the top, and route every deviating
+//!   `set_var`/`remove_var` through it.
+//! - Tests that exercise process-wide mutable state the environment
+//!   configures — the circuit breaker and the adaptive concurrency
+//!   limiter — take the lock with [`EnvVarGuard::acquire`] even when
+//!   they change no variables, so they cannot interleave with a test
+//!   that does.
+//!
+//! The module is compiled unconditionally so the integration tests
+//! under `tests/` can use it; it is not part of the connector's API. It
+//! also hosts the integration tests' database-naming scheme, shared
+//! between the tests and the orphan sweeper in
+//! `tests/cleanup_orphans.rs`.
+
+use std::env;
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// RAII guard over deviating environment variables; see the module
+/// documentation for the usage pattern.
+pub struct EnvVarGuard {
+    _lock: MutexGuard<'static, ()>,
+    saved: Vec<(String, Option<String>)>,
+}
+
+impl EnvVarGuard {
+    /// Takes the process-wide environment lock, blocking until any
+    /// other test's guard has been dropped.
+    pub fn acquire() -> Self {
+        static ENV_LOCK: Mutex<()> = Mutex::new(());
+        // A test that panicked while holding the lock has already
+        // restored its variables through its guard's drop, so the
+        // poisoned state carries no torn environment.
+        let lock = ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Self {
+            _lock: lock,
+            saved: Vec::new(),
+        }
+    }
+
+    /// Sets `name` for the lifetime of the guard.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.save(name);
+        env::set_var(name, value);
+    }
+
+    /// Unsets `name` for the lifetime of the guard.
+    pub fn remove(&mut self, name: &str) {
+        self.save(name);
+        env::remove_var(name);
+    }
+
+    fn save(&mut self, name: &str) {
+        // Only the value from before the first touch is restored.
+        if !self.saved.iter().any(|(saved, _)| saved == name) {
+            self.saved.push((name.to_string(), env::var(name).ok()));
+        }
+    }
+}
+
+impl Drop for EnvVarGuard {
+    fn drop(&mut self) {
+        for (name, value) in self.saved.drain(..) {
+            match value {
+                Some(value) => env::set_var(&name, value),
+                None => env::remove_var(&name),
+            }
+        }
+    }
+}
+
+/// Prefix shared by every integration-test database. The orphan sweeper
+/// in `tests/cleanup_orphans.rs` only touches databases matching it.
+pub const INTEG_DATABASE_PREFIX: &str = "influxdb_timestream_connector_integ";
+
+/// Returns a database name unique to this test: the integ prefix, the
+/// creation time in epoch seconds, and a process-unique suffix. Two CI
+/// runs in the same account therefore cannot stomp on each other's
+/// databases, and a name left behind by a crashed run records when that
+/// run started so the orphan sweeper can judge its age.
+pub fn unique_integ_database_name() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the Unix epoch");
+    format!(
+        "{}_{}_{:x}{:08x}_{}",
+        INTEG_DATABASE_PREFIX,
+        now.as_secs(),
+        process::id(),
+        now.subsec_nanos(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+    )
+}
+
+/// Parses the epoch-second creation time out of a database name produced
+/// by [`unique_integ_database_name`]. `None` for names that do not match
+/// the pattern, including the fixed name earlier revisions of the
+/// integration tests shared, which may predate its run by months.
+pub fn integ_database_created_at(database_name: &str) -> Option<u64> {
+    database_name
+        .strip_prefix(INTEG_DATABASE_PREFIX)?
+        .strip_prefix('_')?
+        .split('_')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_integ_database_names_are_unique_and_dated() {
+        let first = unique_integ_database_name();
+        let second = unique_integ_database_name();
+        assert_ne!(first, second);
+
+        let created_at = integ_database_created_at(&first)
+            .expect("Generated name must carry its creation time");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(created_at <= now && now - created_at < 60, "Got: {}", first);
+    }
+
+    #[test]
+    fn test_integ_database_created_at_rejects_foreign_names() {
+        assert_eq!(integ_database_created_at("some_other_db"), None);
+        assert_eq!(integ_database_created_at(INTEG_DATABASE_PREFIX), None);
+        // The fixed name the integration tests used to share.
+        assert_eq!(
+            integ_database_created_at("influxdb_timestream_connector_integ_db"),
+            None
+        );
+    }
+}
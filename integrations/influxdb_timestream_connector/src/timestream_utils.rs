@@ -0,0 +1,2165 @@
+use crate::error::ConnectorError;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_timestreamwrite as timestream_write;
+use aws_sdk_timestreamwrite::error::ProvideErrorMetadata;
+use aws_sdk_timestreamwrite::types::{
+    MagneticStoreWriteProperties, PartitionKey, PartitionKeyEnforcementLevel, PartitionKeyType,
+    Record, RetentionProperties, Schema,
+};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Maximum number of records accepted by a single WriteRecords call.
+pub const MAX_TIMESTREAM_BATCH_SIZE: usize = 100;
+
+/// Approximate payload budget for a single WriteRecords call, kept under
+/// Timestream's 1 MB request limit. Estimated with
+/// `approximate_record_bytes`, which undercounts the protocol framing,
+/// hence the margin.
+pub const MAX_TIMESTREAM_BATCH_BYTES: usize = 900 * 1024;
+
+/// Maximum number of attempts for a single record batch when Timestream
+/// responds with a throttling error.
+const MAX_WRITE_RETRIES: u32 = 3;
+
+/// Base backoff applied between throttled write attempts, scaled linearly
+/// by the attempt count.
+const WRITE_RETRY_BACKOFF_MS: u64 = 100;
+
+/// Number of consecutive successful batch writes required before the
+/// adaptive concurrency controller raises its limit by one.
+const AIMD_SUCCESS_WINDOW: usize = 10;
+
+/// Maximum number of attempts when building the shared client through
+/// `ManagedClient::get_or_build`.
+const CLIENT_INIT_MAX_ATTEMPTS: u32 = 3;
+
+/// Base backoff applied between failed client initialization attempts,
+/// scaled linearly by the attempt count.
+const CLIENT_INIT_RETRY_BACKOFF_MS: u64 = 100;
+
+/// Timestream's maximum memory store retention period, in hours.
+pub const MAX_MEM_STORE_RETENTION_HOURS: i64 = 8766;
+
+/// Timestream's maximum magnetic store retention period, in days.
+pub const MAX_MAG_STORE_RETENTION_DAYS: i64 = 73000;
+
+/// Memory store retention applied when `mem_store_retention_period` is
+/// unset, matching Timestream's own default for tables created without
+/// retention properties.
+pub const DEFAULT_MEM_STORE_RETENTION_HOURS: i64 = 6;
+
+/// Magnetic store retention applied when `mag_store_retention_period` is
+/// unset, matching Timestream's own default.
+pub const DEFAULT_MAG_STORE_RETENTION_DAYS: i64 = 73000;
+
+/// A Timestream client error, classified into the categories the
+/// connector logic branches on. Produced by `TimestreamWriteClient`
+/// implementations so callers never inspect raw SDK error types.
+#[derive(Debug)]
+pub enum ClientError {
+    ResourceNotFound(String),
+    Throttling(String),
+    AccessDenied(String),
+    Other(anyhow::Error),
+}
+
+impl ClientError {
+    pub fn is_resource_not_found(&self) -> bool {
+        matches!(self, ClientError::ResourceNotFound(_))
+    }
+
+    pub fn is_throttling(&self) -> bool {
+        matches!(self, ClientError::Throttling(_))
+    }
+
+    pub fn is_access_denied(&self) -> bool {
+        matches!(self, ClientError::AccessDenied(_))
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::ResourceNotFound(message) => {
+                write!(f, "Resource not found: {}", message)
+            }
+            ClientError::Throttling(message) => write!(f, "Throttled: {}", message),
+            ClientError::AccessDenied(message) => write!(f, "Access denied: {}", message),
+            ClientError::Other(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// The subset of the Timestream write API the connector uses, factored
+/// into a trait so unit tests can substitute a mock for the live client.
+#[async_trait]
+pub trait TimestreamWriteClient: Send + Sync {
+    /// Describes a database, returning its KMS key ID if one is set.
+    async fn describe_database(&self, database_name: &str)
+        -> Result<Option<String>, ClientError>;
+
+    /// Describes a table, succeeding if it exists.
+    async fn describe_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<(), ClientError>;
+
+    /// Creates a database, optionally encrypted with a customer KMS key.
+    async fn create_database(
+        &self,
+        database_name: &str,
+        kms_key_id: Option<&str>,
+    ) -> Result<(), ClientError>;
+
+    /// Creates a table with the given retention, magnetic store, and
+    /// optional partition key schema settings.
+    async fn create_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        retention_properties: RetentionProperties,
+        magnetic_store_write_properties: MagneticStoreWriteProperties,
+        schema: Option<Schema>,
+    ) -> Result<(), ClientError>;
+
+    /// Writes one batch of records to a table.
+    async fn write_records(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        records: Vec<Record>,
+    ) -> Result<(), ClientError>;
+
+    /// Deletes a table.
+    async fn delete_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<(), ClientError>;
+
+    /// Deletes a database.
+    async fn delete_database(&self, database_name: &str) -> Result<(), ClientError>;
+}
+
+/// Classifies an SDK error by its service error code.
+fn to_client_error<E>(error: aws_sdk_timestreamwrite::error::SdkError<E>) -> ClientError
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    let message = error
+        .message()
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string());
+    match error.code() {
+        Some("ResourceNotFoundException") => ClientError::ResourceNotFound(message),
+        Some("ThrottlingException") => ClientError::Throttling(message),
+        Some("AccessDeniedException") => ClientError::AccessDenied(message),
+        _ => ClientError::Other(anyhow!(error)),
+    }
+}
+
+#[async_trait]
+impl TimestreamWriteClient for timestream_write::Client {
+    async fn describe_database(
+        &self,
+        database_name: &str,
+    ) -> Result<Option<String>, ClientError> {
+        let output = self
+            .describe_database()
+            .database_name(database_name)
+            .send()
+            .await
+            .map_err(to_client_error)?;
+        Ok(output
+            .database()
+            .and_then(|database| database.kms_key_id())
+            .map(str::to_string))
+    }
+
+    async fn describe_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<(), ClientError> {
+        self.describe_table()
+            .database_name(database_name)
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(to_client_error)?;
+        Ok(())
+    }
+
+    async fn create_database(
+        &self,
+        database_name: &str,
+        kms_key_id: Option<&str>,
+    ) -> Result<(), ClientError> {
+        let mut create_database_builder = self.create_database().database_name(database_name);
+        if let Some(kms_key_id) = kms_key_id {
+            create_database_builder = create_database_builder.kms_key_id(kms_key_id);
+        }
+        create_database_builder
+            .send()
+            .await
+            .map_err(to_client_error)?;
+        Ok(())
+    }
+
+    async fn create_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        retention_properties: RetentionProperties,
+        magnetic_store_write_properties: MagneticStoreWriteProperties,
+        schema: Option<Schema>,
+    ) -> Result<(), ClientError> {
+        let mut create_table_builder = self
+            .create_table()
+            .database_name(database_name)
+            .table_name(table_name)
+            .retention_properties(retention_properties)
+            .magnetic_store_write_properties(magnetic_store_write_properties);
+        if let Some(schema) = schema {
+            create_table_builder = create_table_builder.schema(schema);
+        }
+        create_table_builder.send().await.map_err(to_client_error)?;
+        Ok(())
+    }
+
+    async fn write_records(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        records: Vec<Record>,
+    ) -> Result<(), ClientError> {
+        self.write_records()
+            .database_name(database_name)
+            .table_name(table_name)
+            .set_records(Some(records))
+            .send()
+            .await
+            .map_err(to_client_error)?;
+        Ok(())
+    }
+
+    async fn delete_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<(), ClientError> {
+        self.delete_table()
+            .database_name(database_name)
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(to_client_error)?;
+        Ok(())
+    }
+
+    async fn delete_database(&self, database_name: &str) -> Result<(), ClientError> {
+        self.delete_database()
+            .database_name(database_name)
+            .send()
+            .await
+            .map_err(to_client_error)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: TimestreamWriteClient> TimestreamWriteClient for Arc<T> {
+    async fn describe_database(
+        &self,
+        database_name: &str,
+    ) -> Result<Option<String>, ClientError> {
+        self.as_ref().describe_database(database_name).await
+    }
+
+    async fn describe_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<(), ClientError> {
+        self.as_ref().describe_table(database_name, table_name).await
+    }
+
+    async fn create_database(
+        &self,
+        database_name: &str,
+        kms_key_id: Option<&str>,
+    ) -> Result<(), ClientError> {
+        self.as_ref().create_database(database_name, kms_key_id).await
+    }
+
+    async fn create_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        retention_properties: RetentionProperties,
+        magnetic_store_write_properties: MagneticStoreWriteProperties,
+        schema: Option<Schema>,
+    ) -> Result<(), ClientError> {
+        self.as_ref()
+            .create_table(
+                database_name,
+                table_name,
+                retention_properties,
+                magnetic_store_write_properties,
+                schema,
+            )
+            .await
+    }
+
+    async fn write_records(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        records: Vec<Record>,
+    ) -> Result<(), ClientError> {
+        self.as_ref()
+            .write_records(database_name, table_name, records)
+            .await
+    }
+
+    async fn delete_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<(), ClientError> {
+        self.as_ref().delete_table(database_name, table_name).await
+    }
+
+    async fn delete_database(&self, database_name: &str) -> Result<(), ClientError> {
+        self.as_ref().delete_database(database_name).await
+    }
+}
+
+/// Table creation settings resolved from the environment.
+#[derive(Debug, Clone)]
+pub struct TableConfig {
+    pub mem_store_retention_period: i64,
+    pub mag_store_retention_period: i64,
+    pub enable_mag_store_writes: bool,
+    pub custom_partition_key_type: Option<String>,
+    pub custom_partition_key_dimension: Option<String>,
+    pub enforce_custom_partition_key: bool,
+}
+
+/// Resolves the table creation settings from the environment. Unset
+/// retention periods fall back to Timestream's defaults so the config
+/// resolves on paths that never create a table; set values must still
+/// parse and fall within the allowed ranges. Startup validation
+/// additionally requires the retention variables to be defined when
+/// table creation is enabled, so a misconfiguration surfaces at deploy
+/// time rather than when the first new measurement appears.
+pub fn get_table_config() -> Result<TableConfig> {
+    let mem_store_retention_period =
+        resolve_mem_store_retention(env::var("mem_store_retention_period").ok().as_deref())?;
+    let mag_store_retention_period =
+        resolve_mag_store_retention(env::var("mag_store_retention_period").ok().as_deref())?;
+    let config = TableConfig {
+        mem_store_retention_period,
+        mag_store_retention_period,
+        enable_mag_store_writes: crate::records_builder::env_var_to_bool(
+            "enable_mag_store_writes",
+        ),
+        custom_partition_key_type: env::var("custom_partition_key_type").ok(),
+        custom_partition_key_dimension: env::var("custom_partition_key_dimension").ok(),
+        enforce_custom_partition_key: crate::records_builder::env_var_to_bool(
+            "enforce_custom_partition_key",
+        ),
+    };
+    // Reject an invalid partition key combination here, at configuration
+    // resolution, rather than when the first new measurement triggers a
+    // create_table.
+    build_partition_key_schema(&config)?;
+    Ok(config)
+}
+
+/// Resolves a memory store retention value: unset falls back to
+/// `DEFAULT_MEM_STORE_RETENTION_HOURS`, set values must parse and pass
+/// validation.
+pub fn resolve_mem_store_retention(value: Option<&str>) -> Result<i64> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_MEM_STORE_RETENTION_HOURS);
+    };
+    let hours = value
+        .parse::<i64>()
+        .context("mem_store_retention_period must be an integer number of hours")?;
+    validate_mem_store_retention(hours)?;
+    Ok(hours)
+}
+
+/// Resolves a magnetic store retention value: unset falls back to
+/// `DEFAULT_MAG_STORE_RETENTION_DAYS`, set values must parse and pass
+/// validation.
+pub fn resolve_mag_store_retention(value: Option<&str>) -> Result<i64> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_MAG_STORE_RETENTION_DAYS);
+    };
+    let days = value
+        .parse::<i64>()
+        .context("mag_store_retention_period must be an integer number of days")?;
+    validate_mag_store_retention(days)?;
+    Ok(days)
+}
+
+/// Checks that a memory store retention period falls within Timestream's
+/// allowed range, so misconfigurations fail with a descriptive error
+/// instead of a runtime `ValidationException`.
+pub fn validate_mem_store_retention(hours: i64) -> Result<()> {
+    if !(1..=MAX_MEM_STORE_RETENTION_HOURS).contains(&hours) {
+        return Err(anyhow!(
+            "mem_store_retention_period must be between 1 and {} hours, got {}",
+            MAX_MEM_STORE_RETENTION_HOURS,
+            hours
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a magnetic store retention period falls within
+/// Timestream's allowed range.
+pub fn validate_mag_store_retention(days: i64) -> Result<()> {
+    if !(1..=MAX_MAG_STORE_RETENTION_DAYS).contains(&days) {
+        return Err(anyhow!(
+            "mag_store_retention_period must be between 1 and {} days, got {}",
+            MAX_MAG_STORE_RETENTION_DAYS,
+            days
+        ));
+    }
+    Ok(())
+}
+
+/// Resolves the target AWS region. The connector's own `region` variable
+/// takes precedence, falling back to the standard `AWS_REGION` (always set
+/// in Lambda) and `AWS_DEFAULT_REGION` variables.
+pub fn resolve_region() -> Result<String> {
+    resolve_region_from(|variable_name| env::var(variable_name).ok())
+}
+
+fn resolve_region_from<F>(lookup: F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for variable_name in ["region", "AWS_REGION", "AWS_DEFAULT_REGION"] {
+        if let Some(region) = lookup(variable_name) {
+            if !region.is_empty() {
+                return Ok(region);
+            }
+        }
+    }
+    Err(anyhow!(
+        "No region configured; set the region environment variable \
+        (or AWS_REGION/AWS_DEFAULT_REGION)"
+    ))
+}
+
+/// Whether the connector is allowed to create its target database.
+pub fn database_creation_enabled() -> Result<bool> {
+    Ok(env::var("enable_database_creation")
+        .context("enable_database_creation environment variable is not defined")?
+        .to_lowercase()
+        == "true")
+}
+
+/// Whether the connector is allowed to create missing tables.
+pub fn table_creation_enabled() -> Result<bool> {
+    env::var("enable_table_creation")
+        .context("enable_table_creation environment variable is not defined")?;
+    Ok(crate::records_builder::env_var_to_bool(
+        "enable_table_creation",
+    ))
+}
+
+/// Creates a Timestream write client with endpoint discovery enabled and
+/// spawns its endpoint reload task. `behavior_version` pins the SDK
+/// behavior major version for deployments that control upgrades; `None`
+/// uses the latest.
+pub async fn get_connection(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> Result<timestream_write::Client> {
+    let (client, _health) = get_connection_with_health(region, behavior_version).await?;
+    Ok(client)
+}
+
+/// Like `get_connection`, but also returns a health flag that is cleared
+/// if the spawned endpoint discovery reload task ever exits. A cleared
+/// flag means the client's cached endpoints will go stale, so holders
+/// (see `ManagedClient`) should rebuild the client rather than keep
+/// using it.
+pub async fn get_connection_with_health(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> Result<(timestream_write::Client, Arc<AtomicBool>)> {
+    let config = aws_config::defaults(behavior_version.unwrap_or_else(BehaviorVersion::latest))
+        .region(Region::new(region.to_owned()))
+        .load()
+        .await;
+    let (client, reload) = timestream_write::Client::new(&config)
+        .with_endpoint_discovery_enabled()
+        .await
+        .map_err(|error| anyhow!("Failed to enable endpoint discovery: {}", error))?;
+    let healthy = Arc::new(AtomicBool::new(true));
+    let reload_healthy = Arc::clone(&healthy);
+    tokio::spawn(async move {
+        reload.reload_task().await;
+        tracing::error!(
+            "Endpoint discovery reload task exited; the client will be \
+            rebuilt on the next invocation"
+        );
+        reload_healthy.store(false, Ordering::SeqCst);
+    });
+    Ok((client, healthy))
+}
+
+/// Process-lifetime holder for the shared write client. The client is
+/// built lazily on first use, with transient initialization failures
+/// retried, and rebuilt when its health flag has been cleared (e.g.
+/// because the endpoint discovery reload task died). Concurrent callers
+/// racing past an empty or unhealthy slot may build twice; the last
+/// build wins, which is harmless since every built client is usable.
+pub struct ManagedClient<C> {
+    slot: Mutex<Option<(Arc<C>, Arc<AtomicBool>)>>,
+}
+
+impl<C> ManagedClient<C> {
+    pub const fn new() -> Self {
+        ManagedClient {
+            slot: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached client if it is still healthy, otherwise builds
+    /// a new one with `build`, retrying failures with a linear backoff.
+    pub async fn get_or_build<F, Fut>(&self, build: F) -> Result<Arc<C>>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<(C, Arc<AtomicBool>)>>,
+    {
+        if let Some((client, healthy)) = self.slot.lock().unwrap().as_ref() {
+            if healthy.load(Ordering::SeqCst) {
+                return Ok(Arc::clone(client));
+            }
+        }
+        let mut attempts: u32 = 0;
+        loop {
+            match build().await {
+                Ok((client, healthy)) => {
+                    let client = Arc::new(client);
+                    *self.slot.lock().unwrap() = Some((Arc::clone(&client), healthy));
+                    return Ok(client);
+                }
+                Err(error) => {
+                    attempts += 1;
+                    if attempts >= CLIENT_INIT_MAX_ATTEMPTS {
+                        return Err(error.context("Failed to initialize the Timestream client"));
+                    }
+                    tracing::warn!(
+                        "Client initialization failed, retrying (attempt {}): {:#}",
+                        attempts,
+                        error
+                    );
+                    tokio::time::sleep(Duration::from_millis(
+                        CLIENT_INIT_RETRY_BACKOFF_MS * u64::from(attempts),
+                    ))
+                    .await;
+                }
+            }
+        }
+    }
+}
+
+impl<C> Default for ManagedClient<C> {
+    fn default() -> Self {
+        ManagedClient::new()
+    }
+}
+
+/// Process-wide slot behind `get_or_init_connection`, so library users
+/// calling it repeatedly share one client and one endpoint discovery
+/// reload task instead of spawning a fresh task per call.
+static SHARED_CLIENT: OnceLock<ManagedClient<timestream_write::Client>> = OnceLock::new();
+
+/// Returns the process-wide shared write client, building it (and
+/// spawning its single endpoint discovery reload task) on the first
+/// call. Later calls return the same client without spawning anything;
+/// a client whose reload task has died is rebuilt, per `ManagedClient`.
+pub async fn get_or_init_connection(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> Result<Arc<timestream_write::Client>> {
+    SHARED_CLIENT
+        .get_or_init(ManagedClient::new)
+        .get_or_build(|| get_connection_with_health(region, behavior_version))
+        .await
+}
+
+/// Returns whether the named database exists.
+pub async fn database_exists(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+) -> Result<bool> {
+    match client.describe_database(database_name).await {
+        Ok(existing_key) => {
+            if let (Ok(configured_key), Some(existing_key)) =
+                (env::var("kms_key_id"), existing_key)
+            {
+                if !existing_key.contains(&configured_key) {
+                    tracing::warn!(
+                        "Database {} already exists with KMS key {}, which does not \
+                        match the configured kms_key_id {}",
+                        database_name,
+                        existing_key,
+                        configured_key
+                    );
+                }
+            }
+            Ok(true)
+        }
+        Err(error) => {
+            if error.is_resource_not_found() {
+                Ok(false)
+            } else {
+                Err(anyhow!(error)
+                    .context(format!("Failed to describe database {}", database_name)))
+            }
+        }
+    }
+}
+
+/// Returns whether the named table exists in the database.
+pub async fn table_exists(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+    table_name: &str,
+) -> Result<bool> {
+    let mut subsegment = crate::xray::Subsegment::begin("table_exists");
+    subsegment.annotate("table_name", serde_json::json!(table_name));
+    let result = match client.describe_table(database_name, table_name).await {
+        Ok(()) => Ok(true),
+        Err(error) => {
+            if error.is_resource_not_found() {
+                Ok(false)
+            } else {
+                Err(anyhow!(error)
+                    .context(format!("Failed to describe table {}", table_name)))
+            }
+        }
+    };
+    if result.is_err() {
+        subsegment.set_error();
+    }
+    subsegment.end();
+    result
+}
+
+/// Process-lifetime cache of databases (`name`) and tables
+/// (`database/table`) confirmed to exist, letting warm Lambda
+/// invocations skip repeated describe calls.
+static EXISTENCE_CACHE: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn existence_cache() -> &'static Mutex<HashSet<String>> {
+    EXISTENCE_CACHE.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn table_cache_key(database_name: &str, table_name: &str) -> String {
+    format!("{}/{}", database_name, table_name)
+}
+
+/// Clears the existence cache, forcing the next checks to describe
+/// again. Call after databases or tables may have been deleted out of
+/// band.
+pub fn invalidate_existence_cache() {
+    existence_cache().lock().unwrap().clear();
+}
+
+/// `database_exists` with a positive process-lifetime cache: once a
+/// database has been confirmed it is not described again.
+pub async fn database_exists_cached(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+) -> Result<bool> {
+    if existence_cache().lock().unwrap().contains(database_name) {
+        return Ok(true);
+    }
+    let exists = database_exists(client, database_name).await?;
+    if exists {
+        existence_cache()
+            .lock()
+            .unwrap()
+            .insert(database_name.to_string());
+    }
+    Ok(exists)
+}
+
+/// Maximum number of concurrent `describe_table` calls the pre-flight
+/// existence check keeps in flight. DescribeTable is far less
+/// rate-limited than the create and write paths, so this sits above
+/// `NUM_BATCH_THREADS` deliberately.
+pub const PREFLIGHT_CONCURRENCY: usize = 32;
+
+/// Checks the existence of every named table in one database
+/// concurrently, bounded by its own semaphore rather than the adaptive
+/// write limit, and returns a name-to-exists map. Confirmed tables land
+/// in the process-lifetime existence cache like any other check. A
+/// describe failure other than not-found fails the whole pre-flight.
+pub async fn preflight_table_check<C: TimestreamWriteClient + 'static>(
+    client: &Arc<C>,
+    database_name: &str,
+    table_names: &[String],
+) -> Result<HashMap<String, bool>> {
+    let preflight_semaphore = Arc::new(Semaphore::new(PREFLIGHT_CONCURRENCY));
+    let mut tasks = Vec::with_capacity(table_names.len());
+    for table_name in table_names {
+        let client = Arc::clone(client);
+        let database_name = database_name.to_string();
+        let table_name = table_name.clone();
+        let permit = Arc::clone(&preflight_semaphore).acquire_owned().await?;
+        tasks.push(tokio::spawn(async move {
+            let _permit = permit;
+            let exists = table_exists_cached(&client, &database_name, &table_name).await?;
+            Ok::<_, anyhow::Error>((table_name, exists))
+        }));
+    }
+    let mut exists_by_table = HashMap::with_capacity(tasks.len());
+    for task in tasks {
+        let (table_name, exists) = task.await??;
+        exists_by_table.insert(table_name, exists);
+    }
+    Ok(exists_by_table)
+}
+
+/// `table_exists` with a positive process-lifetime cache: once a table
+/// has been confirmed it is not described again.
+pub async fn table_exists_cached(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+    table_name: &str,
+) -> Result<bool> {
+    let cache_key = table_cache_key(database_name, table_name);
+    if existence_cache().lock().unwrap().contains(&cache_key) {
+        return Ok(true);
+    }
+    let exists = table_exists(client, database_name, table_name).await?;
+    if exists {
+        existence_cache().lock().unwrap().insert(cache_key);
+    }
+    Ok(exists)
+}
+
+/// Creates a new Timestream database. Callers gate creation on the
+/// resolved connector config (`enable_database_creation`); this function
+/// does not re-read the flag, so the gating decision is made exactly
+/// once.
+pub async fn create_database(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+) -> Result<()> {
+    tracing::info!("Creating database {}", database_name);
+    let kms_key_id = env::var("kms_key_id").ok();
+    client
+        .create_database(database_name, kms_key_id.as_deref())
+        .await
+        .map_err(|error| {
+            if error.is_access_denied() && kms_key_id.is_some() {
+                anyhow!(error).context(format!(
+                    "Access denied creating database {}; verify the key policy of the \
+                    configured kms_key_id grants Timestream access",
+                    database_name
+                ))
+            } else {
+                anyhow!(error).context(format!("Failed to create database {}", database_name))
+            }
+        })?;
+    Ok(())
+}
+
+/// Creates a new Timestream table using the provided table configuration.
+/// Callers gate creation on the resolved connector config
+/// (`enable_table_creation`), mirroring `create_database`.
+pub async fn create_table(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+    table_name: &str,
+    table_config: TableConfig,
+) -> Result<()> {
+    tracing::info!("Creating table {} in database {}", table_name, database_name);
+    let mut subsegment = crate::xray::Subsegment::begin("create_table");
+    subsegment.annotate("table_name", serde_json::json!(table_name));
+
+    let retention_properties = RetentionProperties::builder()
+        .memory_store_retention_period_in_hours(table_config.mem_store_retention_period)
+        .magnetic_store_retention_period_in_days(table_config.mag_store_retention_period)
+        .build()?;
+    let magnetic_store_write_properties = MagneticStoreWriteProperties::builder()
+        .enable_magnetic_store_writes(table_config.enable_mag_store_writes)
+        .build()?;
+    let schema = build_partition_key_schema(&table_config)?;
+
+    let result = client
+        .create_table(
+            database_name,
+            table_name,
+            retention_properties,
+            magnetic_store_write_properties,
+            schema,
+        )
+        .await
+        .map_err(|error| {
+            subsegment.set_error();
+            anyhow::Error::new(ConnectorError::TableCreation {
+                table: table_name.to_string(),
+                source: error,
+            })
+        });
+    let result = result.map(|_| ());
+    subsegment.end();
+    result
+}
+
+/// Builds the composite partition key schema from the table configuration,
+/// if a custom partition key is configured.
+fn build_partition_key_schema(table_config: &TableConfig) -> Result<Option<Schema>> {
+    let Some(partition_key_type) = &table_config.custom_partition_key_type else {
+        return Ok(None);
+    };
+    let partition_key = match partition_key_type.as_str() {
+        "dimension" => {
+            let dimension_name = table_config
+                .custom_partition_key_dimension
+                .as_ref()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "custom_partition_key_dimension must be set when \
+                        custom_partition_key_type is dimension"
+                    )
+                })?;
+            PartitionKey::builder()
+                .r#type(PartitionKeyType::Dimension)
+                .name(dimension_name)
+                .enforcement_in_record(if table_config.enforce_custom_partition_key {
+                    PartitionKeyEnforcementLevel::Required
+                } else {
+                    PartitionKeyEnforcementLevel::Optional
+                })
+                .build()?
+        }
+        "measure" => PartitionKey::builder()
+            .r#type(PartitionKeyType::Measure)
+            .build()?,
+        _ => {
+            return Err(anyhow!(
+                "custom_partition_key_type must be \"dimension\" or \"measure\", got {}",
+                partition_key_type
+            ))
+        }
+    };
+    Ok(Some(
+        Schema::builder().composite_partition_key(partition_key).build(),
+    ))
+}
+
+/// Deletes a table. An already-absent table is treated as success, so
+/// cleanup paths can call this unconditionally.
+pub async fn delete_table(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+    table_name: &str,
+) -> Result<()> {
+    match client.delete_table(database_name, table_name).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.is_resource_not_found() => Ok(()),
+        Err(error) => Err(anyhow!(error)
+            .context(format!("Failed to delete table {}", table_name))),
+    }
+}
+
+/// Deletes a database. An already-absent database is treated as success,
+/// so cleanup paths can call this unconditionally.
+pub async fn delete_database(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+) -> Result<()> {
+    match client.delete_database(database_name).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.is_resource_not_found() => Ok(()),
+        Err(error) => Err(anyhow!(error)
+            .context(format!("Failed to delete database {}", database_name))),
+    }
+}
+
+/// Deletes a set of tables in one database once a test or sample
+/// finishes, using the delete helpers so already-absent resources count
+/// as cleaned up. A batch that owns its database — it was created for
+/// this run rather than borrowed — can register it for deletion too with
+/// [`CleanupBatch::including_database`].
+pub struct CleanupBatch<C: TimestreamWriteClient> {
+    client: C,
+    database_name: String,
+    table_names: Vec<String>,
+    delete_database: bool,
+}
+
+impl<C: TimestreamWriteClient> CleanupBatch<C> {
+    pub fn new(client: C, database_name: &str, table_names: Vec<String>) -> Self {
+        CleanupBatch {
+            client,
+            database_name: database_name.to_string(),
+            table_names,
+            delete_database: false,
+        }
+    }
+
+    /// Marks the database itself for deletion after its tables.
+    pub fn including_database(mut self) -> Self {
+        self.delete_database = true;
+        self
+    }
+
+    /// Deletes the registered tables, then the database when it is
+    /// marked, returning the names of any that failed deletion. The
+    /// database is skipped when a table failed: Timestream refuses to
+    /// delete a non-empty database, so the attempt could only add noise.
+    pub async fn cleanup(&mut self) -> Vec<String> {
+        let mut failed = Vec::new();
+        for table_name in &self.table_names {
+            if let Err(error) =
+                delete_table(&self.client, &self.database_name, table_name).await
+            {
+                tracing::warn!("Failed to delete table {}: {:?}", table_name, error);
+                failed.push(table_name.clone());
+            }
+        }
+        if self.delete_database && failed.is_empty() {
+            if let Err(error) = delete_database(&self.client, &self.database_name).await {
+                tracing::warn!(
+                    "Failed to delete database {}: {:?}",
+                    self.database_name,
+                    error
+                );
+                failed.push(self.database_name.clone());
+            }
+        }
+        failed
+    }
+}
+
+/// Approximates a record's wire size: the summed lengths of every
+/// dimension and measure name and value. Used both for the
+/// self-monitoring `batch_bytes` measure and to keep write batches under
+/// the per-request byte limit.
+pub fn approximate_record_bytes(record: &Record) -> usize {
+    let dimension_bytes: usize = record
+        .dimensions()
+        .iter()
+        .map(|dimension| dimension.name().len() + dimension.value().len())
+        .sum();
+    let measure_bytes: usize = record
+        .measure_values()
+        .iter()
+        .map(|measure| measure.name().len() + measure.value().len())
+        .sum();
+    dimension_bytes + measure_bytes
+}
+
+/// Splits records into write batches that respect both the 100-record
+/// and the per-request byte limits. A single record above the byte
+/// budget still goes out alone, letting Timestream report the rejection
+/// rather than looping forever.
+pub fn split_into_batches(records: &[Record]) -> Vec<&[Record]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut batch_bytes = 0;
+    for (index, record) in records.iter().enumerate() {
+        let record_bytes = approximate_record_bytes(record);
+        let batch_len = index - start;
+        if batch_len > 0
+            && (batch_len >= MAX_TIMESTREAM_BATCH_SIZE
+                || batch_bytes + record_bytes > MAX_TIMESTREAM_BATCH_BYTES)
+        {
+            batches.push(&records[start..index]);
+            start = index;
+            batch_bytes = 0;
+        }
+        batch_bytes += record_bytes;
+    }
+    if start < records.len() {
+        batches.push(&records[start..]);
+    }
+    batches
+}
+
+/// Writes records to a table in batches that stay under both the
+/// `MAX_TIMESTREAM_BATCH_SIZE` record count and the
+/// `MAX_TIMESTREAM_BATCH_BYTES` payload budget.
+#[tracing::instrument(level = "trace", skip(client, records), fields(records = records.len()))]
+pub async fn ingest_records(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+    table_name: &str,
+    records: &[Record],
+) -> Result<(), ConnectorError> {
+    for batch in split_into_batches(records) {
+        let mut subsegment = crate::xray::Subsegment::begin("ingest_record_batch");
+        subsegment.annotate("table_name", serde_json::json!(table_name));
+        subsegment.annotate("record_count", serde_json::json!(batch.len()));
+        let result = ingest_record_batch(client, database_name, table_name, batch).await;
+        if result.is_err() {
+            subsegment.set_error();
+        }
+        subsegment.end();
+        result?;
+    }
+    Ok(())
+}
+
+/// Writes a single record batch, retrying throttling errors with a linear
+/// backoff. Once the circuit breaker trips, no further writes are issued
+/// for the rest of the invocation.
+async fn ingest_record_batch(
+    client: &impl TimestreamWriteClient,
+    database_name: &str,
+    table_name: &str,
+    batch: &[Record],
+) -> Result<(), ConnectorError> {
+    let mut attempts: u32 = 0;
+    loop {
+        if circuit_breaker()
+            .is_open()
+            .map_err(ConnectorError::configuration)?
+        {
+            return Err(ConnectorError::Ingestion {
+                table: table_name.to_string(),
+                source: ClientError::Throttling(format!(
+                    "the circuit breaker is open after {} consecutive throttling failures",
+                    circuit_breaker().consecutive_throttles()
+                )),
+            });
+        }
+        match client
+            .write_records(database_name, table_name, batch.to_vec())
+            .await
+        {
+            Ok(()) => {
+                adaptive_concurrency().on_success();
+                circuit_breaker().record_success();
+                return Ok(());
+            }
+            Err(error) => {
+                if error.is_throttling() {
+                    adaptive_concurrency().on_throttle();
+                    circuit_breaker().record_throttle();
+                }
+                attempts += 1;
+                if !error.is_throttling() || attempts >= MAX_WRITE_RETRIES {
+                    return Err(ConnectorError::Ingestion {
+                        table: table_name.to_string(),
+                        source: error,
+                    });
+                }
+                tracing::warn!(
+                    "Throttled writing to table {}, retrying (attempt {})",
+                    table_name,
+                    attempts
+                );
+                tokio::time::sleep(Duration::from_millis(
+                    WRITE_RETRY_BACKOFF_MS * u64::from(attempts),
+                ))
+                .await;
+            }
+        }
+    }
+}
+
+/// AIMD (additive-increase/multiplicative-decrease) controller for the
+/// per-table ingestion concurrency. Throttling errors halve the limit so
+/// a sustained storm slows the connector down instead of failing
+/// invocations; a window of consecutive successes raises it back one step
+/// at a time, up to the configured maximum.
+pub struct AdaptiveConcurrency {
+    limit: AtomicUsize,
+    success_streak: AtomicUsize,
+    max_limit: usize,
+}
+
+impl AdaptiveConcurrency {
+    pub const fn new(max_limit: usize) -> Self {
+        AdaptiveConcurrency {
+            limit: AtomicUsize::new(max_limit),
+            success_streak: AtomicUsize::new(0),
+            max_limit,
+        }
+    }
+
+    /// The current effective concurrency limit, always between 1 and the
+    /// configured maximum.
+    pub fn current_limit(&self) -> usize {
+        self.limit.load(Ordering::SeqCst)
+    }
+
+    /// Records a successful batch write; after `AIMD_SUCCESS_WINDOW`
+    /// consecutive successes the limit is raised by one.
+    pub fn on_success(&self) {
+        if self.success_streak.fetch_add(1, Ordering::SeqCst) + 1 < AIMD_SUCCESS_WINDOW {
+            return;
+        }
+        self.success_streak.store(0, Ordering::SeqCst);
+        let mut current = self.limit.load(Ordering::SeqCst);
+        while current < self.max_limit {
+            match self.limit.compare_exchange(
+                current,
+                current + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => {
+                    tracing::debug!("Raising ingest concurrency limit to {}", current + 1);
+                    return;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Records an observed throttling error: the limit is halved (to a
+    /// floor of 1) and the success streak starts over.
+    pub fn on_throttle(&self) {
+        self.success_streak.store(0, Ordering::SeqCst);
+        let mut current = self.limit.load(Ordering::SeqCst);
+        loop {
+            let reduced = (current / 2).max(1);
+            if reduced == current {
+                return;
+            }
+            match self
+                .limit
+                .compare_exchange(current, reduced, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => {
+                    tracing::warn!(
+                        "Timestream throttling observed; reducing ingest concurrency \
+                        limit from {} to {}",
+                        current,
+                        reduced
+                    );
+                    return;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Restores full concurrency, forgetting observed throttling.
+    pub fn reset(&self) {
+        self.limit.store(self.max_limit, Ordering::SeqCst);
+        self.success_streak.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Process-wide controller instance; keeping it static lets the reduced
+/// limit survive across warm Lambda invocations.
+static ADAPTIVE_CONCURRENCY: AdaptiveConcurrency =
+    AdaptiveConcurrency::new(crate::NUM_BATCH_THREADS);
+
+/// The process-wide adaptive concurrency controller driven by
+/// `ingest_record_batch` and consulted when sizing the ingest semaphore.
+pub fn adaptive_concurrency() -> &'static AdaptiveConcurrency {
+    &ADAPTIVE_CONCURRENCY
+}
+
+/// Circuit breaker for persistent throttling: once the number of
+/// consecutive throttling failures reaches the configured
+/// `circuit_breaker_threshold`, further writes fail immediately with a
+/// `Throttled` error so the event routes to the DLQ for later replay
+/// instead of extending the invocation with doomed retries.
+pub struct CircuitBreaker {
+    consecutive_throttles: AtomicUsize,
+}
+
+impl CircuitBreaker {
+    pub const fn new() -> Self {
+        CircuitBreaker {
+            consecutive_throttles: AtomicUsize::new(0),
+        }
+    }
+
+    /// Whether the breaker has tripped. Always `false` when no threshold
+    /// is configured.
+    pub fn is_open(&self) -> Result<bool> {
+        Ok(match circuit_breaker_threshold()? {
+            Some(threshold) => self.consecutive_throttles() >= threshold,
+            None => false,
+        })
+    }
+
+    /// The current consecutive throttling failure count.
+    pub fn consecutive_throttles(&self) -> usize {
+        self.consecutive_throttles.load(Ordering::SeqCst)
+    }
+
+    /// Records an observed throttling error.
+    pub fn record_throttle(&self) {
+        self.consecutive_throttles.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records a successful write, closing the breaker.
+    pub fn record_success(&self) {
+        self.consecutive_throttles.store(0, Ordering::SeqCst);
+    }
+
+    /// Closes the breaker, forgetting observed throttling. Called at the
+    /// start of each invocation so the counter tracks consecutive
+    /// failures within one event.
+    pub fn reset(&self) {
+        self.consecutive_throttles.store(0, Ordering::SeqCst);
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new()
+    }
+}
+
+/// Parses the optional `circuit_breaker_threshold` environment variable.
+/// Unset disables the breaker; the value must be a positive integer.
+pub fn circuit_breaker_threshold() -> Result<Option<usize>> {
+    let Ok(threshold) = env::var("circuit_breaker_threshold") else {
+        return Ok(None);
+    };
+    let threshold = threshold.parse::<usize>().map_err(|_| {
+        anyhow!(
+            "circuit_breaker_threshold must be a positive integer, got {}",
+            threshold
+        )
+    })?;
+    if threshold == 0 {
+        return Err(anyhow!("circuit_breaker_threshold must be at least 1"));
+    }
+    Ok(Some(threshold))
+}
+
+static CIRCUIT_BREAKER: CircuitBreaker = CircuitBreaker::new();
+
+/// The process-wide circuit breaker driven by `ingest_record_batch`.
+pub fn circuit_breaker() -> &'static CircuitBreaker {
+    &CIRCUIT_BREAKER
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// A scriptable `TimestreamWriteClient` that records every call. Each
+    /// per-method queue is popped front-first; an empty queue yields `Ok`
+    /// with the method's default success value.
+    #[derive(Default)]
+    pub struct MockTimestreamClient {
+        pub calls: Mutex<Vec<String>>,
+        pub describe_database_results: Mutex<VecDeque<Result<Option<String>, ClientError>>>,
+        pub describe_table_results: Mutex<VecDeque<Result<(), ClientError>>>,
+        pub create_database_results: Mutex<VecDeque<Result<(), ClientError>>>,
+        pub create_table_results: Mutex<VecDeque<Result<(), ClientError>>>,
+        pub write_records_results: Mutex<VecDeque<Result<(), ClientError>>>,
+        pub delete_table_results: Mutex<VecDeque<Result<(), ClientError>>>,
+        pub delete_database_results: Mutex<VecDeque<Result<(), ClientError>>>,
+        /// When set, `write_records` sleeps for this duration so tests can
+        /// observe concurrency.
+        pub write_delay: Mutex<Option<Duration>>,
+        active_writes: AtomicUsize,
+        /// High-water mark of concurrently active `write_records` calls.
+        pub max_concurrent_writes: AtomicUsize,
+    }
+
+    impl MockTimestreamClient {
+        pub fn new() -> Self {
+            MockTimestreamClient::default()
+        }
+
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn record_call(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    #[async_trait]
+    impl TimestreamWriteClient for MockTimestreamClient {
+        async fn describe_database(
+            &self,
+            database_name: &str,
+        ) -> Result<Option<String>, ClientError> {
+            self.record_call(format!("describe_database {}", database_name));
+            self.describe_database_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(None))
+        }
+
+        async fn describe_table(
+            &self,
+            database_name: &str,
+            table_name: &str,
+        ) -> Result<(), ClientError> {
+            self.record_call(format!("describe_table {} {}", database_name, table_name));
+            self.describe_table_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()))
+        }
+
+        async fn create_database(
+            &self,
+            database_name: &str,
+            kms_key_id: Option<&str>,
+        ) -> Result<(), ClientError> {
+            self.record_call(format!(
+                "create_database {} {:?}",
+                database_name, kms_key_id
+            ));
+            self.create_database_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()))
+        }
+
+        async fn create_table(
+            &self,
+            database_name: &str,
+            table_name: &str,
+            _retention_properties: RetentionProperties,
+            _magnetic_store_write_properties: MagneticStoreWriteProperties,
+            schema: Option<Schema>,
+        ) -> Result<(), ClientError> {
+            self.record_call(format!(
+                "create_table {} {} schema={}",
+                database_name,
+                table_name,
+                schema.is_some()
+            ));
+            self.create_table_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()))
+        }
+
+        async fn write_records(
+            &self,
+            database_name: &str,
+            table_name: &str,
+            records: Vec<Record>,
+        ) -> Result<(), ClientError> {
+            self.record_call(format!(
+                "write_records {} {} {}",
+                database_name,
+                table_name,
+                records.len()
+            ));
+            let active = self.active_writes.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_concurrent_writes.fetch_max(active, Ordering::SeqCst);
+            let write_delay = *self.write_delay.lock().unwrap();
+            if let Some(write_delay) = write_delay {
+                tokio::time::sleep(write_delay).await;
+            }
+            self.active_writes.fetch_sub(1, Ordering::SeqCst);
+            self.write_records_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()))
+        }
+
+        async fn delete_table(
+            &self,
+            database_name: &str,
+            table_name: &str,
+        ) -> Result<(), ClientError> {
+            self.record_call(format!("delete_table {} {}", database_name, table_name));
+            self.delete_table_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()))
+        }
+
+        async fn delete_database(&self, database_name: &str) -> Result<(), ClientError> {
+            self.record_call(format!("delete_database {}", database_name));
+            self.delete_database_results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Ok(()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockTimestreamClient;
+    use super::*;
+    use aws_sdk_timestreamwrite::types::Dimension;
+    use std::collections::HashMap;
+
+    fn lookup_in<'a>(
+        vars: &'a HashMap<&'a str, &'a str>,
+    ) -> impl Fn(&str) -> Option<String> + 'a {
+        |variable_name| vars.get(variable_name).map(|value| value.to_string())
+    }
+
+    fn test_record() -> Record {
+        Record::builder().measure_name("test").build()
+    }
+
+    fn throttling() -> ClientError {
+        ClientError::Throttling("Rate exceeded".to_string())
+    }
+
+    #[test]
+    fn test_resolve_region_prefers_region_variable() {
+        let vars = HashMap::from([("region", "us-west-2"), ("AWS_REGION", "us-east-1")]);
+        assert_eq!(resolve_region_from(lookup_in(&vars)).unwrap(), "us-west-2");
+    }
+
+    #[test]
+    fn test_resolve_region_falls_back_to_aws_region() {
+        let vars = HashMap::from([
+            ("AWS_REGION", "us-east-1"),
+            ("AWS_DEFAULT_REGION", "eu-west-1"),
+        ]);
+        assert_eq!(resolve_region_from(lookup_in(&vars)).unwrap(), "us-east-1");
+    }
+
+    #[test]
+    fn test_resolve_region_falls_back_to_aws_default_region() {
+        let vars = HashMap::from([("AWS_DEFAULT_REGION", "eu-west-1")]);
+        assert_eq!(resolve_region_from(lookup_in(&vars)).unwrap(), "eu-west-1");
+    }
+
+    #[test]
+    fn test_resolve_region_ignores_empty_values() {
+        let vars = HashMap::from([("region", ""), ("AWS_REGION", "us-east-1")]);
+        assert_eq!(resolve_region_from(lookup_in(&vars)).unwrap(), "us-east-1");
+    }
+
+    #[test]
+    fn test_resolve_region_errors_when_unset() {
+        let vars = HashMap::new();
+        assert!(resolve_region_from(lookup_in(&vars)).is_err());
+    }
+
+    #[test]
+    fn test_retention_period_boundaries() {
+        assert!(validate_mem_store_retention(1).is_ok());
+        assert!(validate_mem_store_retention(MAX_MEM_STORE_RETENTION_HOURS).is_ok());
+        assert!(validate_mag_store_retention(1).is_ok());
+        assert!(validate_mag_store_retention(MAX_MAG_STORE_RETENTION_DAYS).is_ok());
+    }
+
+    #[test]
+    fn test_retention_resolution_defaults_when_unset() {
+        assert_eq!(
+            resolve_mem_store_retention(None).unwrap(),
+            DEFAULT_MEM_STORE_RETENTION_HOURS
+        );
+        assert_eq!(
+            resolve_mag_store_retention(None).unwrap(),
+            DEFAULT_MAG_STORE_RETENTION_DAYS
+        );
+        assert_eq!(resolve_mem_store_retention(Some("24")).unwrap(), 24);
+        assert_eq!(resolve_mag_store_retention(Some("7")).unwrap(), 7);
+        assert!(resolve_mem_store_retention(Some("a day")).is_err());
+        assert!(resolve_mag_store_retention(Some("0")).is_err());
+    }
+
+    #[test]
+    fn test_retention_period_out_of_range() {
+        for hours in [0, -1, MAX_MEM_STORE_RETENTION_HOURS + 1] {
+            let error = validate_mem_store_retention(hours)
+                .expect_err("Out-of-range memory retention must be rejected");
+            assert!(error.to_string().contains("between 1 and 8766 hours"));
+        }
+        for days in [0, -1, MAX_MAG_STORE_RETENTION_DAYS + 1] {
+            let error = validate_mag_store_retention(days)
+                .expect_err("Out-of-range magnetic retention must be rejected");
+            assert!(error.to_string().contains("between 1 and 73000 days"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_exists_classifies_results() {
+        let client = MockTimestreamClient::new();
+        client.describe_table_results.lock().unwrap().extend([
+            Ok(()),
+            Err(ClientError::ResourceNotFound("no such table".to_string())),
+            Err(ClientError::Other(anyhow!("connection reset"))),
+        ]);
+
+        assert!(table_exists(&client, "db", "readings").await.unwrap());
+        assert!(!table_exists(&client, "db", "readings").await.unwrap());
+        assert!(table_exists(&client, "db", "readings").await.is_err());
+        assert_eq!(client.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_database_exists_classifies_results() {
+        let client = MockTimestreamClient::new();
+        client.describe_database_results.lock().unwrap().extend([
+            Ok(None),
+            Err(ClientError::ResourceNotFound("no such database".to_string())),
+        ]);
+
+        assert!(database_exists(&client, "db").await.unwrap());
+        assert!(!database_exists(&client, "db").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_batches_writes() {
+        // Successful writes feed the process-wide breaker and limiter;
+        // hold the environment lock so the circuit breaker tests are
+        // undisturbed.
+        let _env = crate::test_support::EnvVarGuard::acquire();
+        let client = MockTimestreamClient::new();
+        let records = vec![test_record(); 250];
+
+        ingest_records(&client, "db", "readings", &records)
+            .await
+            .expect("Failed to ingest records");
+        assert_eq!(
+            client.calls(),
+            vec![
+                "write_records db readings 100",
+                "write_records db readings 100",
+                "write_records db readings 50",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_splits_oversized_batches_by_bytes() {
+        let _env = crate::test_support::EnvVarGuard::acquire();
+        let client = MockTimestreamClient::new();
+        // ~100 KB per record: well under the count limit at 50 records,
+        // but ~5 MB in total, so the byte budget must drive the split.
+        let oversized_record = Record::builder()
+            .measure_name("test")
+            .dimensions(
+                Dimension::builder()
+                    .name("payload")
+                    .value("x".repeat(100 * 1024))
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+        let records = vec![oversized_record; 50];
+
+        ingest_records(&client, "db", "readings", &records)
+            .await
+            .expect("Failed to ingest oversized records");
+        let calls = client.calls();
+        assert!(calls.len() > 1, "Expected a byte-driven split: {:?}", calls);
+        // Every batch stays under the byte budget (8 records of ~100 KB
+        // fit in 900 KB; a ninth would not).
+        assert_eq!(calls.len(), 7);
+        for call in &calls[..6] {
+            assert_eq!(call, "write_records db readings 8");
+        }
+        assert_eq!(calls[6], "write_records db readings 2");
+    }
+
+    #[test]
+    fn test_split_into_batches_respects_both_limits() {
+        // Small records split by count alone.
+        let small_records = vec![test_record(); 250];
+        let batches = split_into_batches(&small_records);
+        assert_eq!(
+            batches.iter().map(|batch| batch.len()).collect::<Vec<_>>(),
+            vec![100, 100, 50]
+        );
+
+        // A single record above the byte budget still ships alone.
+        let huge_record = Record::builder()
+            .measure_name("test")
+            .dimensions(
+                Dimension::builder()
+                    .name("payload")
+                    .value("x".repeat(MAX_TIMESTREAM_BATCH_BYTES + 1))
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+        let batches = split_into_batches(std::slice::from_ref(&huge_record));
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_retries_throttling() {
+        // Holding the environment lock keeps the circuit breaker tests,
+        // which configure a threshold and share the process-wide breaker
+        // state, from interleaving with the simulated throttling here.
+        let _env = crate::test_support::EnvVarGuard::acquire();
+        let client = MockTimestreamClient::new();
+        client
+            .write_records_results
+            .lock()
+            .unwrap()
+            .extend([Err(throttling()), Err(throttling()), Ok(())]);
+
+        ingest_records(&client, "db", "readings", &[test_record()])
+            .await
+            .expect("Throttled writes must be retried");
+        assert_eq!(client.calls().len(), 3);
+        // The simulated throttling shrank the process-wide concurrency
+        // limit; restore it for the other tests.
+        adaptive_concurrency().reset();
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_gives_up_after_max_retries() {
+        let _env = crate::test_support::EnvVarGuard::acquire();
+        let client = MockTimestreamClient::new();
+        client
+            .write_records_results
+            .lock()
+            .unwrap()
+            .extend([Err(throttling()), Err(throttling()), Err(throttling())]);
+
+        assert!(ingest_records(&client, "db", "readings", &[test_record()])
+            .await
+            .is_err());
+        assert_eq!(client.calls().len(), MAX_WRITE_RETRIES as usize);
+        adaptive_concurrency().reset();
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_trips_after_threshold() {
+        let client = MockTimestreamClient::new();
+        client
+            .write_records_results
+            .lock()
+            .unwrap()
+            .extend([Err(throttling()), Err(throttling()), Err(throttling())]);
+
+        // Reset only after taking the lock: the retry tests above count
+        // their simulated throttles against the same process-wide breaker.
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        circuit_breaker().reset();
+        env_vars.set("circuit_breaker_threshold", "2");
+        let error = ingest_records(&client, "db", "readings", &[test_record()])
+            .await
+            .expect_err("Persistent throttling must trip the breaker");
+        // The third retry is never issued: the breaker opens after two
+        // consecutive throttling failures.
+        assert_eq!(client.calls().len(), 2);
+        assert!(error.to_string().contains("circuit breaker"));
+        assert!(
+            matches!(
+                &error,
+                ConnectorError::Ingestion { table, source }
+                    if table == "readings" && source.is_throttling()
+            ),
+            "Expected a throttling Ingestion error, got: {}",
+            error
+        );
+        circuit_breaker().reset();
+        adaptive_concurrency().reset();
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_closes_on_success() {
+        let client = MockTimestreamClient::new();
+        client
+            .write_records_results
+            .lock()
+            .unwrap()
+            .extend([Err(throttling()), Ok(())]);
+
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        circuit_breaker().reset();
+        env_vars.set("circuit_breaker_threshold", "2");
+        ingest_records(&client, "db", "readings", &[test_record()])
+            .await
+            .expect("A retried write under the threshold must succeed");
+        assert_eq!(circuit_breaker().consecutive_throttles(), 0);
+        adaptive_concurrency().reset();
+    }
+
+    #[test]
+    fn test_circuit_breaker_threshold_parsing() {
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        assert_eq!(
+            circuit_breaker_threshold().expect("Unset must disable the breaker"),
+            None
+        );
+        env_vars.set("circuit_breaker_threshold", "5");
+        let threshold = circuit_breaker_threshold();
+        env_vars.set("circuit_breaker_threshold", "0");
+        let zero = circuit_breaker_threshold();
+        env_vars.set("circuit_breaker_threshold", "lots");
+        let unparsable = circuit_breaker_threshold();
+        env_vars.remove("circuit_breaker_threshold");
+        assert_eq!(threshold.expect("A positive threshold must parse"), Some(5));
+        assert!(zero.is_err());
+        assert!(unparsable.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_does_not_retry_other_errors() {
+        let _env = crate::test_support::EnvVarGuard::acquire();
+        let client = MockTimestreamClient::new();
+        client
+            .write_records_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::Other(anyhow!("validation error"))));
+
+        assert!(ingest_records(&client, "db", "readings", &[test_record()])
+            .await
+            .is_err());
+        assert_eq!(client.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_table_rejects_invalid_partition_key_type() {
+        let client = MockTimestreamClient::new();
+        let table_config = TableConfig {
+            mem_store_retention_period: 24,
+            mag_store_retention_period: 7,
+            enable_mag_store_writes: true,
+            custom_partition_key_type: Some("bogus".to_string()),
+            custom_partition_key_dimension: None,
+            enforce_custom_partition_key: false,
+        };
+
+        let error = create_table(&client, "db", "readings", table_config)
+            .await
+            .expect_err("Invalid partition key type must be rejected");
+        assert!(error.to_string().contains("custom_partition_key_type"));
+        assert!(client.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_table_builds_dimension_partition_key() {
+        let client = MockTimestreamClient::new();
+        let table_config = TableConfig {
+            mem_store_retention_period: 24,
+            mag_store_retention_period: 7,
+            enable_mag_store_writes: true,
+            custom_partition_key_type: Some("dimension".to_string()),
+            custom_partition_key_dimension: Some("fleet".to_string()),
+            enforce_custom_partition_key: true,
+        };
+
+        create_table(&client, "db", "readings", table_config)
+            .await
+            .expect("Failed to create table with partition key");
+        assert_eq!(client.calls(), vec!["create_table db readings schema=true"]);
+    }
+
+    #[test]
+    fn test_get_table_config_partition_key_combinations() {
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        env_vars.set("mem_store_retention_period", "24");
+        env_vars.set("mag_store_retention_period", "7");
+        env_vars.remove("custom_partition_key_type");
+        env_vars.remove("custom_partition_key_dimension");
+        env_vars.remove("enforce_custom_partition_key");
+
+        // No partition key variables: nothing resolved, nothing enforced.
+        let config = get_table_config().expect("Config without partition key must resolve");
+        assert!(config.custom_partition_key_type.is_none());
+        assert!(config.custom_partition_key_dimension.is_none());
+        assert!(!config.enforce_custom_partition_key);
+
+        // A dimension key without its dimension name is rejected at
+        // resolution, not at the first create_table.
+        env_vars.set("custom_partition_key_type", "dimension");
+        let error =
+            get_table_config().expect_err("A dimension key without a dimension must be rejected");
+        assert!(error.to_string().contains("custom_partition_key_dimension"));
+
+        // With the name it resolves, enforcement defaulting to optional.
+        env_vars.set("custom_partition_key_dimension", "fleet");
+        let config = get_table_config().expect("Dimension key config must resolve");
+        assert_eq!(
+            config.custom_partition_key_type.as_deref(),
+            Some("dimension")
+        );
+        assert_eq!(config.custom_partition_key_dimension.as_deref(), Some("fleet"));
+        assert!(!config.enforce_custom_partition_key);
+
+        env_vars.set("enforce_custom_partition_key", "true");
+        let config = get_table_config().expect("Enforced dimension key config must resolve");
+        assert!(config.enforce_custom_partition_key);
+
+        // A measure key carries no name; a leftover dimension or
+        // enforcement variable is ignored rather than rejected.
+        env_vars.set("custom_partition_key_type", "measure");
+        let config = get_table_config().expect("Measure key config must resolve");
+        assert_eq!(config.custom_partition_key_type.as_deref(), Some("measure"));
+        env_vars.remove("custom_partition_key_dimension");
+        env_vars.remove("enforce_custom_partition_key");
+        get_table_config().expect("Bare measure key config must resolve");
+
+        // Anything but dimension or measure is rejected up front.
+        env_vars.set("custom_partition_key_type", "bogus");
+        let error = get_table_config().expect_err("An unknown key type must be rejected");
+        assert!(error.to_string().contains("custom_partition_key_type"));
+    }
+
+    #[tokio::test]
+    async fn test_create_table_with_default_retention() {
+        let client = MockTimestreamClient::new();
+        let table_config = TableConfig {
+            mem_store_retention_period: resolve_mem_store_retention(None).unwrap(),
+            mag_store_retention_period: resolve_mag_store_retention(None).unwrap(),
+            enable_mag_store_writes: false,
+            custom_partition_key_type: None,
+            custom_partition_key_dimension: None,
+            enforce_custom_partition_key: false,
+        };
+
+        create_table(&client, "db", "readings", table_config)
+            .await
+            .expect("Creation with default retention must succeed");
+        assert_eq!(client.calls(), vec!["create_table db readings schema=false"]);
+    }
+
+    fn describe_table_count(client: &MockTimestreamClient) -> usize {
+        client
+            .calls()
+            .iter()
+            .filter(|call| call.starts_with("describe_table"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_table_existence_is_cached_once_confirmed() {
+        let client = MockTimestreamClient::new();
+        // The names are unique to this test since the cache is global to
+        // the test process.
+        assert!(table_exists_cached(&client, "cache_db", "cached_readings")
+            .await
+            .unwrap());
+        assert!(table_exists_cached(&client, "cache_db", "cached_readings")
+            .await
+            .unwrap());
+        assert_eq!(describe_table_count(&client), 1);
+
+        invalidate_existence_cache();
+        assert!(table_exists_cached(&client, "cache_db", "cached_readings")
+            .await
+            .unwrap());
+        assert_eq!(describe_table_count(&client), 2);
+    }
+
+    #[tokio::test]
+    async fn test_missing_table_is_not_cached() {
+        let client = MockTimestreamClient::new();
+        client
+            .describe_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::ResourceNotFound(
+                "no such table".to_string(),
+            )));
+
+        assert!(!table_exists_cached(&client, "cache_db", "missing_readings")
+            .await
+            .unwrap());
+        // A negative result must not be cached: the table may be created
+        // at any time.
+        assert!(table_exists_cached(&client, "cache_db", "missing_readings")
+            .await
+            .unwrap());
+        assert_eq!(describe_table_count(&client), 2);
+    }
+
+    #[tokio::test]
+    async fn test_preflight_table_check_maps_existing_tables() {
+        let client = Arc::new(MockTimestreamClient::new());
+        let table_names: Vec<String> =
+            (0..5).map(|index| format!("preflight_{}", index)).collect();
+
+        // The empty mock queue answers every describe with success, so
+        // each table maps to `true` and is described exactly once.
+        let exists_by_table = preflight_table_check(&client, "preflight_db", &table_names)
+            .await
+            .unwrap();
+        assert_eq!(exists_by_table.len(), 5);
+        assert!(table_names
+            .iter()
+            .all(|table_name| exists_by_table[table_name]));
+        assert_eq!(describe_table_count(&client), 5);
+    }
+
+    #[tokio::test]
+    async fn test_preflight_table_check_maps_missing_table() {
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::ResourceNotFound(
+                "table not found".to_string(),
+            )));
+
+        let table_names = vec!["preflight_missing".to_string()];
+        let exists_by_table = preflight_table_check(&client, "preflight_db", &table_names)
+            .await
+            .unwrap();
+        assert!(!exists_by_table["preflight_missing"]);
+    }
+
+    #[tokio::test]
+    async fn test_preflight_table_check_propagates_describe_errors() {
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .describe_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::Other(anyhow!("internal error"))));
+
+        let table_names = vec!["preflight_error".to_string()];
+        assert!(
+            preflight_table_check(&client, "preflight_db", &table_names)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_managed_client_rebuilds_after_reload_failure() {
+        let managed: ManagedClient<MockTimestreamClient> = ManagedClient::new();
+        let builds = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let healthy = Arc::new(AtomicBool::new(true));
+        let build = || {
+            let builds = Arc::clone(&builds);
+            let healthy = Arc::clone(&healthy);
+            async move {
+                builds.fetch_add(1, Ordering::SeqCst);
+                Ok((MockTimestreamClient::new(), healthy))
+            }
+        };
+
+        let first = managed.get_or_build(build).await.unwrap();
+        let second = managed.get_or_build(build).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+
+        // Simulate the endpoint discovery reload task dying: the health
+        // flag clears and the next call must build a fresh client.
+        healthy.store(false, Ordering::SeqCst);
+        let third = managed.get_or_build(build).await.unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_shared_client_slot_builds_once_across_repeated_calls() {
+        // Mirrors the `SHARED_CLIENT`/`get_or_init_connection` wiring with
+        // a mock builder; each build stands in for one spawned reload
+        // task, so the count staying at 1 means repeated calls neither
+        // rebuild the client nor spawn duplicate tasks.
+        static SHARED: OnceLock<ManagedClient<MockTimestreamClient>> = OnceLock::new();
+        let reload_tasks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let build = || {
+            let reload_tasks = Arc::clone(&reload_tasks);
+            async move {
+                reload_tasks.fetch_add(1, Ordering::SeqCst);
+                Ok((MockTimestreamClient::new(), Arc::new(AtomicBool::new(true))))
+            }
+        };
+
+        let first = SHARED.get_or_init(ManagedClient::new).get_or_build(build).await.unwrap();
+        let second = SHARED.get_or_init(ManagedClient::new).get_or_build(build).await.unwrap();
+        let third = SHARED.get_or_init(ManagedClient::new).get_or_build(build).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&first, &third));
+        assert_eq!(reload_tasks.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_managed_client_retries_initialization() {
+        let managed: ManagedClient<MockTimestreamClient> = ManagedClient::new();
+        let attempts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        managed
+            .get_or_build(|| {
+                let attempts = Arc::clone(&attempts);
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) + 1 < CLIENT_INIT_MAX_ATTEMPTS as usize
+                    {
+                        Err(anyhow!("transient initialization failure"))
+                    } else {
+                        Ok((MockTimestreamClient::new(), Arc::new(AtomicBool::new(true))))
+                    }
+                }
+            })
+            .await
+            .expect("Initialization should succeed within the retry budget");
+        assert_eq!(attempts.load(Ordering::SeqCst), CLIENT_INIT_MAX_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn test_adaptive_concurrency_trajectory() {
+        // A local controller keeps this test independent of the
+        // process-wide instance driven by the ingest tests.
+        let controller = AdaptiveConcurrency::new(12);
+        assert_eq!(controller.current_limit(), 12);
+
+        // Throttling halves the limit, down to a floor of 1.
+        controller.on_throttle();
+        assert_eq!(controller.current_limit(), 6);
+        controller.on_throttle();
+        assert_eq!(controller.current_limit(), 3);
+        for _ in 0..4 {
+            controller.on_throttle();
+        }
+        assert_eq!(controller.current_limit(), 1);
+
+        // A full window of successes raises the limit one step; a partial
+        // window does not.
+        for _ in 0..AIMD_SUCCESS_WINDOW {
+            controller.on_success();
+        }
+        assert_eq!(controller.current_limit(), 2);
+        for _ in 0..AIMD_SUCCESS_WINDOW - 1 {
+            controller.on_success();
+        }
+        assert_eq!(controller.current_limit(), 2);
+
+        // The limit never exceeds the configured maximum.
+        let controller = AdaptiveConcurrency::new(2);
+        for _ in 0..AIMD_SUCCESS_WINDOW * 3 {
+            controller.on_success();
+        }
+        assert_eq!(controller.current_limit(), 2);
+    }
+
+    #[test]
+    fn test_adaptive_concurrency_throttle_resets_success_streak() {
+        let controller = AdaptiveConcurrency::new(12);
+        for _ in 0..AIMD_SUCCESS_WINDOW - 1 {
+            controller.on_success();
+        }
+        controller.on_throttle();
+        assert_eq!(controller.current_limit(), 6);
+
+        // The streak started over, so one more success must not raise the
+        // limit; a full window from here does.
+        controller.on_success();
+        assert_eq!(controller.current_limit(), 6);
+        for _ in 0..AIMD_SUCCESS_WINDOW - 1 {
+            controller.on_success();
+        }
+        assert_eq!(controller.current_limit(), 7);
+
+        controller.reset();
+        assert_eq!(controller.current_limit(), 12);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_batch_deletes_tables_then_database() {
+        let client = Arc::new(MockTimestreamClient::new());
+        let mut cleanup = CleanupBatch::new(
+            Arc::clone(&client),
+            "integ_db",
+            vec!["first".to_string(), "second".to_string()],
+        )
+        .including_database();
+        assert!(cleanup.cleanup().await.is_empty());
+        assert_eq!(
+            client.calls(),
+            vec![
+                "delete_table integ_db first",
+                "delete_table integ_db second",
+                "delete_database integ_db",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_batch_leaves_unowned_database_alone() {
+        let client = Arc::new(MockTimestreamClient::new());
+        let mut cleanup = CleanupBatch::new(
+            Arc::clone(&client),
+            "integ_db",
+            vec!["readings".to_string()],
+        );
+        assert!(cleanup.cleanup().await.is_empty());
+        assert_eq!(client.calls(), vec!["delete_table integ_db readings"]);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_batch_tolerates_already_deleted_resources() {
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .delete_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::ResourceNotFound(
+                "table gone".to_string(),
+            )));
+        client
+            .delete_database_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::ResourceNotFound(
+                "database gone".to_string(),
+            )));
+        let mut cleanup = CleanupBatch::new(
+            Arc::clone(&client),
+            "integ_db",
+            vec!["readings".to_string()],
+        )
+        .including_database();
+        assert!(cleanup.cleanup().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_batch_reports_failures_and_skips_database() {
+        let client = Arc::new(MockTimestreamClient::new());
+        client
+            .delete_table_results
+            .lock()
+            .unwrap()
+            .push_back(Err(ClientError::Other(anyhow!("internal error"))));
+        let mut cleanup = CleanupBatch::new(
+            Arc::clone(&client),
+            "integ_db",
+            vec!["first".to_string(), "second".to_string()],
+        )
+        .including_database();
+        // The failed first table is reported, the second is still
+        // attempted, and the non-empty database is left alone.
+        assert_eq!(cleanup.cleanup().await, vec!["first".to_string()]);
+        assert_eq!(
+            client.calls(),
+            vec!["delete_table integ_db first", "delete_table integ_db second"]
+        );
+    }
+}
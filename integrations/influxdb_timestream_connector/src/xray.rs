@@ -0,0 +1,237 @@
+//! Minimal X-Ray subsegment emission.
+//!
+//! Lambda populates `_X_AMZN_TRACE_ID` with the sampled trace context and
+//! runs an X-Ray daemon reachable over UDP (`AWS_XRAY_DAEMON_ADDRESS`,
+//! defaulting to `127.0.0.1:2000`). The connector emits one subsegment
+//! document per instrumented stage — parsing, record building, table
+//! existence checks, and each batch write — annotated with the table name
+//! and record count, so traces show where invocation time goes. When the
+//! trace header is absent or unsampled (local runs, tests), every
+//! operation here is a no-op.
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Daemon address used when `AWS_XRAY_DAEMON_ADDRESS` is not set.
+const DEFAULT_DAEMON_ADDRESS: &str = "127.0.0.1:2000";
+
+/// The trace context parsed from `_X_AMZN_TRACE_ID`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub parent_id: String,
+}
+
+/// Parses an `_X_AMZN_TRACE_ID` header of the form
+/// `Root=...;Parent=...;Sampled=1`. Returns `None` for malformed headers
+/// and for unsampled traces, which must not be emitted.
+pub fn parse_trace_header(header: &str) -> Option<TraceContext> {
+    let mut trace_id = None;
+    let mut parent_id = None;
+    let mut sampled = false;
+    for part in header.split(';') {
+        match part.split_once('=') {
+            Some(("Root", value)) => trace_id = Some(value.to_string()),
+            Some(("Parent", value)) => parent_id = Some(value.to_string()),
+            Some(("Sampled", value)) => sampled = value == "1",
+            _ => {}
+        }
+    }
+    if !sampled {
+        return None;
+    }
+    Some(TraceContext {
+        trace_id: trace_id?,
+        parent_id: parent_id?,
+    })
+}
+
+/// The current invocation's trace context, or `None` when tracing is off.
+fn trace_context() -> Option<TraceContext> {
+    parse_trace_header(&env::var("_X_AMZN_TRACE_ID").ok()?)
+}
+
+/// Seconds since the epoch as the fractional timestamp X-Ray expects.
+fn epoch_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+static SUBSEGMENT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A 16-hex-digit subsegment id, unique within the process.
+fn subsegment_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u64;
+    let counter = SUBSEGMENT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:016x}", (counter << 30) ^ nanos)
+}
+
+/// Builds the in-progress-free subsegment document sent to the daemon.
+fn subsegment_document(
+    name: &str,
+    id: &str,
+    start_time: f64,
+    end_time: f64,
+    context: &TraceContext,
+    annotations: &BTreeMap<String, Value>,
+) -> Value {
+    let mut document = json!({
+        "name": name,
+        "id": id,
+        "start_time": start_time,
+        "end_time": end_time,
+        "type": "subsegment",
+        "trace_id": context.trace_id,
+        "parent_id": context.parent_id,
+    });
+    if !annotations.is_empty() {
+        document["annotations"] = json!(annotations);
+    }
+    document
+}
+
+/// Sends a document to the daemon, prefixed with the UDP protocol header.
+/// Emission is best-effort: an unreachable daemon must never affect
+/// ingestion.
+fn emit(document: &Value) {
+    let address =
+        env::var("AWS_XRAY_DAEMON_ADDRESS").unwrap_or_else(|_| DEFAULT_DAEMON_ADDRESS.to_string());
+    let payload = format!("{}\n{}", json!({"format": "json", "version": 1}), document);
+    if let Ok(socket) = UdpSocket::bind("0.0.0.0:0") {
+        let _ = socket.send_to(payload.as_bytes(), address);
+    }
+}
+
+/// One timed subsegment. `begin` captures the start time; `end` emits the
+/// completed document. Every method is a no-op when tracing is off, so
+/// call sites need no conditionals.
+pub struct Subsegment(Option<ActiveSubsegment>);
+
+struct ActiveSubsegment {
+    name: String,
+    id: String,
+    start_time: f64,
+    context: TraceContext,
+    annotations: BTreeMap<String, Value>,
+}
+
+impl Subsegment {
+    /// Starts a subsegment, or a no-op handle when the trace header is
+    /// absent or unsampled.
+    pub fn begin(name: &str) -> Self {
+        Subsegment(trace_context().map(|context| ActiveSubsegment {
+            name: name.to_string(),
+            id: subsegment_id(),
+            start_time: epoch_seconds(),
+            context,
+            annotations: BTreeMap::new(),
+        }))
+    }
+
+    /// Attaches an annotation shown in the X-Ray console's filter
+    /// expressions, e.g. the table name or record count.
+    pub fn annotate(&mut self, key: &str, value: Value) {
+        if let Some(active) = &mut self.0 {
+            active.annotations.insert(key.to_string(), value);
+        }
+    }
+
+    /// Marks the subsegment as failed.
+    pub fn set_error(&mut self) {
+        self.annotate("error", json!(true));
+    }
+
+    /// Completes the subsegment and sends it to the daemon.
+    pub fn end(self) {
+        let Some(active) = self.0 else {
+            return;
+        };
+        let document = subsegment_document(
+            &active.name,
+            &active.id,
+            active.start_time,
+            epoch_seconds(),
+            &active.context,
+            &active.annotations,
+        );
+        emit(&document);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trace_header() {
+        let context = parse_trace_header(
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+        )
+        .expect("Sampled header must parse");
+        assert_eq!(context.trace_id, "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(context.parent_id, "53995c3f42cd8ad8");
+
+        // Unsampled or incomplete headers disable emission.
+        assert!(parse_trace_header(
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=0"
+        )
+        .is_none());
+        assert!(parse_trace_header("Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1").is_none());
+        assert!(parse_trace_header("").is_none());
+    }
+
+    #[test]
+    fn test_subsegment_document_contents() {
+        let context = TraceContext {
+            trace_id: "1-5759e988-bd862e3fe1be46a994272793".to_string(),
+            parent_id: "53995c3f42cd8ad8".to_string(),
+        };
+        let annotations = BTreeMap::from([
+            ("table_name".to_string(), json!("readings")),
+            ("record_count".to_string(), json!(100)),
+        ]);
+        let document = subsegment_document(
+            "ingest_record_batch",
+            "0123456789abcdef",
+            1677605771.0,
+            1677605771.5,
+            &context,
+            &annotations,
+        );
+        assert_eq!(document["name"], "ingest_record_batch");
+        assert_eq!(document["type"], "subsegment");
+        assert_eq!(document["trace_id"], "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(document["parent_id"], "53995c3f42cd8ad8");
+        assert_eq!(document["annotations"]["table_name"], "readings");
+        assert_eq!(document["annotations"]["record_count"], 100);
+        assert_eq!(document["end_time"], 1677605771.5);
+    }
+
+    #[test]
+    fn test_subsegment_noop_without_trace_header() {
+        let mut env_vars = crate::test_support::EnvVarGuard::acquire();
+        env_vars.remove("_X_AMZN_TRACE_ID");
+        let mut subsegment = Subsegment::begin("build_records");
+        subsegment.annotate("metrics", json!(5));
+        subsegment.set_error();
+        // Ending a disabled subsegment must not emit or panic.
+        subsegment.end();
+    }
+
+    #[test]
+    fn test_subsegment_ids_are_unique() {
+        let first = subsegment_id();
+        let second = subsegment_id();
+        assert_eq!(first.len(), 16);
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,103 @@
+//! Standalone sweeper for integration-test databases left behind by
+//! crashed or interrupted runs; run it with
+//!
+//! ```text
+//! cargo test --test cleanup_orphans -- --ignored
+//! ```
+//!
+//! Only databases matching the integration tests' naming pattern (see
+//! `test_support::unique_integ_database_name`) and older than
+//! `integ_orphan_max_age_hours` (default 2) are deleted, so a sweep
+//! cannot race a healthy concurrent run.
+
+use aws_sdk_timestreamwrite as timestream_write;
+use influxdb_timestream_connector::test_support::integ_database_created_at;
+use influxdb_timestream_connector::timestream_utils;
+use std::env;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_MAX_AGE_HOURS: u64 = 2;
+
+#[tokio::test]
+#[ignore]
+async fn cleanup_orphaned_integ_databases() {
+    let region = env::var("region").unwrap_or_else(|_| "us-east-1".to_string());
+    let client = Arc::new(
+        timestream_utils::get_connection(&region, None)
+            .await
+            .expect("Failed to create Timestream client"),
+    );
+
+    let max_age_hours = match env::var("integ_orphan_max_age_hours") {
+        Ok(value) => value
+            .parse::<u64>()
+            .expect("integ_orphan_max_age_hours must be an integer number of hours"),
+        Err(_) => DEFAULT_MAX_AGE_HOURS,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the Unix epoch")
+        .as_secs();
+
+    let mut failed: Vec<String> = Vec::new();
+    let mut next_token: Option<String> = None;
+    loop {
+        let output = client
+            .list_databases()
+            .set_next_token(next_token.take())
+            .send()
+            .await
+            .expect("Failed to list databases");
+        for database in output.databases() {
+            let Some(database_name) = database.database_name() else {
+                continue;
+            };
+            let Some(created_at) = integ_database_created_at(database_name) else {
+                continue;
+            };
+            if created_at + max_age_hours * 3600 > now {
+                continue;
+            }
+            match sweep_database(&client, database_name).await {
+                Ok(()) => println!("Deleted orphaned database {}", database_name),
+                Err(error) => {
+                    eprintln!("Failed to sweep database {}: {:?}", database_name, error);
+                    failed.push(database_name.to_string());
+                }
+            }
+        }
+        next_token = output.next_token().map(str::to_string);
+        if next_token.is_none() {
+            break;
+        }
+    }
+    assert!(failed.is_empty(), "Failed to sweep databases: {:?}", failed);
+}
+
+/// Deletes every table in the database, then the database itself;
+/// Timestream refuses to delete a database that still holds tables.
+async fn sweep_database(
+    client: &Arc<timestream_write::Client>,
+    database_name: &str,
+) -> anyhow::Result<()> {
+    let mut next_token: Option<String> = None;
+    loop {
+        let output = client
+            .list_tables()
+            .database_name(database_name)
+            .set_next_token(next_token.take())
+            .send()
+            .await?;
+        for table in output.tables() {
+            if let Some(table_name) = table.table_name() {
+                timestream_utils::delete_table(client, database_name, table_name).await?;
+            }
+        }
+        next_token = output.next_token().map(str::to_string);
+        if next_token.is_none() {
+            break;
+        }
+    }
+    timestream_utils::delete_database(client, database_name).await
+}
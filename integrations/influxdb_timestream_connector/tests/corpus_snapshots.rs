@@ -0,0 +1,128 @@
+//! Golden-file corpus tests: each `.lp` file under `tests/corpus/` is
+//! parsed and built into records with a fixed configuration, and the
+//! result is compared line-by-line against its checked-in `.snap.json`
+//! snapshot. This pins down outputs refactors keep subtly changing —
+//! dimension order, float formatting, measure value types — without
+//! needing AWS credentials. Regenerate snapshots after an intended
+//! change with `UPDATE_SNAPSHOTS=1 cargo test --test corpus_snapshots`.
+
+use aws_sdk_timestreamwrite::types::{Record, TimeUnit};
+use influxdb_timestream_connector::line_protocol_parser::parse_line_protocol;
+use influxdb_timestream_connector::records_builder::build_records;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// Projects a `Record` onto a stable JSON shape, since the SDK type has
+/// no serde support of its own.
+fn record_to_json(record: &Record) -> Value {
+    json!({
+        "time": record.time(),
+        "time_unit": record.time_unit().map(|unit| unit.as_str()),
+        "measure_name": record.measure_name(),
+        "measure_value_type": record.measure_value_type().map(|value_type| value_type.as_str()),
+        "dimensions": record
+            .dimensions()
+            .iter()
+            .map(|dimension| json!({ "name": dimension.name(), "value": dimension.value() }))
+            .collect::<Vec<Value>>(),
+        "measure_values": record
+            .measure_values()
+            .iter()
+            .map(|measure| {
+                json!({
+                    "name": measure.name(),
+                    "value": measure.value(),
+                    "type": measure.r#type().as_str(),
+                })
+            })
+            .collect::<Vec<Value>>(),
+    })
+}
+
+/// Parses one corpus file and builds its snapshot document: the parsed
+/// metrics (via the `Metric` serde support) and the built records,
+/// grouped by table in sorted order for determinism.
+fn build_snapshot(line_protocol: &str) -> Value {
+    let metrics = parse_line_protocol(line_protocol).expect("Failed to parse corpus file");
+    let tables = build_records(metrics.clone(), &TimeUnit::Nanoseconds, "corpus-measure")
+        .expect("Failed to build records for corpus file");
+    let mut table_names: Vec<&String> = tables.keys().collect();
+    table_names.sort();
+    json!({
+        "metrics": metrics,
+        "tables": table_names
+            .iter()
+            .map(|name| {
+                json!({
+                    "table": name,
+                    "records": tables[*name].iter().map(record_to_json).collect::<Vec<Value>>(),
+                })
+            })
+            .collect::<Vec<Value>>(),
+    })
+}
+
+/// Reports the first differing line between the rendered snapshot and
+/// the checked-in one, which reads better than one giant JSON blob.
+fn assert_snapshot_matches(name: &str, rendered: &str, expected: &str) {
+    if rendered == expected {
+        return;
+    }
+    for (line_number, (rendered_line, expected_line)) in
+        rendered.lines().zip(expected.lines()).enumerate()
+    {
+        if rendered_line != expected_line {
+            panic!(
+                "Snapshot mismatch for {} at line {}:\n  expected: {}\n  actual:   {}\n\
+                Rerun with UPDATE_SNAPSHOTS=1 if the change is intended.",
+                name,
+                line_number + 1,
+                expected_line,
+                rendered_line
+            );
+        }
+    }
+    panic!(
+        "Snapshot mismatch for {}: lengths differ ({} vs {} lines). \
+        Rerun with UPDATE_SNAPSHOTS=1 if the change is intended.",
+        name,
+        expected.lines().count(),
+        rendered.lines().count()
+    );
+}
+
+#[test]
+fn corpus_files_match_snapshots() {
+    let corpus_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let update = std::env::var("UPDATE_SNAPSHOTS").as_deref() == Ok("1");
+    let mut checked = 0;
+    let mut entries: Vec<_> = fs::read_dir(&corpus_dir)
+        .expect("Failed to read tests/corpus")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.extension().and_then(|extension| extension.to_str()) != Some("lp") {
+            continue;
+        }
+        let line_protocol = fs::read_to_string(&path).unwrap();
+        let rendered =
+            serde_json::to_string_pretty(&build_snapshot(&line_protocol)).unwrap() + "\n";
+        let snapshot_path = path.with_extension("snap.json");
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        if update {
+            fs::write(&snapshot_path, &rendered).unwrap();
+        } else {
+            let expected = fs::read_to_string(&snapshot_path).unwrap_or_else(|_| {
+                panic!(
+                    "Missing snapshot for {}; generate it with UPDATE_SNAPSHOTS=1",
+                    name
+                )
+            });
+            assert_snapshot_matches(&name, &rendered, &expected);
+        }
+        checked += 1;
+    }
+    assert!(checked >= 3, "Corpus directory lost its .lp files");
+}
@@ -0,0 +1,784 @@
+//! Integration tests for the InfluxDB Timestream connector. These tests
+//! require AWS credentials with Timestream write permissions and are
+//! ignored by default; run them with `cargo test -- --ignored`.
+
+mod query_support;
+
+use aws_sdk_timestreamquery::types::ScalarType;
+use aws_sdk_timestreamwrite as timestream_write;
+use influxdb_timestream_connector::timestream_utils::CleanupBatch;
+use influxdb_timestream_connector::{
+    ingest_line_protocol, lambda_handler, timestream_utils, ConnectorConfig,
+};
+use influxdb_timestream_connector::test_support::{unique_integ_database_name, EnvVarGuard};
+use lambda_runtime::{Context, LambdaEvent};
+use serde_json::{json, Value};
+use std::env;
+use std::sync::Arc;
+
+/// Sets the baseline variables shared by every integration test, points
+/// `database_name` at a database unique to this test run, and returns
+/// the guard any deviating variable changes must be routed through along
+/// with the database's name; see
+/// `influxdb_timestream_connector::test_support` for the pattern. Each
+/// test creates and deletes its own database, so concurrent CI jobs in
+/// one account do not stomp on each other's tables; orphans from crashed
+/// runs are swept by `tests/cleanup_orphans.rs`.
+#[must_use]
+fn set_environment_variables() -> (EnvVarGuard, String) {
+    let mut guard = EnvVarGuard::acquire();
+    if env::var("region").is_err() {
+        env::set_var("region", "us-east-1");
+    }
+    let database_name = unique_integ_database_name();
+    guard.set("database_name", &database_name);
+    env::set_var("enable_database_creation", "true");
+    env::set_var("enable_table_creation", "true");
+    env::set_var("enable_mag_store_writes", "true");
+    env::set_var("mag_store_retention_period", "7");
+    env::set_var("mem_store_retention_period", "24");
+    env::set_var("measure_name_for_multi_measure_records", "influxdb-measure");
+    (guard, database_name)
+}
+
+async fn get_client() -> Arc<timestream_write::Client> {
+    let region = env::var("region").expect("region environment variable is not defined");
+    Arc::new(
+        timestream_utils::get_connection(&region, None)
+            .await
+            .expect("Failed to create Timestream client"),
+    )
+}
+
+async fn get_query_client() -> aws_sdk_timestreamquery::Client {
+    let region = env::var("region").expect("region environment variable is not defined");
+    query_support::get_query_connection(&region)
+        .await
+        .expect("Failed to create Timestream query client")
+}
+
+/// Builds an API Gateway 1.0-shaped write event carrying a line
+/// protocol body and a `precision` query string parameter, the envelope
+/// `lambda_handler` receives in production.
+fn make_write_event(body: &str, precision: &str) -> LambdaEvent<Value> {
+    let event = json!({
+        "body": body,
+        "queryStringParameters": { "precision": precision },
+    });
+    LambdaEvent::new(event, Context::default())
+}
+
+/// Extracts the numeric `statusCode` from a handler response envelope.
+fn status_of(response: &Value) -> u64 {
+    response["statusCode"]
+        .as_u64()
+        .expect("Response has no numeric statusCode")
+}
+
+
+#[tokio::test]
+#[ignore]
+async fn test_mtmm_basic() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["readings".to_string()],
+    )
+    .including_database();
+
+    let event = make_write_event(
+        "readings,fleet=Alberta fuel=30i 1677605771000000000",
+        "ns",
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+    let body: Value =
+        serde_json::from_str(response["body"].as_str().expect("Response body is not a string"))
+            .expect("Response body is not JSON");
+    assert_eq!(body["lines_parsed"], 1);
+    assert_eq!(body["records_written"], 1);
+    assert_eq!(body["tables"], json!(["readings"]));
+
+    // Verify the stored point, not just the write response.
+    query_support::assert_point_ingested(
+        &get_query_client().await,
+        &database_name,
+        "readings",
+        &[("fleet", "Alberta")],
+        "fuel",
+        "30",
+        ScalarType::Bigint,
+        1677605771000000000,
+    )
+    .await;
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mtmm_float() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["readings".to_string()],
+    )
+    .including_database();
+
+    let event = make_write_event("readings,fleet=Alberta fuel=40.5 1677605771000000000", "ns");
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+
+    query_support::assert_point_ingested(
+        &get_query_client().await,
+        &database_name,
+        "readings",
+        &[("fleet", "Alberta")],
+        "fuel",
+        "40.5",
+        ScalarType::Double,
+        1677605771000000000,
+    )
+    .await;
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mtmm_string() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["readings".to_string()],
+    )
+    .including_database();
+
+    let event = make_write_event(
+        "readings,fleet=Alberta status=\"active\" 1677605771000000000",
+        "ns",
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+
+    query_support::assert_point_ingested(
+        &get_query_client().await,
+        &database_name,
+        "readings",
+        &[("fleet", "Alberta")],
+        "status",
+        "active",
+        ScalarType::Varchar,
+        1677605771000000000,
+    )
+    .await;
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mtmm_bool() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["readings".to_string()],
+    )
+    .including_database();
+
+    let event = make_write_event(
+        "readings,fleet=Alberta active=true 1677605771000000000",
+        "ns",
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+
+    query_support::assert_point_ingested(
+        &get_query_client().await,
+        &database_name,
+        "readings",
+        &[("fleet", "Alberta")],
+        "active",
+        "true",
+        ScalarType::Boolean,
+        1677605771000000000,
+    )
+    .await;
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_ingest_line_protocol_library_entry_point() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["readings".to_string()],
+    )
+    .including_database();
+
+    // Exercises the embeddable entry point without any Lambda event types.
+    let config = ConnectorConfig::from_env().expect("Failed to resolve config");
+    let summary = ingest_line_protocol(
+        &client,
+        &config,
+        "readings,fleet=Alberta fuel=30i 1677605771000000000\n\
+        readings,fleet=Zurich fuel=35i 1677605772000000000",
+        &aws_sdk_timestreamwrite::types::TimeUnit::Nanoseconds,
+    )
+    .await
+    .expect("Failed to ingest line protocol");
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_eq!(summary.lines_parsed, 2);
+    assert_eq!(summary.records_written, 2);
+    assert_eq!(summary.tables, vec!["readings".to_string()]);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_gzip_compressed_payload() {
+    use base64::Engine;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["gzip_readings".to_string()],
+    )
+    .including_database();
+
+    let mut lines: Vec<String> = Vec::new();
+    for index in 0..100 {
+        lines.push(format!(
+            "gzip_readings,fleet=Alberta fuel={}i {}",
+            index,
+            1677605771000000000i64 + index
+        ));
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(lines.join("\n").as_bytes())
+        .expect("Failed to compress payload");
+    let compressed = encoder.finish().expect("Failed to finish compression");
+
+    let event = LambdaEvent::new(
+        json!({
+            "body": base64::engine::general_purpose::STANDARD.encode(compressed),
+            "isBase64Encoded": true,
+            "headers": { "Content-Encoding": "gzip" },
+            "queryStringParameters": { "precision": "ns" },
+        }),
+        Context::default(),
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_eq!(status_of(&response), 200);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_prometheus_remote_write_ingestion() {
+    use base64::Engine;
+    use influxdb_timestream_connector::prometheus_remote_write::{
+        Label, Sample, TimeSeries, WriteRequest, METRIC_NAME_LABEL,
+    };
+    use prost::Message;
+
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["cpu_usage".to_string()],
+    )
+    .including_database();
+
+    let write_request = WriteRequest {
+        timeseries: vec![TimeSeries {
+            labels: vec![
+                Label {
+                    name: METRIC_NAME_LABEL.to_string(),
+                    value: "cpu_usage".to_string(),
+                },
+                Label {
+                    name: "host".to_string(),
+                    value: "alpha".to_string(),
+                },
+            ],
+            samples: vec![Sample {
+                value: 0.5,
+                timestamp: 1677605771000,
+            }],
+        }],
+    };
+    let compressed = snap::raw::Encoder::new()
+        .compress_vec(&write_request.encode_to_vec())
+        .expect("Failed to snappy-compress WriteRequest");
+    let event = LambdaEvent::new(
+        json!({
+            "body": base64::engine::general_purpose::STANDARD.encode(compressed),
+            "isBase64Encoded": true,
+            "headers": { "Content-Type": "application/x-protobuf" },
+        }),
+        Context::default(),
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_eq!(status_of(&response), 200);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_skip_invalid_lines_reports_skipped_records() {
+    let (mut env_vars, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["skip_invalid_readings".to_string()],
+    )
+    .including_database();
+
+    // Ten valid lines interleaved with five malformed ones.
+    let mut lines: Vec<String> = Vec::new();
+    for index in 0..15i64 {
+        if index % 3 == 2 {
+            lines.push("skip_invalid_readings,fleet= 1677605771000000000".to_string());
+        } else {
+            lines.push(format!(
+                "skip_invalid_readings,fleet=Alberta fuel={}i {}",
+                index,
+                1677605771000000000i64 + index
+            ));
+        }
+    }
+    let body = lines.join("\n");
+
+    // Strict mode (the default) rejects the whole batch.
+    env_vars.set("skip_invalid_lines", "false");
+    let response = lambda_handler(&client, make_write_event(&body, "ns"))
+        .await
+        .expect("Handler returned an error");
+    assert_ne!(status_of(&response), 200);
+
+    // Lenient mode ingests the valid lines and reports the skip count.
+    env_vars.set("skip_invalid_lines", "true");
+    let response = lambda_handler(&client, make_write_event(&body, "ns"))
+        .await
+        .expect("Handler returned an error");
+    env_vars.remove("skip_invalid_lines");
+    assert_eq!(status_of(&response), 200);
+    let response_body: Value =
+        serde_json::from_str(response["body"].as_str().expect("Response has no body"))
+            .expect("Response body is not JSON");
+    assert_eq!(response_body["records_skipped"], 5);
+    assert_eq!(response_body["records_written"], 10);
+
+    // The valid records must actually be queryable.
+    let query_client = get_query_client().await;
+    let query_output = query_client
+        .query()
+        .query_string(format!(
+            "SELECT COUNT(*) FROM \"{}\".\"skip_invalid_readings\"",
+            database_name
+        ))
+        .send()
+        .await
+        .expect("Failed to query record count");
+    let record_count = query_output
+        .rows()
+        .first()
+        .and_then(|row| row.data().first())
+        .and_then(|datum| datum.scalar_value())
+        .expect("Count query returned no scalar value")
+        .to_string();
+
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_eq!(record_count, "10");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_custom_dimension_partition_key_enforcement() {
+    let (mut env_vars, database_name) = set_environment_variables();
+    env_vars.set("custom_partition_key_type", "dimension");
+    env_vars.set("custom_partition_key_dimension", "fleet");
+    env_vars.set("enforce_custom_partition_key", "true");
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["partition_readings".to_string()],
+    )
+    .including_database();
+
+    // A point carrying the partition key dimension creates the table and
+    // ingests cleanly.
+    let event = make_write_event(
+        "partition_readings,fleet=Alberta fuel=30i 1677605771000000000",
+        "ns",
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+
+    // The created table must carry the configured partition key schema.
+    let described = client
+        .describe_table()
+        .database_name(&database_name)
+        .table_name("partition_readings")
+        .send()
+        .await
+        .expect("Failed to describe created table");
+    let partition_keys = described
+        .table()
+        .and_then(|table| table.schema())
+        .map(|schema| schema.composite_partition_key())
+        .expect("Created table has no partition key schema");
+    assert_eq!(partition_keys.len(), 1);
+    assert_eq!(
+        partition_keys[0].r#type(),
+        &timestream_write::types::PartitionKeyType::Dimension
+    );
+    assert_eq!(partition_keys[0].name(), Some("fleet"));
+    assert_eq!(
+        partition_keys[0].enforcement_in_record(),
+        Some(&timestream_write::types::PartitionKeyEnforcementLevel::Required)
+    );
+
+    // With enforcement required, a point missing the dimension must be
+    // rejected by the write.
+    let event = make_write_event("partition_readings fuel=31i 1677605772000000000", "ns");
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_ne!(status_of(&response), 200);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_custom_measure_partition_key_schema() {
+    let (mut env_vars, database_name) = set_environment_variables();
+    env_vars.set("custom_partition_key_type", "measure");
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["measure_partition_readings".to_string()],
+    )
+    .including_database();
+
+    let event = make_write_event(
+        "measure_partition_readings,fleet=Alberta fuel=30i 1677605771000000000",
+        "ns",
+    );
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+
+    // A measure-type key partitions on the measure name; the schema
+    // carries neither a dimension name nor an enforcement level.
+    let described = client
+        .describe_table()
+        .database_name(&database_name)
+        .table_name("measure_partition_readings")
+        .send()
+        .await
+        .expect("Failed to describe created table");
+    let partition_keys = described
+        .table()
+        .and_then(|table| table.schema())
+        .map(|schema| schema.composite_partition_key())
+        .expect("Created table has no partition key schema");
+    assert_eq!(partition_keys.len(), 1);
+    assert_eq!(
+        partition_keys[0].r#type(),
+        &timestream_write::types::PartitionKeyType::Measure
+    );
+    assert_eq!(partition_keys[0].name(), None);
+
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn test_concurrent_invocations_same_table() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["concurrent_readings".to_string()],
+    )
+    .including_database();
+
+    // Ten parallel invocations each write a 100-point batch with distinct
+    // timestamps into the same table, exercising the NUM_BATCH_THREADS
+    // semaphore and Arc<Client> sharing. Each batch is at most 100 records,
+    // the write-records API limit, so any oversized batch would be rejected
+    // by the service and fail the invocation.
+    let base_time = 1677605771000000000i64;
+    let mut handles = Vec::new();
+    for task_index in 0..10i64 {
+        let client = Arc::clone(&client);
+        handles.push(tokio::task::spawn(async move {
+            let mut lines: Vec<String> = Vec::new();
+            for point_index in 0..100i64 {
+                let offset = task_index * 100 + point_index;
+                lines.push(format!(
+                    "concurrent_readings,fleet=Alberta fuel={}i {}",
+                    offset,
+                    base_time + offset
+                ));
+            }
+            lambda_handler(&client, make_write_event(&lines.join("\n"), "ns")).await
+        }));
+    }
+    for handle in handles {
+        let response = handle
+            .await
+            .expect("Invocation task panicked")
+            .expect("Handler returned an error");
+        assert_eq!(status_of(&response), 200);
+    }
+
+    let query_client = get_query_client().await;
+    let query_output = query_client
+        .query()
+        .query_string(format!(
+            "SELECT COUNT(*) FROM \"{}\".\"concurrent_readings\"",
+            database_name
+        ))
+        .send()
+        .await
+        .expect("Failed to query record count");
+    let record_count = query_output
+        .rows()
+        .first()
+        .and_then(|row| row.data().first())
+        .and_then(|datum| datum.scalar_value())
+        .expect("Count query returned no scalar value")
+        .to_string();
+
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_eq!(record_count, "1000");
+}
+
+#[cfg(feature = "kms_integration_tests")]
+#[tokio::test]
+#[ignore]
+async fn test_create_database_with_kms_key() {
+    let (mut env_vars, database_name) = set_environment_variables();
+    let kms_key_id = env::var("integ_kms_key_id")
+        .expect("integ_kms_key_id environment variable is not defined");
+    env_vars.set("kms_key_id", &kms_key_id);
+    let database_name = format!("{}_kms", database_name);
+    env_vars.set("database_name", &database_name);
+    let client = get_client().await;
+
+    timestream_utils::create_database(&client, &database_name)
+        .await
+        .expect("Failed to create database with KMS key");
+    let described = client
+        .describe_database()
+        .database_name(&database_name)
+        .send()
+        .await
+        .expect("Failed to describe created database");
+    let described_key = described
+        .database()
+        .and_then(|database| database.kms_key_id())
+        .expect("Created database has no KMS key");
+    assert!(described_key.contains(&kms_key_id));
+
+    client
+        .delete_database()
+        .database_name(&database_name)
+        .send()
+        .await
+        .expect("Failed to delete KMS test database");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mtmm_beyond_max_unique_field_keys() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["field_overflow".to_string()],
+    )
+    .including_database();
+
+    // Timestream allows at most 1024 unique measure names per table; a
+    // single batch exceeding that must be rejected.
+    let mut lines: Vec<String> = Vec::new();
+    for index in 0..1025 {
+        lines.push(format!(
+            "field_overflow field_{}=1i 1677605771000000000",
+            index
+        ));
+    }
+    let event = make_write_event(&lines.join("\n"), "ns");
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_ne!(status_of(&response), 200);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mtmm_measure_overflow_splits_tables() {
+    let (mut env_vars, database_name) = set_environment_variables();
+    env_vars.set("batch_split_on_measure_count", "true");
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec![
+            "field_overflow".to_string(),
+            "field_overflow_2".to_string(),
+        ],
+    )
+    .including_database();
+
+    // 41 lines of 25 unique field keys each carry 1025 distinct measure
+    // names; the last line pushes the table past the 1024 limit, so it
+    // must land in a created field_overflow_2 table instead of the whole
+    // batch being rejected.
+    let mut lines: Vec<String> = Vec::new();
+    for line in 0..41 {
+        let fields: Vec<String> = (0..25)
+            .map(|field| format!("field_{}_{}=1i", line, field))
+            .collect();
+        lines.push(format!(
+            "field_overflow {} 1677605771000000{:03}",
+            fields.join(","),
+            line
+        ));
+    }
+    let event = make_write_event(&lines.join("\n"), "ns");
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert_eq!(status_of(&response), 200);
+    let body: Value = serde_json::from_str(response["body"].as_str().unwrap())
+        .expect("Response body is not JSON");
+    assert_eq!(body["records_written"], 41);
+    let mut tables: Vec<String> = body["tables"]
+        .as_array()
+        .expect("Response has no tables array")
+        .iter()
+        .map(|table| table.as_str().unwrap().to_string())
+        .collect();
+    tables.sort();
+    assert_eq!(tables, vec!["field_overflow", "field_overflow_2"]);
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_adaptive_concurrency_normal_ingestion() {
+    let (_env, database_name) = set_environment_variables();
+    timestream_utils::adaptive_concurrency().reset();
+    let client = get_client().await;
+    let mut cleanup = CleanupBatch::new(
+        Arc::clone(&client),
+        &database_name,
+        vec!["readings".to_string()],
+    )
+    .including_database();
+
+    // Without any throttling the adaptive limiter must stay at full
+    // concurrency and leave normal ingestion unaffected.
+    let mut lines: Vec<String> = Vec::new();
+    for index in 0..250 {
+        lines.push(format!(
+            "readings,fleet=Alberta fuel={}i 167760577100000{:04}",
+            index, index
+        ));
+    }
+    let event = make_write_event(&lines.join("\n"), "ns");
+    let response = lambda_handler(&client, event)
+        .await
+        .expect("Handler returned an error");
+    assert!(cleanup.cleanup().await.is_empty(), "Cleanup failed for some tables");
+    assert_eq!(status_of(&response), 200);
+    let body: Value = serde_json::from_str(response["body"].as_str().unwrap())
+        .expect("Response body is not JSON");
+    assert_eq!(body["records_written"], 250);
+    assert_eq!(
+        body["concurrency_limit"],
+        influxdb_timestream_connector::NUM_BATCH_THREADS
+    );
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_delete_helpers_ignore_missing_resources() {
+    let (_env, database_name) = set_environment_variables();
+    let client = get_client().await;
+
+    timestream_utils::delete_table(&client, &database_name, "no_such_table")
+        .await
+        .expect("Deleting a non-existent table must succeed");
+    timestream_utils::delete_database(&client, "no_such_database")
+        .await
+        .expect("Deleting a non-existent database must succeed");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_firehose_config_failure_fails_the_invocation() {
+    // HTTP callers get problems reported in the response envelope, but
+    // Firehose invokes the Lambda asynchronously: only an invocation
+    // error makes Firehose retry the batch or dead-letter it, so a
+    // configuration failure must surface as `Err` rather than a 4xx
+    // body that Firehose would treat as success.
+    let (mut env_vars, _database_name) = set_environment_variables();
+    env_vars.remove("database_name");
+    let client = get_client().await;
+
+    let event = json!({
+        "deliveryStreamArn":
+            "arn:aws:firehose:us-east-1:123456789012:deliverystream/integ",
+        "records": [],
+    });
+    let result = lambda_handler(&client, LambdaEvent::new(event, Context::default())).await;
+    let error = result.expect_err("A misconfigured Firehose invocation must fail");
+    assert!(
+        error.to_string().contains("database_name"),
+        "Unexpected invocation error: {}",
+        error
+    );
+}
@@ -0,0 +1,98 @@
+//! Read-after-write verification for the integration tests. A 200
+//! response only proves the write call succeeded; these helpers query
+//! the target table through `aws_sdk_timestreamquery` so a regression
+//! that stores wrong measure values, types, or dimensions fails the
+//! test. Rows are polled with a short backoff because a freshly created
+//! table may not be queryable immediately.
+
+use anyhow::{anyhow, Result};
+use aws_sdk_timestreamquery::types::ScalarType;
+use std::time::Duration;
+
+/// How many times a verification query is retried before giving up.
+const QUERY_RETRY_ATTEMPTS: u32 = 10;
+
+/// Backoff between verification query attempts.
+const QUERY_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Creates a Timestream query client with endpoint discovery enabled and
+/// spawns its endpoint reload task, mirroring
+/// `timestream_utils::get_connection` on the write side.
+pub async fn get_query_connection(region: &str) -> Result<aws_sdk_timestreamquery::Client> {
+    let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        .region(aws_config::Region::new(region.to_owned()))
+        .load()
+        .await;
+    let (client, reload) = aws_sdk_timestreamquery::Client::new(&config)
+        .with_endpoint_discovery_enabled()
+        .await
+        .map_err(|error| anyhow!("Failed to enable query endpoint discovery: {}", error))?;
+    tokio::spawn(async move {
+        reload.reload_task().await;
+    });
+    Ok(client)
+}
+
+/// Asserts that the point identified by its dimensions and timestamp was
+/// ingested with the expected value and column type for `field`. The
+/// query is retried until a row appears; after the retry budget the
+/// helper panics with the query and the last failure.
+#[allow(clippy::too_many_arguments)]
+pub async fn assert_point_ingested(
+    client: &aws_sdk_timestreamquery::Client,
+    database_name: &str,
+    table_name: &str,
+    dimensions: &[(&str, &str)],
+    field: &str,
+    expected_value: &str,
+    expected_type: ScalarType,
+    timestamp_nanos: i64,
+) {
+    let mut predicates = vec![format!("time = from_nanoseconds({})", timestamp_nanos)];
+    for (name, value) in dimensions {
+        predicates.push(format!("\"{}\" = '{}'", name, value));
+    }
+    let query_string = format!(
+        "SELECT \"{}\" FROM \"{}\".\"{}\" WHERE {}",
+        field,
+        database_name,
+        table_name,
+        predicates.join(" AND ")
+    );
+
+    let mut last_failure = String::new();
+    for _ in 0..QUERY_RETRY_ATTEMPTS {
+        match client.query().query_string(&query_string).send().await {
+            Ok(output) => {
+                if let Some(row) = output.rows().first() {
+                    let column_type = output
+                        .column_info()
+                        .first()
+                        .and_then(|column| column.r#type())
+                        .and_then(|column_type| column_type.scalar_type());
+                    assert_eq!(
+                        column_type,
+                        Some(&expected_type),
+                        "Unexpected column type for query {}",
+                        query_string
+                    );
+                    let value = row.data().first().and_then(|datum| datum.scalar_value());
+                    assert_eq!(
+                        value,
+                        Some(expected_value),
+                        "Unexpected value for query {}",
+                        query_string
+                    );
+                    return;
+                }
+                last_failure = "the query returned no rows".to_string();
+            }
+            Err(error) => last_failure = format!("{:?}", error),
+        }
+        tokio::time::sleep(QUERY_RETRY_BACKOFF).await;
+    }
+    panic!(
+        "Point never appeared for query {}: {}",
+        query_string, last_failure
+    );
+}
@@ -0,0 +1,116 @@
+//! Cleanup sample for Amazon Timestream for LiveAnalytics: deletes the
+//! table and database the other samples create. With `--dry-run` it only
+//! lists what would be deleted, which is the safe default to reach for in
+//! shared environments.
+
+use anyhow::{anyhow, Result};
+use aws_sdk_timestreamwrite as timestream_write;
+use timestream_sample_app::{resolve_region, timestream_helper, DATABASE_NAME, TABLE_NAME};
+
+const USAGE: &str = "Usage: cleanup_sample [--database <name>] [--table <name>] [--dry-run]
+
+Deletes the sample table (default host_metrics) and then the sample
+database (default rust_sample_db). With --dry-run, prints the resources
+that would be deleted without calling any delete APIs.";
+
+struct Args {
+    database: String,
+    table: String,
+    dry_run: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Args> {
+    let mut database = DATABASE_NAME.to_string();
+    let mut table = TABLE_NAME.to_string();
+    let mut dry_run = false;
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value_of = |flag: &str| {
+            iter.next()
+                .ok_or_else(|| anyhow!("{} requires a value", flag))
+        };
+        match flag.as_str() {
+            "--database" => database = value_of("--database")?.clone(),
+            "--table" => table = value_of("--table")?.clone(),
+            "--dry-run" => dry_run = true,
+            other => return Err(anyhow!("Unrecognized argument: {}\n\n{}", other, USAGE)),
+        }
+    }
+    Ok(Args {
+        database,
+        table,
+        dry_run,
+    })
+}
+
+/// Returns the table's name if it exists, `None` if there is nothing to
+/// delete.
+async fn should_delete_table(
+    client: &timestream_write::Client,
+    args: &Args,
+) -> Option<String> {
+    client
+        .describe_table()
+        .database_name(&args.database)
+        .table_name(&args.table)
+        .send()
+        .await
+        .ok()
+        .map(|_| args.table.clone())
+}
+
+/// Returns the database's name if it exists, `None` if there is nothing
+/// to delete.
+async fn should_delete_database(
+    client: &timestream_write::Client,
+    args: &Args,
+) -> Option<String> {
+    client
+        .describe_database()
+        .database_name(&args.database)
+        .send()
+        .await
+        .ok()
+        .map(|_| args.database.clone())
+}
+
+/// Deletes the sample table and database, or with `dry_run` only prints
+/// what the deletion would remove.
+async fn do_cleanup(
+    client: &timestream_write::Client,
+    args: &Args,
+    dry_run: bool,
+) -> Result<()> {
+    match should_delete_table(client, args).await {
+        Some(table) if dry_run => {
+            println!("Would delete table {}.{}", args.database, table);
+        }
+        Some(table) => {
+            println!("Deleting table {}.{}", args.database, table);
+            timestream_helper::delete_table(client, &args.database, &table).await?;
+        }
+        None => println!("Table {}.{} does not exist", args.database, args.table),
+    }
+
+    match should_delete_database(client, args).await {
+        Some(database) if dry_run => {
+            println!("Would delete database {}", database);
+        }
+        Some(database) => {
+            println!("Deleting database {}", database);
+            timestream_helper::delete_database(client, &database).await?;
+        }
+        None => println!("Database {} does not exist", args.database),
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = parse_args(&std::env::args().skip(1).collect::<Vec<String>>())?;
+
+    let region = resolve_region();
+    let client = timestream_helper::get_connection(&region, None).await?;
+    do_cleanup(&client, &args, args.dry_run).await
+}
@@ -0,0 +1,137 @@
+//! CSV ingestion sample for Amazon Timestream for LiveAnalytics: reads a
+//! CSV file and writes one single-measure record per row. The column
+//! layout is configurable through `--mapping`, so the sample can ingest
+//! arbitrary CSVs, not just the bundled `data/sample.csv`.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use timestream_sample_app::csv_ingestion::{BuildMode, CsvMapping};
+use timestream_sample_app::{resolve_region, timestream_helper, DATABASE_NAME};
+
+/// Table the CSV sample ingests into.
+const CSV_TABLE_NAME: &str = "csv_metrics";
+
+const USAGE: &str = "Usage: ingestion_csv_sample [--file <path>] [--mapping <spec>] \
+[--current-time] [--multi-measure] [--multi-measure-name <name>]
+
+Reads a CSV file (default data/sample.csv) and ingests one record per row.
+The mapping spec is semicolon-separated key=value entries naming column
+indices, e.g.:
+
+  dimensions=0,1,2;measure_name=3;measure_value=4;measure_type=5;time=6;time_unit=ms
+
+With --current-time, row timestamps are replaced by the current time so
+old files stay within the table's memory store retention.
+
+With --multi-measure, each row becomes one multi-measure record: every
+column outside the dimensions and timestamp becomes a measure value named
+after its header, and the record's measure name comes from
+--multi-measure-name (default metrics).";
+
+struct Args {
+    file: PathBuf,
+    mapping: CsvMapping,
+    current_time: bool,
+    build_mode: BuildMode,
+}
+
+fn parse_args(args: &[String]) -> Result<Args> {
+    let mut file = PathBuf::from("data/sample.csv");
+    let mut mapping = CsvMapping::default();
+    let mut current_time = false;
+    let mut multi_measure = false;
+    let mut multi_measure_name = "metrics".to_string();
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value_of = |flag: &str| {
+            iter.next()
+                .ok_or_else(|| anyhow!("{} requires a value", flag))
+        };
+        match flag.as_str() {
+            "--file" => file = PathBuf::from(value_of("--file")?),
+            "--mapping" => mapping = CsvMapping::parse(value_of("--mapping")?)?,
+            "--current-time" => current_time = true,
+            "--multi-measure" => multi_measure = true,
+            "--multi-measure-name" => {
+                multi_measure_name = value_of("--multi-measure-name")?.clone()
+            }
+            other => return Err(anyhow!("Unrecognized argument: {}\n\n{}", other, USAGE)),
+        }
+    }
+    Ok(Args {
+        file,
+        mapping,
+        current_time,
+        build_mode: if multi_measure {
+            BuildMode::MultiMeasure {
+                measure_name: multi_measure_name,
+            }
+        } else {
+            BuildMode::SingleMeasure
+        },
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = parse_args(&std::env::args().skip(1).collect::<Vec<String>>())?;
+
+    if !args.file.exists() {
+        return Err(anyhow!(
+            "CSV file {} does not exist; pass --file with a path relative \
+            to the current directory",
+            args.file.display()
+        ));
+    }
+    let mut reader = csv::Reader::from_path(&args.file)
+        .with_context(|| format!("Failed to open {}", args.file.display()))?;
+    let header: Vec<String> = reader
+        .headers()
+        .context("Failed to read the CSV header")?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    args.mapping.validate_against_header(&header)?;
+
+    // With --current-time every row is stamped with the current epoch
+    // milliseconds, so the mapping's time unit is overridden to match.
+    let mut mapping = args.mapping;
+    let now_millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)?
+        .as_millis()
+        .to_string();
+    if args.current_time {
+        mapping.time_unit = aws_sdk_timestreamwrite::types::TimeUnit::Milliseconds;
+    }
+
+    let mut records = Vec::new();
+    for (index, row) in reader.records().enumerate() {
+        let row = row.with_context(|| format!("Failed to read CSV row {}", index + 2))?;
+        let mut row: Vec<String> = row.iter().map(str::to_string).collect();
+        if args.current_time {
+            row[mapping.time_column] = now_millis.clone();
+        }
+        records.push(
+            mapping
+                .record_from_row_with_mode(&header, &row, &args.build_mode)
+                .with_context(|| format!("Failed to build record from CSV row {}", index + 2))?,
+        );
+    }
+    println!("Read {} records from {}", records.len(), args.file.display());
+
+    let region = resolve_region();
+    let client = timestream_helper::get_connection(&region, None).await?;
+    timestream_helper::create_database_if_not_exists(&client, DATABASE_NAME).await?;
+    timestream_helper::create_table_if_not_exists(&client, DATABASE_NAME, CSV_TABLE_NAME).await?;
+    timestream_helper::write_record_batches(&client, DATABASE_NAME, CSV_TABLE_NAME, &records)
+        .await?;
+    println!(
+        "Wrote {} records to {}.{}",
+        records.len(),
+        DATABASE_NAME,
+        CSV_TABLE_NAME
+    );
+    Ok(())
+}
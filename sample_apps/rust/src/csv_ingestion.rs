@@ -0,0 +1,366 @@
+//! Helpers for the CSV ingestion sample: a column mapping describing how
+//! CSV columns translate to Timestream dimensions, measure, and
+//! timestamp, so the sample can ingest arbitrary CSV layouts instead of
+//! only the bundled `sample.csv`.
+
+use anyhow::{anyhow, Result};
+use aws_sdk_timestreamwrite as timestream_write;
+use timestream_write::types::{Dimension, MeasureValue, MeasureValueType, Record, TimeUnit};
+
+/// How records are built from rows: one single-measure record per row
+/// using the mapped measure columns, or one multi-measure record per row
+/// grouping every non-dimension, non-timestamp column into a measure
+/// value named after its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildMode {
+    SingleMeasure,
+    MultiMeasure { measure_name: String },
+}
+
+/// Which CSV columns hold which record parts. Dimension columns take
+/// their dimension names from the CSV header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvMapping {
+    pub dimension_columns: Vec<usize>,
+    pub measure_name_column: usize,
+    pub measure_value_column: usize,
+    pub measure_type_column: usize,
+    pub time_column: usize,
+    pub time_unit: TimeUnit,
+}
+
+impl Default for CsvMapping {
+    /// The layout of the bundled `sample.csv`: three dimension columns,
+    /// then measure name, value, and type, then a millisecond timestamp.
+    fn default() -> Self {
+        CsvMapping {
+            dimension_columns: vec![0, 1, 2],
+            measure_name_column: 3,
+            measure_value_column: 4,
+            measure_type_column: 5,
+            time_column: 6,
+            time_unit: TimeUnit::Milliseconds,
+        }
+    }
+}
+
+impl CsvMapping {
+    /// Parses a mapping spec of semicolon-separated `key=value` entries,
+    /// e.g. `dimensions=0,1,2;measure_name=3;measure_value=4;\
+    /// measure_type=5;time=6;time_unit=ms`. All keys are required.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut dimension_columns: Option<Vec<usize>> = None;
+        let mut measure_name_column: Option<usize> = None;
+        let mut measure_value_column: Option<usize> = None;
+        let mut measure_type_column: Option<usize> = None;
+        let mut time_column: Option<usize> = None;
+        let mut time_unit: Option<TimeUnit> = None;
+
+        for entry in spec.split(';') {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Mapping entry {} is not key=value", entry))?;
+            let parse_index = |value: &str| {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("Column index {} is not a number", value))
+            };
+            match key {
+                "dimensions" => {
+                    dimension_columns = Some(
+                        value
+                            .split(',')
+                            .map(parse_index)
+                            .collect::<Result<Vec<usize>>>()?,
+                    )
+                }
+                "measure_name" => measure_name_column = Some(parse_index(value)?),
+                "measure_value" => measure_value_column = Some(parse_index(value)?),
+                "measure_type" => measure_type_column = Some(parse_index(value)?),
+                "time" => time_column = Some(parse_index(value)?),
+                "time_unit" => {
+                    time_unit = Some(match value {
+                        "s" => TimeUnit::Seconds,
+                        "ms" => TimeUnit::Milliseconds,
+                        "us" => TimeUnit::Microseconds,
+                        "ns" => TimeUnit::Nanoseconds,
+                        other => {
+                            return Err(anyhow!(
+                                "time_unit must be one of s, ms, us, ns, got {}",
+                                other
+                            ))
+                        }
+                    })
+                }
+                other => return Err(anyhow!("Unrecognized mapping key {}", other)),
+            }
+        }
+
+        let missing = |key: &str| anyhow!("Mapping is missing the {} entry", key);
+        Ok(CsvMapping {
+            dimension_columns: dimension_columns.ok_or_else(|| missing("dimensions"))?,
+            measure_name_column: measure_name_column.ok_or_else(|| missing("measure_name"))?,
+            measure_value_column: measure_value_column
+                .ok_or_else(|| missing("measure_value"))?,
+            measure_type_column: measure_type_column.ok_or_else(|| missing("measure_type"))?,
+            time_column: time_column.ok_or_else(|| missing("time"))?,
+            time_unit: time_unit.ok_or_else(|| missing("time_unit"))?,
+        })
+    }
+
+    /// Checks that every mapped column index exists in the header.
+    pub fn validate_against_header(&self, header: &[String]) -> Result<()> {
+        let mut indices = self.dimension_columns.clone();
+        indices.extend([
+            self.measure_name_column,
+            self.measure_value_column,
+            self.measure_type_column,
+            self.time_column,
+        ]);
+        for index in indices {
+            if index >= header.len() {
+                return Err(anyhow!(
+                    "Mapping references column {} but the CSV header has only {} columns",
+                    index,
+                    header.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds a single-measure record from one CSV row, naming dimensions
+    /// after their header columns.
+    pub fn record_from_row(&self, header: &[String], row: &[String]) -> Result<Record> {
+        self.record_from_row_with_mode(header, row, &BuildMode::SingleMeasure)
+    }
+
+    /// Builds a record from one CSV row according to `mode`. In
+    /// multi-measure mode the mapped measure columns are not special:
+    /// every column outside the dimension set and the time column becomes
+    /// one measure value, named after its header with an inferred type.
+    pub fn record_from_row_with_mode(
+        &self,
+        header: &[String],
+        row: &[String],
+        mode: &BuildMode,
+    ) -> Result<Record> {
+        if row.len() != header.len() {
+            return Err(anyhow!(
+                "Row has {} columns but the header has {}",
+                row.len(),
+                header.len()
+            ));
+        }
+        let mut record_builder = Record::builder()
+            .time(&row[self.time_column])
+            .time_unit(self.time_unit.clone());
+        match mode {
+            BuildMode::SingleMeasure => {
+                record_builder = record_builder
+                    .measure_name(&row[self.measure_name_column])
+                    .measure_value(&row[self.measure_value_column])
+                    .measure_value_type(parse_measure_type(&row[self.measure_type_column])?);
+            }
+            BuildMode::MultiMeasure { measure_name } => {
+                record_builder = record_builder
+                    .measure_name(measure_name)
+                    .measure_value_type(MeasureValueType::Multi);
+                let mut measure_values = Vec::new();
+                for (index, value) in row.iter().enumerate() {
+                    if index == self.time_column || self.dimension_columns.contains(&index) {
+                        continue;
+                    }
+                    measure_values.push(
+                        MeasureValue::builder()
+                            .name(&header[index])
+                            .value(value)
+                            .r#type(infer_measure_type(value))
+                            .build()?,
+                    );
+                }
+                if measure_values.is_empty() {
+                    return Err(anyhow!(
+                        "Row has no measure columns outside the dimensions and timestamp"
+                    ));
+                }
+                record_builder = record_builder.set_measure_values(Some(measure_values));
+            }
+        }
+        for &index in &self.dimension_columns {
+            record_builder = record_builder.dimensions(
+                Dimension::builder()
+                    .name(&header[index])
+                    .value(&row[index])
+                    .build()?,
+            );
+        }
+        Ok(record_builder.build())
+    }
+}
+
+/// Infers a measure value type from a cell: integers become BIGINT,
+/// other numbers DOUBLE, booleans BOOLEAN, and everything else VARCHAR.
+fn infer_measure_type(value: &str) -> MeasureValueType {
+    if value.parse::<i64>().is_ok() {
+        MeasureValueType::Bigint
+    } else if value.parse::<f64>().is_ok() {
+        MeasureValueType::Double
+    } else if matches!(value, "true" | "false") {
+        MeasureValueType::Boolean
+    } else {
+        MeasureValueType::Varchar
+    }
+}
+
+fn parse_measure_type(value: &str) -> Result<MeasureValueType> {
+    match value.to_uppercase().as_str() {
+        "DOUBLE" => Ok(MeasureValueType::Double),
+        "BIGINT" => Ok(MeasureValueType::Bigint),
+        "VARCHAR" => Ok(MeasureValueType::Varchar),
+        "BOOLEAN" => Ok(MeasureValueType::Boolean),
+        "TIMESTAMP" => Ok(MeasureValueType::Timestamp),
+        other => Err(anyhow!(
+            "Unsupported measure type {}; expected DOUBLE, BIGINT, VARCHAR, BOOLEAN, \
+            or TIMESTAMP",
+            other
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header() -> Vec<String> {
+        ["region", "az", "hostname", "measure_name", "measure_value", "measure_type", "time"]
+            .iter()
+            .map(|column| column.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_mapping_spec() {
+        let mapping = CsvMapping::parse(
+            "dimensions=0,1,2;measure_name=3;measure_value=4;measure_type=5;time=6;time_unit=ms",
+        )
+        .expect("Valid mapping spec must parse");
+        assert_eq!(mapping, CsvMapping::default());
+
+        assert!(CsvMapping::parse("dimensions=0;measure_name=1").is_err());
+        assert!(CsvMapping::parse("bogus").is_err());
+        assert!(CsvMapping::parse(
+            "dimensions=a;measure_name=3;measure_value=4;measure_type=5;time=6;time_unit=ms"
+        )
+        .is_err());
+        assert!(CsvMapping::parse(
+            "dimensions=0;measure_name=3;measure_value=4;measure_type=5;time=6;time_unit=weeks"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_against_header() {
+        let mapping = CsvMapping::default();
+        mapping
+            .validate_against_header(&sample_header())
+            .expect("Default mapping must fit the sample header");
+        let error = mapping
+            .validate_against_header(&sample_header()[..4])
+            .expect_err("A short header must be rejected");
+        assert!(error.to_string().contains("4 columns"));
+    }
+
+    #[test]
+    fn test_record_from_row() {
+        let header = sample_header();
+        let row: Vec<String> =
+            ["us-east-1", "az1", "host1", "cpu_utilization", "13.5", "DOUBLE", "1677605771000"]
+                .iter()
+                .map(|column| column.to_string())
+                .collect();
+        let record = CsvMapping::default()
+            .record_from_row(&header, &row)
+            .expect("Failed to build record from sample row");
+        assert_eq!(record.dimensions().len(), 3);
+        assert_eq!(record.dimensions()[0].name(), "region");
+        assert_eq!(record.dimensions()[0].value(), "us-east-1");
+        assert_eq!(record.dimensions()[2].name(), "hostname");
+        assert_eq!(record.measure_name(), Some("cpu_utilization"));
+        assert_eq!(record.measure_value(), Some("13.5"));
+        assert_eq!(record.measure_value_type(), Some(&MeasureValueType::Double));
+        assert_eq!(record.time(), Some("1677605771000"));
+        assert_eq!(record.time_unit(), Some(&TimeUnit::Milliseconds));
+
+        let short_row = &row[..5];
+        assert!(CsvMapping::default()
+            .record_from_row(&header, short_row)
+            .is_err());
+    }
+
+    #[test]
+    fn test_record_from_row_modes_side_by_side() {
+        let header: Vec<String> =
+            ["region", "hostname", "cpu_utilization", "disk_reads", "status", "time"]
+                .iter()
+                .map(|column| column.to_string())
+                .collect();
+        let row: Vec<String> = ["us-east-1", "host1", "13.5", "1523", "ok", "1677605771000"]
+            .iter()
+            .map(|column| column.to_string())
+            .collect();
+        let mapping = CsvMapping {
+            dimension_columns: vec![0, 1],
+            measure_name_column: 2,
+            measure_value_column: 3,
+            measure_type_column: 4,
+            time_column: 5,
+            time_unit: TimeUnit::Milliseconds,
+        };
+
+        let multi = mapping
+            .record_from_row_with_mode(
+                &header,
+                &row,
+                &BuildMode::MultiMeasure {
+                    measure_name: "metrics".to_string(),
+                },
+            )
+            .expect("Failed to build multi-measure record");
+        assert_eq!(multi.measure_name(), Some("metrics"));
+        assert_eq!(multi.measure_value_type(), Some(&MeasureValueType::Multi));
+        assert_eq!(multi.measure_value(), None);
+        assert_eq!(multi.dimensions().len(), 2);
+        let measures = multi.measure_values();
+        assert_eq!(measures.len(), 3);
+        assert_eq!(measures[0].name(), "cpu_utilization");
+        assert_eq!(measures[0].value(), "13.5");
+        assert_eq!(measures[0].r#type(), &MeasureValueType::Double);
+        assert_eq!(measures[1].name(), "disk_reads");
+        assert_eq!(measures[1].r#type(), &MeasureValueType::Bigint);
+        assert_eq!(measures[2].name(), "status");
+        assert_eq!(measures[2].r#type(), &MeasureValueType::Varchar);
+
+        // In single-measure mode the same row shape uses the mapped
+        // measure columns instead.
+        let single_row: Vec<String> =
+            ["us-east-1", "host1", "cpu_utilization", "13.5", "DOUBLE", "1677605771000"]
+                .iter()
+                .map(|column| column.to_string())
+                .collect();
+        let single = mapping
+            .record_from_row_with_mode(&header, &single_row, &BuildMode::SingleMeasure)
+            .expect("Failed to build single-measure record");
+        assert_eq!(single.measure_name(), Some("cpu_utilization"));
+        assert_eq!(single.measure_value(), Some("13.5"));
+        assert!(single.measure_values().is_empty());
+    }
+
+    #[test]
+    fn test_infer_measure_type() {
+        assert_eq!(infer_measure_type("1523"), MeasureValueType::Bigint);
+        assert_eq!(infer_measure_type("13.5"), MeasureValueType::Double);
+        assert_eq!(infer_measure_type("true"), MeasureValueType::Boolean);
+        assert_eq!(infer_measure_type("ok"), MeasureValueType::Varchar);
+    }
+}
@@ -0,0 +1,19 @@
+//! Shared helpers for the Amazon Timestream for LiveAnalytics Rust
+//! sample application.
+
+pub mod csv_ingestion;
+pub mod query_common;
+pub mod timestream_helper;
+
+/// Database the sample application creates and queries.
+pub const DATABASE_NAME: &str = "rust_sample_db";
+/// Table the sample application ingests into.
+pub const TABLE_NAME: &str = "host_metrics";
+
+/// Resolves the region the sample runs against, from `AWS_REGION` or
+/// `AWS_DEFAULT_REGION`, defaulting to us-east-1.
+pub fn resolve_region() -> String {
+    std::env::var("AWS_REGION")
+        .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|_| "us-east-1".to_string())
+}
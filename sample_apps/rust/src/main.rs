@@ -0,0 +1,41 @@
+//! Getting-started sample for Amazon Timestream for LiveAnalytics:
+//! creates a database and table, ingests a few multi-measure records,
+//! and runs a query over the ingested data.
+
+use anyhow::Result;
+use timestream_sample_app::{
+    query_common, resolve_region, timestream_helper, DATABASE_NAME, TABLE_NAME,
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = query_common::parse_args(&std::env::args().skip(1).collect::<Vec<String>>())?;
+    let region = resolve_region();
+    let write_client = timestream_helper::get_connection(&region, None).await?;
+
+    timestream_helper::create_database_if_not_exists(&write_client, DATABASE_NAME).await?;
+    timestream_helper::create_table_if_not_exists(&write_client, DATABASE_NAME, TABLE_NAME)
+        .await?;
+    timestream_helper::write_sample_records(&write_client, DATABASE_NAME, TABLE_NAME).await?;
+
+    let query_client = query_common::get_connection(&region, None).await?;
+    let since = args.since.as_deref().unwrap_or("15m");
+    let mut query = format!(
+        "SELECT hostname, ROUND(AVG(cpu_utilization), 2) AS avg_cpu, \
+        ROUND(AVG(memory_utilization), 2) AS avg_memory \
+        FROM \"{}\".\"{}\" WHERE time > ago({}) GROUP BY hostname ORDER BY hostname",
+        DATABASE_NAME, TABLE_NAME, since
+    );
+    if let Some(limit) = args.limit {
+        query.push_str(&format!(" LIMIT {}", limit));
+    }
+    println!("Average utilization per host over the last {}:", since);
+    let rows =
+        query_common::run_query_collect(&query_client, &query, args.limit.unwrap_or(i32::MAX))
+            .await?;
+    let mut stdout = std::io::stdout();
+    for row in rows {
+        query_common::write(&mut stdout, row.join(", "))?;
+    }
+    Ok(())
+}
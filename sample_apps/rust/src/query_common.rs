@@ -0,0 +1,650 @@
+//! Helpers shared by the query side of the sample application.
+
+use anyhow::{anyhow, Result};
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_timestreamquery as timestream_query;
+use serde_json::{json, Value};
+use std::io::Write;
+
+/// Loads the SDK configuration for the region. `behavior_version` pins
+/// the SDK behavior major version for deployments that control upgrades;
+/// `None` uses the latest.
+pub async fn load_sdk_config(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> aws_config::SdkConfig {
+    aws_config::defaults(behavior_version.unwrap_or_else(BehaviorVersion::latest))
+        .region(Region::new(region.to_owned()))
+        .load()
+        .await
+}
+
+/// Creates a Timestream query client for the region, with the endpoint
+/// discovery the query API requires enabled and kept fresh in the
+/// background.
+pub async fn get_connection(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> Result<timestream_query::Client> {
+    let config = load_sdk_config(region, behavior_version).await;
+    let (client, reload) = timestream_query::Client::new(&config)
+        .with_endpoint_discovery_enabled()
+        .await
+        .map_err(|error| anyhow!("Failed to enable endpoint discovery: {}", error))?;
+    tokio::spawn(async move {
+        reload.reload_task().await;
+    });
+    Ok(client)
+}
+
+/// Command line options for the query side of the sample.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Args {
+    /// Maximum number of rows to fetch; applied both as the collection
+    /// cap and as an SQL `LIMIT`.
+    pub limit: Option<i32>,
+    /// Time window for the sample query, e.g. `30m` or `2h`, injected as
+    /// `WHERE time > ago(...)`.
+    pub since: Option<String>,
+}
+
+/// Parses `--limit <rows>` and `--since <duration>` from the command
+/// line arguments (without the program name).
+pub fn parse_args(args: &[String]) -> Result<Args> {
+    let mut parsed = Args::default();
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let mut value_of = |flag: &str| {
+            iter.next()
+                .ok_or_else(|| anyhow!("{} requires a value", flag))
+        };
+        match flag.as_str() {
+            "--limit" => {
+                let limit: i32 = value_of("--limit")?
+                    .parse()
+                    .map_err(|_| anyhow!("--limit must be a positive integer"))?;
+                if limit < 1 {
+                    return Err(anyhow!("--limit must be at least 1"));
+                }
+                parsed.limit = Some(limit);
+            }
+            "--since" => {
+                let value = value_of("--since")?;
+                validate_since(value)?;
+                parsed.since = Some(value.to_string());
+            }
+            other => return Err(anyhow!("Unrecognized argument: {}", other)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Validates a duration of the form `<count><s|m|h|d>` (e.g. `30m`,
+/// `2h`, `1d`), the shape Timestream's `ago()` function accepts.
+pub fn validate_since(value: &str) -> Result<()> {
+    let valid = value.is_ascii()
+        && value.len() >= 2
+        && value.ends_with(['s', 'm', 'h', 'd'])
+        && value[..value.len() - 1]
+            .parse::<u64>()
+            .map(|count| count > 0)
+            .unwrap_or(false);
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Invalid duration {}; expected <count><s|m|h|d>, like 30m or 2h",
+            value
+        ))
+    }
+}
+
+/// Writes a line of query output. Generic over `Write` so callers can
+/// target stdout, a file, or an in-memory buffer in tests.
+pub fn write<W: Write>(writer: &mut W, line: String) -> Result<()> {
+    writeln!(writer, "{}", line).map_err(|error| anyhow!("Failed to write output: {}", error))
+}
+
+/// Runs a query, following pagination, and writes each row to the
+/// writer.
+pub async fn run_query<W: Write>(
+    client: &timestream_query::Client,
+    query: &str,
+    writer: &mut W,
+) -> Result<()> {
+    for row in run_query_collect(client, query, i32::MAX).await? {
+        write(writer, row.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Runs a query, following pagination, and returns each row's rendered
+/// datum values so results can be consumed in code. At most `max_rows`
+/// rows are collected; pagination stops early once the cap is reached.
+pub async fn run_query_collect(
+    client: &timestream_query::Client,
+    query: &str,
+    max_rows: i32,
+) -> Result<Vec<Vec<String>>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut next_token: Option<String> = None;
+    loop {
+        let mut request = client.query().query_string(query);
+        if let Some(next_token) = &next_token {
+            request = request.next_token(next_token);
+        }
+        let output = request
+            .send()
+            .await
+            .map_err(|error| anyhow!("Query failed: {:?}", error))?;
+        for row in output.rows() {
+            if rows.len() >= max_rows as usize {
+                return Ok(rows);
+            }
+            rows.push(process_row_values(row));
+        }
+        next_token = output.next_token().map(str::to_string);
+        if next_token.is_none() {
+            return Ok(rows);
+        }
+    }
+}
+
+/// Renders a row as a comma-separated list of its datum values.
+pub fn process_row(row: &timestream_query::types::Row) -> String {
+    process_row_values(row).join(", ")
+}
+
+/// Renders each datum of a row separately.
+pub fn process_row_values(row: &timestream_query::types::Row) -> Vec<String> {
+    row.data().iter().map(process_datum).collect()
+}
+
+/// Sentinel rendered for null datum values.
+pub const NULL_SENTINEL: &str = "NULL";
+
+fn process_datum(datum: &timestream_query::types::Datum) -> String {
+    process_datum_with_sentinel(datum, NULL_SENTINEL)
+}
+
+/// Renders a datum, substituting `sentinel` for nulls. Timestream
+/// reports nulls through `null_value` rather than an absent scalar; a
+/// datum with neither a null marker nor a scalar (an unsupported nested
+/// type) also renders as the sentinel instead of erroring.
+pub fn process_datum_with_sentinel(
+    datum: &timestream_query::types::Datum,
+    sentinel: &str,
+) -> String {
+    if datum.null_value() == Some(true) {
+        return sentinel.to_string();
+    }
+    datum.scalar_value().unwrap_or(sentinel).to_string()
+}
+
+/// Renders a datum according to its column type, descending into time
+/// series, array, and row values. The branches dispatch on which nested
+/// column info the type carries and on the `ScalarType` variant itself,
+/// never on the scalar type rendering as an empty string, so an absent
+/// scalar type cannot route a column down the wrong path.
+pub fn process_column_datum(
+    column_type: &timestream_query::types::Type,
+    datum: &timestream_query::types::Datum,
+) -> String {
+    if datum.null_value() == Some(true) {
+        return NULL_SENTINEL.to_string();
+    }
+    if let Some(value_column) = column_type.time_series_measure_value_column_info() {
+        return process_time_series_type(value_column, datum.time_series_value());
+    }
+    if let Some(element_column) = column_type.array_column_info() {
+        return process_array_type(element_column, datum.array_value());
+    }
+    let row_columns = column_type.row_column_info();
+    if !row_columns.is_empty() {
+        return process_row_type(row_columns, datum.row_value());
+    }
+    process_scalar_type(column_type.scalar_type(), datum)
+}
+
+/// Renders a time series datum as `[{time=..., value=...}, ...]`, with
+/// each point's value rendered under the series' measure value column
+/// type.
+pub fn process_time_series_type(
+    value_column: &timestream_query::types::ColumnInfo,
+    points: &[timestream_query::types::TimeSeriesDataPoint],
+) -> String {
+    let rendered: Vec<String> = points
+        .iter()
+        .map(|point| {
+            let value = match (value_column.r#type(), point.value()) {
+                (Some(value_type), Some(value)) => process_column_datum(value_type, value),
+                _ => NULL_SENTINEL.to_string(),
+            };
+            format!("{{time={}, value={}}}", point.time(), value)
+        })
+        .collect();
+    format!("[{}]", rendered.join(", "))
+}
+
+/// Renders an array datum as `[a, b, ...]` under the element column
+/// type.
+pub fn process_array_type(
+    element_column: &timestream_query::types::ColumnInfo,
+    elements: &[timestream_query::types::Datum],
+) -> String {
+    let rendered: Vec<String> = elements
+        .iter()
+        .map(|element| match element_column.r#type() {
+            Some(element_type) => process_column_datum(element_type, element),
+            None => NULL_SENTINEL.to_string(),
+        })
+        .collect();
+    format!("[{}]", rendered.join(", "))
+}
+
+/// Renders a row datum as `(a, b, ...)`, pairing each nested datum with
+/// its column type.
+pub fn process_row_type(
+    columns: &[timestream_query::types::ColumnInfo],
+    row: Option<&timestream_query::types::Row>,
+) -> String {
+    let Some(row) = row else {
+        return NULL_SENTINEL.to_string();
+    };
+    let rendered: Vec<String> = columns
+        .iter()
+        .zip(row.data())
+        .map(|(column, datum)| match column.r#type() {
+            Some(column_type) => process_column_datum(column_type, datum),
+            None => NULL_SENTINEL.to_string(),
+        })
+        .collect();
+    format!("({})", rendered.join(", "))
+}
+
+/// Renders a scalar datum. Every `ScalarType` variant is matched
+/// explicitly: the types Timestream renders as text pass the scalar
+/// value through, while `UnknownValue` (Timestream's own `UNKNOWN`
+/// scalar type, used for nulls of indeterminate type) and any variant
+/// added to the SDK later render as the null sentinel.
+fn process_scalar_type(
+    scalar_type: Option<&timestream_query::types::ScalarType>,
+    datum: &timestream_query::types::Datum,
+) -> String {
+    use timestream_query::types::ScalarType;
+    match scalar_type {
+        Some(
+            ScalarType::Bigint
+            | ScalarType::Boolean
+            | ScalarType::Date
+            | ScalarType::Double
+            | ScalarType::Integer
+            | ScalarType::IntervalDayToSecond
+            | ScalarType::IntervalYearToMonth
+            | ScalarType::Time
+            | ScalarType::Timestamp
+            | ScalarType::Varchar,
+        ) => datum.scalar_value().unwrap_or(NULL_SENTINEL).to_string(),
+        Some(ScalarType::UnknownValue) => NULL_SENTINEL.to_string(),
+        Some(_) | None => NULL_SENTINEL.to_string(),
+    }
+}
+
+/// Serialization formats a query Lambda client can request through the
+/// `Accept` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryResultFormat {
+    Json,
+    Csv,
+}
+
+impl QueryResultFormat {
+    /// Resolves the format from an `Accept` header value. JSON is the
+    /// default for an absent header or `*/*`.
+    pub fn from_accept(accept: Option<&str>) -> Result<Self> {
+        match accept.map(|value| value.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("*/*") | Some("application/json") => {
+                Ok(QueryResultFormat::Json)
+            }
+            Some("text/csv") => Ok(QueryResultFormat::Csv),
+            Some(other) => Err(anyhow!(
+                "Unsupported Accept value {}; expected application/json or text/csv",
+                other
+            )),
+        }
+    }
+
+    /// The Content-Type header of a response in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            QueryResultFormat::Json => "application/json",
+            QueryResultFormat::Csv => "text/csv",
+        }
+    }
+}
+
+/// Serializes collected query rows in the requested format: a JSON array
+/// of row arrays, or CSV with one line per row.
+pub fn format_rows(rows: &[Vec<String>], format: &QueryResultFormat) -> Result<String> {
+    match format {
+        QueryResultFormat::Json => {
+            serde_json::to_string(rows).map_err(|error| anyhow!("Failed to serialize rows: {}", error))
+        }
+        QueryResultFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for row in rows {
+                writer
+                    .write_record(row)
+                    .map_err(|error| anyhow!("Failed to serialize rows: {}", error))?;
+            }
+            let bytes = writer
+                .into_inner()
+                .map_err(|error| anyhow!("Failed to serialize rows: {}", error))?;
+            String::from_utf8(bytes).map_err(|error| anyhow!("CSV output is not UTF-8: {}", error))
+        }
+    }
+}
+
+/// Lambda-style handler for the query sample, analogous to the write
+/// connector's `lambda_handler`: reads the SQL query from the event body,
+/// the result format from the `Accept` header, runs the query, and
+/// returns an API Gateway response in the requested format.
+pub async fn query_handler(
+    client: &timestream_query::Client,
+    event: &Value,
+) -> Result<Value> {
+    let query = match event["body"].as_str().map(str::trim) {
+        Some(query) if !query.is_empty() => query,
+        _ => {
+            return Ok(json!({
+                "statusCode": 400,
+                "body": "Request body must contain a query",
+            }))
+        }
+    };
+    let accept = event["headers"].as_object().and_then(|headers| {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("accept"))
+            .and_then(|(_, value)| value.as_str())
+    });
+    let format = match QueryResultFormat::from_accept(accept) {
+        Ok(format) => format,
+        Err(error) => {
+            return Ok(json!({ "statusCode": 406, "body": error.to_string() }))
+        }
+    };
+    match run_query_collect(client, query, i32::MAX).await {
+        Ok(rows) => Ok(json!({
+            "statusCode": 200,
+            "headers": { "Content-Type": format.content_type() },
+            "body": format_rows(&rows, &format)?,
+        })),
+        Err(error) => Ok(json!({ "statusCode": 400, "body": error.to_string() })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_sdk_config_accepts_behavior_versions() {
+        // Both the fallback and a pinned behavior version must yield a
+        // configuration a client can be built from; no request is sent.
+        let config = load_sdk_config("us-east-1", None).await;
+        timestream_query::Client::new(&config);
+        let config =
+            load_sdk_config("us-east-1", Some(BehaviorVersion::v2026_01_12())).await;
+        timestream_query::Client::new(&config);
+    }
+
+    #[test]
+    fn test_validate_since() {
+        validate_since("30m").expect("30m is a valid duration");
+        validate_since("2h").expect("2h is a valid duration");
+        validate_since("1d").expect("1d is a valid duration");
+        assert!(validate_since("fifteen minutes").is_err());
+        assert!(validate_since("0h").is_err());
+        assert!(validate_since("m").is_err());
+        assert!(validate_since("15").is_err());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse_args(&[
+            "--limit".to_string(),
+            "25".to_string(),
+            "--since".to_string(),
+            "2h".to_string(),
+        ])
+        .expect("Valid arguments must parse");
+        assert_eq!(args.limit, Some(25));
+        assert_eq!(args.since.as_deref(), Some("2h"));
+
+        assert_eq!(parse_args(&[]).expect("No arguments is valid"), Args::default());
+        assert!(parse_args(&["--limit".to_string(), "0".to_string()]).is_err());
+        assert!(parse_args(&["--since".to_string(), "soon".to_string()]).is_err());
+        assert!(parse_args(&["--bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_process_row_values() {
+        let row = timestream_query::types::Row::builder()
+            .data(
+                timestream_query::types::Datum::builder()
+                    .scalar_value("host-1")
+                    .build(),
+            )
+            .data(
+                timestream_query::types::Datum::builder()
+                    .scalar_value("42.0")
+                    .build(),
+            )
+            .build()
+            .expect("Failed to build row");
+        assert_eq!(process_row_values(&row), vec!["host-1", "42.0"]);
+        assert_eq!(process_row(&row), "host-1, 42.0");
+    }
+
+    #[test]
+    fn test_process_null_datum() {
+        let null_datum = timestream_query::types::Datum::builder()
+            .null_value(true)
+            .build();
+        assert_eq!(process_datum_with_sentinel(&null_datum, NULL_SENTINEL), "NULL");
+        assert_eq!(process_datum_with_sentinel(&null_datum, "-"), "-");
+
+        // A null marker wins even if a scalar value is also present.
+        let null_with_scalar = timestream_query::types::Datum::builder()
+            .null_value(true)
+            .scalar_value("42.0")
+            .build();
+        assert_eq!(
+            process_datum_with_sentinel(&null_with_scalar, NULL_SENTINEL),
+            "NULL"
+        );
+
+        // Null datums render through the row path without erroring.
+        let row = timestream_query::types::Row::builder()
+            .data(
+                timestream_query::types::Datum::builder()
+                    .scalar_value("host-1")
+                    .build(),
+            )
+            .data(
+                timestream_query::types::Datum::builder()
+                    .null_value(true)
+                    .build(),
+            )
+            .build()
+            .expect("Failed to build row");
+        assert_eq!(process_row(&row), "host-1, NULL");
+    }
+
+    #[test]
+    fn test_process_scalar_column_datum() {
+        let varchar_type = timestream_query::types::Type::builder()
+            .scalar_type(timestream_query::types::ScalarType::Varchar)
+            .build();
+        let datum = timestream_query::types::Datum::builder()
+            .scalar_value("host-1")
+            .build();
+        assert_eq!(process_column_datum(&varchar_type, &datum), "host-1");
+
+        // Timestream's own UNKNOWN scalar type and a type with no scalar
+        // type at all both render as the sentinel, never as a nested
+        // value.
+        let unknown_type = timestream_query::types::Type::builder()
+            .scalar_type(timestream_query::types::ScalarType::UnknownValue)
+            .build();
+        assert_eq!(process_column_datum(&unknown_type, &datum), NULL_SENTINEL);
+        let empty_type = timestream_query::types::Type::builder().build();
+        assert_eq!(process_column_datum(&empty_type, &datum), NULL_SENTINEL);
+    }
+
+    #[test]
+    fn test_process_time_series_type() {
+        let value_column = timestream_query::types::ColumnInfo::builder()
+            .r#type(
+                timestream_query::types::Type::builder()
+                    .scalar_type(timestream_query::types::ScalarType::Double)
+                    .build(),
+            )
+            .build();
+        let series_type = timestream_query::types::Type::builder()
+            .time_series_measure_value_column_info(value_column)
+            .build();
+        let datum = timestream_query::types::Datum::builder()
+            .time_series_value(
+                timestream_query::types::TimeSeriesDataPoint::builder()
+                    .time("2023-02-28 18:16:11.000000000")
+                    .value(
+                        timestream_query::types::Datum::builder()
+                            .scalar_value("42.0")
+                            .build(),
+                    )
+                    .build()
+                    .expect("Failed to build data point"),
+            )
+            .build();
+        assert_eq!(
+            process_column_datum(&series_type, &datum),
+            "[{time=2023-02-28 18:16:11.000000000, value=42.0}]"
+        );
+    }
+
+    #[test]
+    fn test_process_array_type() {
+        let element_column = timestream_query::types::ColumnInfo::builder()
+            .r#type(
+                timestream_query::types::Type::builder()
+                    .scalar_type(timestream_query::types::ScalarType::Bigint)
+                    .build(),
+            )
+            .build();
+        let array_type = timestream_query::types::Type::builder()
+            .array_column_info(element_column)
+            .build();
+        let datum = timestream_query::types::Datum::builder()
+            .array_value(
+                timestream_query::types::Datum::builder()
+                    .scalar_value("1")
+                    .build(),
+            )
+            .array_value(
+                timestream_query::types::Datum::builder()
+                    .null_value(true)
+                    .build(),
+            )
+            .build();
+        assert_eq!(process_column_datum(&array_type, &datum), "[1, NULL]");
+    }
+
+    #[test]
+    fn test_process_row_type() {
+        let column = |scalar_type| {
+            timestream_query::types::ColumnInfo::builder()
+                .r#type(
+                    timestream_query::types::Type::builder()
+                        .scalar_type(scalar_type)
+                        .build(),
+                )
+                .build()
+        };
+        let row_type = timestream_query::types::Type::builder()
+            .row_column_info(column(timestream_query::types::ScalarType::Varchar))
+            .row_column_info(column(timestream_query::types::ScalarType::Double))
+            .build();
+        let datum = timestream_query::types::Datum::builder()
+            .row_value(
+                timestream_query::types::Row::builder()
+                    .data(
+                        timestream_query::types::Datum::builder()
+                            .scalar_value("host-1")
+                            .build(),
+                    )
+                    .data(
+                        timestream_query::types::Datum::builder()
+                            .scalar_value("42.0")
+                            .build(),
+                    )
+                    .build()
+                    .expect("Failed to build row"),
+            )
+            .build();
+        assert_eq!(process_column_datum(&row_type, &datum), "(host-1, 42.0)");
+
+        // A row type without a row value renders as the sentinel.
+        let empty_datum = timestream_query::types::Datum::builder().build();
+        assert_eq!(process_column_datum(&row_type, &empty_datum), NULL_SENTINEL);
+    }
+
+    #[test]
+    fn test_query_result_format_from_accept() {
+        assert_eq!(
+            QueryResultFormat::from_accept(None).unwrap(),
+            QueryResultFormat::Json
+        );
+        assert_eq!(
+            QueryResultFormat::from_accept(Some("*/*")).unwrap(),
+            QueryResultFormat::Json
+        );
+        assert_eq!(
+            QueryResultFormat::from_accept(Some("application/json")).unwrap(),
+            QueryResultFormat::Json
+        );
+        assert_eq!(
+            QueryResultFormat::from_accept(Some("Text/CSV")).unwrap(),
+            QueryResultFormat::Csv
+        );
+        assert!(QueryResultFormat::from_accept(Some("application/xml")).is_err());
+    }
+
+    #[test]
+    fn test_format_rows() {
+        let rows = vec![
+            vec!["host-1".to_string(), "42.0".to_string()],
+            vec!["host-2".to_string(), "a, quoted value".to_string()],
+        ];
+        assert_eq!(
+            format_rows(&rows, &QueryResultFormat::Json).unwrap(),
+            r#"[["host-1","42.0"],["host-2","a, quoted value"]]"#
+        );
+        assert_eq!(
+            format_rows(&rows, &QueryResultFormat::Csv).unwrap(),
+            "host-1,42.0\nhost-2,\"a, quoted value\"\n"
+        );
+    }
+
+    #[test]
+    fn test_write_to_in_memory_buffer() {
+        let mut buffer: Vec<u8> = Vec::new();
+        write(&mut buffer, "host-1, 42.0".to_string()).expect("Failed to write");
+        write(&mut buffer, "host-2, 43.5".to_string()).expect("Failed to write");
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "host-1, 42.0\nhost-2, 43.5\n"
+        );
+    }
+}
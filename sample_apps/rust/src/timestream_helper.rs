@@ -0,0 +1,296 @@
+//! Helpers shared by the ingestion side of the sample application.
+
+use anyhow::{anyhow, Result};
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_timestreamwrite as timestream_write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Hours records stay in the memory store of the sample table.
+const MEM_STORE_RETENTION_HOURS: i64 = 24;
+/// Days records stay in the magnetic store of the sample table.
+const MAG_STORE_RETENTION_DAYS: i64 = 7;
+
+/// Loads the SDK configuration for the region. `behavior_version` pins
+/// the SDK behavior major version for deployments that control upgrades;
+/// `None` uses the latest.
+pub async fn load_sdk_config(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> aws_config::SdkConfig {
+    aws_config::defaults(behavior_version.unwrap_or_else(BehaviorVersion::latest))
+        .region(Region::new(region.to_owned()))
+        .load()
+        .await
+}
+
+/// Creates a Timestream write client for the region, with the endpoint
+/// discovery the write API requires enabled and kept fresh in the
+/// background.
+pub async fn get_connection(
+    region: &str,
+    behavior_version: Option<BehaviorVersion>,
+) -> Result<timestream_write::Client> {
+    let config = load_sdk_config(region, behavior_version).await;
+    let (client, reload) = timestream_write::Client::new(&config)
+        .with_endpoint_discovery_enabled()
+        .await
+        .map_err(|error| anyhow!("Failed to enable endpoint discovery: {}", error))?;
+    tokio::spawn(async move {
+        reload.reload_task().await;
+    });
+    Ok(client)
+}
+
+/// Creates the database unless it already exists.
+pub async fn create_database_if_not_exists(
+    client: &timestream_write::Client,
+    database_name: &str,
+) -> Result<()> {
+    if client
+        .describe_database()
+        .database_name(database_name)
+        .send()
+        .await
+        .is_ok()
+    {
+        println!("Database {} already exists", database_name);
+        return Ok(());
+    }
+    println!("Creating database {}", database_name);
+    client
+        .create_database()
+        .database_name(database_name)
+        .send()
+        .await
+        .map_err(|error| anyhow!("Failed to create database: {:?}", error))?;
+    Ok(())
+}
+
+/// Creates the table with the sample retention settings unless it
+/// already exists.
+pub async fn create_table_if_not_exists(
+    client: &timestream_write::Client,
+    database_name: &str,
+    table_name: &str,
+) -> Result<()> {
+    if client
+        .describe_table()
+        .database_name(database_name)
+        .table_name(table_name)
+        .send()
+        .await
+        .is_ok()
+    {
+        println!("Table {} already exists", table_name);
+        return Ok(());
+    }
+    println!("Creating table {}", table_name);
+    client
+        .create_table()
+        .database_name(database_name)
+        .table_name(table_name)
+        .retention_properties(
+            timestream_write::types::RetentionProperties::builder()
+                .memory_store_retention_period_in_hours(MEM_STORE_RETENTION_HOURS)
+                .magnetic_store_retention_period_in_days(MAG_STORE_RETENTION_DAYS)
+                .build()?,
+        )
+        .send()
+        .await
+        .map_err(|error| anyhow!("Failed to create table: {:?}", error))?;
+    Ok(())
+}
+
+/// Deletes a table. An already-absent table is treated as success, so
+/// cleanup paths can call this unconditionally.
+pub async fn delete_table(
+    client: &timestream_write::Client,
+    database_name: &str,
+    table_name: &str,
+) -> Result<()> {
+    match client
+        .delete_table()
+        .database_name(database_name)
+        .table_name(table_name)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error)
+            if error
+                .as_service_error()
+                .is_some_and(|error| error.is_resource_not_found_exception()) =>
+        {
+            Ok(())
+        }
+        Err(error) => Err(anyhow!("Failed to delete table: {:?}", error)),
+    }
+}
+
+/// Deletes a database. An already-absent database is treated as success,
+/// so cleanup paths can call this unconditionally.
+pub async fn delete_database(
+    client: &timestream_write::Client,
+    database_name: &str,
+) -> Result<()> {
+    match client
+        .delete_database()
+        .database_name(database_name)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error)
+            if error
+                .as_service_error()
+                .is_some_and(|error| error.is_resource_not_found_exception()) =>
+        {
+            Ok(())
+        }
+        Err(error) => Err(anyhow!("Failed to delete database: {:?}", error)),
+    }
+}
+
+/// Builds a batch of sample records: cpu and memory utilization readings
+/// for a few hosts, stamped with the current time.
+pub fn build_sample_records() -> Result<Vec<timestream_write::types::Record>> {
+    let now_millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)?
+        .as_millis()
+        .to_string();
+    let mut records: Vec<timestream_write::types::Record> = Vec::new();
+    for (host_index, hostname) in ["host-1", "host-2", "host-3"].iter().enumerate() {
+        records.push(
+            timestream_write::types::Record::builder()
+                .dimensions(
+                    timestream_write::types::Dimension::builder()
+                        .name("hostname")
+                        .value(*hostname)
+                        .build()?,
+                )
+                .dimensions(
+                    timestream_write::types::Dimension::builder()
+                        .name("region")
+                        .value("us-east-1")
+                        .build()?,
+                )
+                .measure_name("host_metric")
+                .measure_value_type(timestream_write::types::MeasureValueType::Multi)
+                .measure_values(
+                    timestream_write::types::MeasureValue::builder()
+                        .name("cpu_utilization")
+                        .value(format!("{}", 35.0 + host_index as f64 * 10.0))
+                        .r#type(timestream_write::types::MeasureValueType::Double)
+                        .build()?,
+                )
+                .measure_values(
+                    timestream_write::types::MeasureValue::builder()
+                        .name("memory_utilization")
+                        .value(format!("{}", 40.0 + host_index as f64 * 5.0))
+                        .r#type(timestream_write::types::MeasureValueType::Double)
+                        .build()?,
+                )
+                .time(&now_millis)
+                .time_unit(timestream_write::types::TimeUnit::Milliseconds)
+                .build(),
+        );
+    }
+    Ok(records)
+}
+
+/// Timestream's per-WriteRecords-call record limit.
+pub const MAX_RECORDS_PER_WRITE: usize = 100;
+
+/// Splits records into batches of at most `MAX_RECORDS_PER_WRITE`; the
+/// final batch carries the remainder.
+pub fn record_batches(
+    records: &[timestream_write::types::Record],
+) -> std::slice::Chunks<'_, timestream_write::types::Record> {
+    records.chunks(MAX_RECORDS_PER_WRITE)
+}
+
+/// Writes records in batches of at most `MAX_RECORDS_PER_WRITE`.
+pub async fn write_record_batches(
+    client: &timestream_write::Client,
+    database_name: &str,
+    table_name: &str,
+    records: &[timestream_write::types::Record],
+) -> Result<()> {
+    for batch in record_batches(records) {
+        client
+            .write_records()
+            .database_name(database_name)
+            .table_name(table_name)
+            .set_records(Some(batch.to_vec()))
+            .send()
+            .await
+            .map_err(|error| anyhow!("Failed to write records: {:?}", error))?;
+    }
+    Ok(())
+}
+
+/// Writes the sample records into the table.
+pub async fn write_sample_records(
+    client: &timestream_write::Client,
+    database_name: &str,
+    table_name: &str,
+) -> Result<()> {
+    let records = build_sample_records()?;
+    println!("Writing {} sample records", records.len());
+    client
+        .write_records()
+        .database_name(database_name)
+        .table_name(table_name)
+        .set_records(Some(records))
+        .send()
+        .await
+        .map_err(|error| anyhow!("Failed to write records: {:?}", error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_sdk_config_accepts_behavior_versions() {
+        let config = load_sdk_config("us-east-1", None).await;
+        timestream_write::Client::new(&config);
+        let config =
+            load_sdk_config("us-east-1", Some(BehaviorVersion::v2026_01_12())).await;
+        timestream_write::Client::new(&config);
+    }
+
+    #[test]
+    fn test_record_batches_boundaries() {
+        let record = |index: usize| {
+            timestream_write::types::Record::builder()
+                .measure_name("cpu_utilization")
+                .measure_value(format!("{}", index))
+                .measure_value_type(timestream_write::types::MeasureValueType::Double)
+                .time("1677605771000")
+                .time_unit(timestream_write::types::TimeUnit::Milliseconds)
+                .build()
+        };
+        let records: Vec<_> = (0..250).map(record).collect();
+
+        let sizes: Vec<usize> = record_batches(&records[..99]).map(<[_]>::len).collect();
+        assert_eq!(sizes, vec![99]);
+        let sizes: Vec<usize> = record_batches(&records[..100]).map(<[_]>::len).collect();
+        assert_eq!(sizes, vec![100]);
+        let sizes: Vec<usize> = record_batches(&records[..101]).map(<[_]>::len).collect();
+        assert_eq!(sizes, vec![100, 1]);
+        // The final partial batch must not be dropped.
+        let sizes: Vec<usize> = record_batches(&records).map(<[_]>::len).collect();
+        assert_eq!(sizes, vec![100, 100, 50]);
+        assert_eq!(record_batches(&records[..0]).count(), 0);
+    }
+
+    #[test]
+    fn test_build_sample_records() {
+        let records = build_sample_records().expect("Failed to build sample records");
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].dimensions().len(), 2);
+        assert_eq!(records[0].measure_values().len(), 2);
+    }
+}